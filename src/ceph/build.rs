@@ -0,0 +1,14 @@
+extern crate pkg_config;
+
+fn main() {
+	// Ask pkg-config where librados lives, so it's found even when Ceph is
+	// installed in a non-standard prefix (point PKG_CONFIG_PATH at the
+	// directory containing librados.pc). On success pkg-config emits the
+	// cargo:rustc-link-search and cargo:rustc-link-lib directives itself.
+	match pkg_config::Config::new().probe("librados") {
+		Ok(_) => {},
+		// Otherwise, fall back to the bare #[link(name = "rados")] in
+		// rados.rs and the linker's default search path.
+		Err(e) => println!("cargo:warning=pkg-config couldn't find librados, using default search path: {}", e),
+	}
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use rados::{IoCtx, RadosError, RadosIo};
+
+/// How much `CachedIoCtx::read()` asks for at a time
+const READ_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Wraps a `RadosIo` (an `IoCtx` by default) with an LRU cache of the
+/// objects most recently read in full, for read-heavy workloads on objects
+/// that rarely change.
+///
+/// Writes and removes through the wrapper invalidate the cached copy of the
+/// object first. Writes made any other way, through another context or by
+/// another client, are not noticed: a cached object is served as it was
+/// when read until it's evicted or invalidated, so only cache objects no
+/// one else modifies.
+///
+/// # Examples
+///
+/// ```rust
+/// use ceph::cache::CachedIoCtx;
+/// use ceph::mock::MockIo;
+/// use ceph::rados::RadosIo;
+///
+/// let cache = CachedIoCtx::new(MockIo::new(), 16);
+/// cache.write_full("hw", b"Hello, world.").unwrap();
+/// assert_eq!(cache.read("hw").unwrap(), b"Hello, world.");
+///
+/// // The second read is served from the cache...
+/// let reads = cache.inner().read_count();
+/// assert_eq!(cache.read("hw").unwrap(), b"Hello, world.");
+/// assert_eq!(cache.inner().read_count(), reads);
+///
+/// // ...until the object is written through the cache
+/// cache.write_full("hw", b"Goodbye.").unwrap();
+/// assert_eq!(cache.read("hw").unwrap(), b"Goodbye.");
+/// ```
+pub struct CachedIoCtx<I: RadosIo = IoCtx> {
+	inner: I,
+	capacity: usize,
+	cache: Mutex<Lru>,
+}
+
+/// The cached objects, with the tick each was last used at
+struct Lru {
+	objects: HashMap<String, (Vec<u8>, u64)>,
+	tick: u64,
+}
+
+impl<I: RadosIo> CachedIoCtx<I> {
+	/// Cache up to `capacity` objects read through `inner`
+	pub fn new(inner: I, capacity: usize) -> CachedIoCtx<I> {
+		CachedIoCtx {
+			inner: inner,
+			capacity: capacity,
+			cache: Mutex::new(Lru { objects: HashMap::new(), tick: 0 }),
+		}
+	}
+
+	/// The wrapped context, e.g. for operations the cache doesn't cover.
+	/// Writes made through it directly don't invalidate the cache.
+	pub fn inner(&self) -> &I {
+		&self.inner
+	}
+
+	/// Read the whole `oid` object, from the cache if it's there.
+	///
+	/// # Returns
+	///
+	/// * `Ok(contents)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn read(&self, oid: &str) -> Result<Vec<u8>, RadosError> {
+		{
+			let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+			cache.tick += 1;
+			let tick = cache.tick;
+			if let Some(entry) = cache.objects.get_mut(oid) {
+				entry.1 = tick;
+				return Ok(entry.0.clone());
+			}
+		}
+		let data = self.read_uncached(oid)?;
+		self.insert(oid, &data);
+		Ok(data)
+	}
+
+	/// Forget the cached copy of the `oid` object, if any
+	pub fn invalidate(&self, oid: &str) {
+		self.cache.lock().unwrap_or_else(PoisonError::into_inner).objects.remove(oid);
+	}
+
+	/// Forget all the cached objects
+	pub fn clear(&self) {
+		self.cache.lock().unwrap_or_else(PoisonError::into_inner).objects.clear();
+	}
+
+	fn read_uncached(&self, oid: &str) -> Result<Vec<u8>, RadosError> {
+		let mut data = Vec::new();
+		loop {
+			let filled = data.len();
+			data.resize(filled + READ_CHUNK_SIZE, 0);
+			let len = self.inner.read_at(oid, &mut data[filled..], filled as u64)?;
+			data.truncate(filled + len);
+			if len == 0 {
+				return Ok(data);
+			}
+		}
+	}
+
+	fn insert(&self, oid: &str, data: &[u8]) {
+		if self.capacity == 0 {
+			return;
+		}
+		let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+		if !cache.objects.contains_key(oid) && cache.objects.len() >= self.capacity {
+			let oldest = cache.objects.iter().min_by_key(|&(_, entry)| entry.1).map(|(oid, _)| oid.clone());
+			if let Some(oldest) = oldest {
+				cache.objects.remove(&oldest);
+			}
+		}
+		cache.tick += 1;
+		let tick = cache.tick;
+		cache.objects.insert(oid.to_string(), (data.to_vec(), tick));
+	}
+}
+
+impl<I: RadosIo> RadosIo for CachedIoCtx<I> {
+	fn write_full(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		self.invalidate(oid);
+		self.inner.write_full(oid, data)
+	}
+
+	fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError> {
+		self.invalidate(oid);
+		self.inner.write_at(oid, data, offset)
+	}
+
+	fn append(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		self.invalidate(oid);
+		self.inner.append(oid, data)
+	}
+
+	/// Served from the cached copy of the object if there is one
+	fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError> {
+		{
+			let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+			if let Some(entry) = cache.objects.get(oid) {
+				let object = &entry.0;
+				let offset = offset as usize;
+				if offset >= object.len() {
+					return Ok(0);
+				}
+				let len = buf.len().min(object.len() - offset);
+				buf[..len].copy_from_slice(&object[offset..offset + len]);
+				return Ok(len);
+			}
+		}
+		self.inner.read_at(oid, buf, offset)
+	}
+
+	fn remove(&self, oid: &str) -> Result<(), RadosError> {
+		self.invalidate(oid);
+		self.inner.remove(oid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use mock::MockIo;
+
+	#[test]
+	fn second_read_is_cached() {
+		let cache = CachedIoCtx::new(MockIo::new(), 4);
+		cache.write_full("a", b"first").unwrap();
+		assert_eq!(cache.read("a").unwrap(), b"first");
+		let reads = cache.inner().read_count();
+		assert_eq!(cache.read("a").unwrap(), b"first");
+		let mut buf = [0u8; 3];
+		assert_eq!(cache.read_at("a", &mut buf, 2).unwrap(), 3);
+		assert_eq!(&buf, b"rst");
+		assert_eq!(cache.inner().read_count(), reads);
+	}
+
+	#[test]
+	fn writes_invalidate() {
+		let cache = CachedIoCtx::new(MockIo::new(), 4);
+		cache.write_full("a", b"first").unwrap();
+		cache.read("a").unwrap();
+		cache.write_at("a", b"F", 0).unwrap();
+		assert_eq!(cache.read("a").unwrap(), b"First");
+		cache.append("a", b"!").unwrap();
+		assert_eq!(cache.read("a").unwrap(), b"First!");
+		cache.remove("a").unwrap();
+		assert_eq!(cache.read("a"), Err(RadosError::ObjectNotFound));
+
+		// Writes bypassing the cache aren't noticed
+		cache.write_full("b", b"cached").unwrap();
+		cache.read("b").unwrap();
+		cache.inner().write_full("b", b"behind its back").unwrap();
+		assert_eq!(cache.read("b").unwrap(), b"cached");
+		cache.invalidate("b");
+		assert_eq!(cache.read("b").unwrap(), b"behind its back");
+	}
+
+	#[test]
+	fn least_recently_used_is_evicted() {
+		let cache = CachedIoCtx::new(MockIo::new(), 2);
+		for oid in &["a", "b", "c"] {
+			cache.write_full(oid, oid.as_bytes()).unwrap();
+		}
+		cache.read("a").unwrap();
+		cache.read("b").unwrap();
+		// "a" was used last, so "b" goes
+		cache.read("a").unwrap();
+		cache.read("c").unwrap();
+		let reads = cache.inner().read_count();
+		cache.read("a").unwrap();
+		cache.read("c").unwrap();
+		assert_eq!(cache.inner().read_count(), reads);
+		cache.read("b").unwrap();
+		assert!(cache.inner().read_count() > reads);
+	}
+
+	#[test]
+	fn zero_capacity_caches_nothing() {
+		let cache = CachedIoCtx::new(MockIo::new(), 0);
+		cache.write_full("a", b"data").unwrap();
+		cache.read("a").unwrap();
+		let reads = cache.inner().read_count();
+		cache.read("a").unwrap();
+		assert!(cache.inner().read_count() > reads);
+	}
+}
@@ -0,0 +1,88 @@
+use rados::{library_version, Version};
+
+/// The librados version of Hammer, the first with `rados_watch2()` and
+/// `rados_nobjects_list_*`
+const HAMMER: Version = Version { major: 0, minor: 69, extra: 0 };
+
+/// The librados version of Luminous, which added
+/// `rados_application_enable()`, `rados_read_op_checksum()` and
+/// `rados_read_op_omap_get_vals2()`, and is what the `luminous` feature
+/// links against
+const LUMINOUS: Version = Version { major: 3, minor: 0, extra: 0 };
+
+/// Which librados APIs the linked library provides, see `available()`.
+///
+/// A library too old for the APIs this crate was built to use doesn't
+/// link at all; this tells which optional APIs can be relied on at
+/// runtime, e.g. to explain why a call fails with `EOPNOTSUPP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureSet {
+	/// The version of the linked librados, `None` if it couldn't be told
+	pub version: Option<Version>,
+	/// Watches and notifies, as used by `rados::ResilientWatch`
+	pub watch2: bool,
+	/// Listing objects with `rados_nobjects_list_*`, as `IoCtx::list_objects()`
+	/// does with the `luminous` feature
+	pub nobjects_list: bool,
+	/// Tagging pools with the application using them
+	pub application_enable: bool,
+	/// Computing checksums on the OSDs, for `IoCtx::checksum()`
+	pub checksum: bool,
+}
+
+/// Find out which librados APIs are usable, from the version of the
+/// linked library.
+///
+/// Note that this is the version of the librados API, which doesn't follow
+/// Ceph releases: it has been `3.0.0` since Luminous. If the version can't
+/// be told, every feature is reported missing.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ceph::features;
+///
+/// let features = features::available();
+/// if !features.checksum {
+/// 	println!("librados {:?} can't compute checksums", features.version);
+/// }
+/// ```
+pub fn available() -> FeatureSet {
+	features_of(library_version())
+}
+
+/// The APIs a librados of `version` provides, see `available()`
+fn features_of(version: Option<Version>) -> FeatureSet {
+	let at_least = |threshold: Version| version.map_or(false, |v| v >= threshold);
+	FeatureSet {
+		version: version,
+		watch2: at_least(HAMMER),
+		nobjects_list: at_least(HAMMER),
+		application_enable: at_least(LUMINOUS),
+		checksum: cfg!(feature = "luminous") && at_least(LUMINOUS),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn features_follow_version() {
+		let none = features_of(None);
+		assert_eq!(none.version, None);
+		assert!(!none.watch2 && !none.nobjects_list && !none.application_enable && !none.checksum);
+
+		let firefly = features_of(Some(Version::new(0, 68, 3)));
+		assert!(!firefly.watch2 && !firefly.nobjects_list && !firefly.application_enable && !firefly.checksum);
+
+		let jewel = features_of(Some(Version::new(0, 69, 1)));
+		assert!(jewel.watch2 && jewel.nobjects_list);
+		assert!(!jewel.application_enable && !jewel.checksum);
+
+		let luminous = features_of(Some(LUMINOUS));
+		assert_eq!(luminous.version, Some(LUMINOUS));
+		assert!(luminous.watch2 && luminous.nobjects_list && luminous.application_enable);
+		assert_eq!(luminous.checksum, cfg!(feature = "luminous"));
+	}
+}
@@ -1,6 +1,26 @@
 #![feature(core, convert, libc, cstr_to_str)]
 extern crate libc;
 extern crate core;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate bincode;
+#[cfg(feature = "futures")]
+extern crate futures;
+#[macro_use]
+extern crate bitflags;
+#[macro_use]
+extern crate serde_json;
 
+pub mod cache;
 pub mod ceph;
+pub mod crc32c;
+pub mod features;
+pub mod lock;
+pub mod mock;
+pub mod multi_pool;
 pub mod rados;
+pub mod record_file;
+mod sha256;
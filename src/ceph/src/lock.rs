@@ -0,0 +1,290 @@
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rados::{IoCtx, RadosError};
+
+/// `ClsLockType::LOCK_EXCLUSIVE` from `cls/lock/cls_lock_types.h`
+const LOCK_EXCLUSIVE: u8 = 1;
+
+/// The `CEPH_ENTITY_TYPE_*` names, from `include/msgr.h`
+const ENTITY_TYPES: &[(u8, &str)] = &[(0x01, "mon"), (0x02, "mds"), (0x04, "osd"), (0x08, "client"), (0x10, "mgr")];
+
+/// Makes every cookie handed out by this process unique
+static NEXT_COOKIE: AtomicUsize = AtomicUsize::new(0);
+
+/// An exclusive advisory lock on an object, taken through the `lock` object
+/// class, as librbd does. The lock is released when the guard is dropped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags};
+/// use ceph::lock::ObjectLock;
+///
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// # let ioctx = cluster.create_ioctx("data").unwrap();
+/// {
+/// 	let _lock = ObjectLock::exclusive(&ioctx, "hw", "writer", None).unwrap();
+/// 	ioctx.write("hw", "Hello, world.").unwrap();
+/// } // unlocked here
+/// ```
+pub struct ObjectLock<'a> {
+	ioctx: &'a IoCtx,
+	oid: String,
+	name: String,
+	cookie: String,
+}
+
+/// Who holds a lock on an object, see `ObjectLock::info()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockInfo {
+	/// The holders of the lock, empty if no one holds it
+	pub lockers: Vec<Locker>,
+	/// Whether the lock is held exclusively, rather than shared
+	pub exclusive: bool,
+	/// The tag the lock was taken with
+	pub tag: String,
+}
+
+/// A holder of a lock, see `LockInfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locker {
+	/// The client holding the lock, e.g. `"client.4123"`
+	pub entity: String,
+	/// The cookie the lock was taken with, see `ObjectLock::cookie()`
+	pub cookie: String,
+	/// When the OSDs expire the lock, `None` if it's held until released
+	pub expiration: Option<SystemTime>,
+}
+
+impl<'a> ObjectLock<'a> {
+	/// Take the `name` exclusive lock on the `oid` object.
+	///
+	/// If `duration` is given the OSDs expire the lock after that long,
+	/// otherwise it's held until released.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectLock)` on success
+	/// * `Err(RadosError)` on failure, `EBUSY` if someone else holds the
+	///   lock
+	pub fn exclusive(ioctx: &'a IoCtx, oid: &str, name: &str, duration: Option<Duration>) -> Result<ObjectLock<'a>, RadosError> {
+		let cookie = format!("ceph-rs.{}.{}", process::id(), NEXT_COOKIE.fetch_add(1, Ordering::SeqCst));
+		let duration = duration.unwrap_or(Duration::from_secs(0));
+
+		// cls_lock_lock_op
+		let mut op = Vec::new();
+		encode_str(&mut op, name);
+		op.push(LOCK_EXCLUSIVE);
+		encode_str(&mut op, &cookie);
+		// tag
+		encode_str(&mut op, "");
+		// description
+		encode_str(&mut op, "");
+		// duration, as a utime_t
+		encode_u32(&mut op, duration.as_secs() as u32);
+		encode_u32(&mut op, duration.subsec_nanos());
+		// flags
+		op.push(0);
+
+		ioctx.exec(oid, "lock", "lock", &versioned(1, 1, &op))?;
+		Ok(ObjectLock {
+			ioctx: ioctx,
+			oid: oid.to_string(),
+			name: name.to_string(),
+			cookie: cookie,
+		})
+	}
+
+	/// Get who holds the `name` lock on the `oid` object, with the `lock`
+	/// object class's `get_info` method.
+	///
+	/// The lockers' addresses and descriptions aren't decoded.
+	///
+	/// # Returns
+	///
+	/// * `Ok(LockInfo)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::ObjectNotFound` if the
+	///   object doesn't exist
+	pub fn info(ioctx: &IoCtx, oid: &str, name: &str) -> Result<LockInfo, RadosError> {
+		// cls_lock_get_info_op
+		let mut op = Vec::new();
+		encode_str(&mut op, name);
+		let out = ioctx.exec(oid, "lock", "get_info", &versioned(1, 1, &op))?;
+		decode_info(&out)
+	}
+
+	/// The cookie identifying this holder of the lock, unique to the guard
+	pub fn cookie(&self) -> &str {
+		&self.cookie
+	}
+
+	/// Release the lock now, reporting any failure. Dropping the guard does
+	/// the same but ignores errors.
+	pub fn unlock(mut self) -> Result<(), RadosError> {
+		let result = self.release();
+		// Keep Drop from unlocking again
+		self.cookie.clear();
+		result
+	}
+
+	fn release(&self) -> Result<(), RadosError> {
+		// cls_lock_unlock_op
+		let mut op = Vec::new();
+		encode_str(&mut op, &self.name);
+		encode_str(&mut op, &self.cookie);
+		self.ioctx.exec(&self.oid, "lock", "unlock", &versioned(1, 1, &op)).map(|_| ())
+	}
+}
+
+impl<'a> Drop for ObjectLock<'a> {
+	fn drop(&mut self) {
+		if !self.cookie.is_empty() {
+			let _ = self.release();
+		}
+	}
+}
+
+fn encode_u32(buf: &mut Vec<u8>, n: u32) {
+	buf.extend_from_slice(&[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]);
+}
+
+fn encode_str(buf: &mut Vec<u8>, s: &str) {
+	encode_u32(buf, s.len() as u32);
+	buf.extend_from_slice(s.as_bytes());
+}
+
+/// Decode a `cls_lock_get_info_reply`
+fn decode_info(out: &[u8]) -> Result<LockInfo, RadosError> {
+	let mut reply = Decoder(out);
+	let mut reply = Decoder(reply.versioned()?);
+	let count = reply.u32()?;
+	let mut lockers = Vec::new();
+	for _ in 0..count {
+		// locker_id_t
+		let mut id = Decoder(reply.versioned()?);
+		let entity_type = id.u8()?;
+		let entity_num = id.u64()? as i64;
+		let cookie = id.string()?;
+		// locker_info_t, of which only the expiration comes before the
+		// variable length address
+		let mut info = Decoder(reply.versioned()?);
+		let (secs, nsecs) = (info.u32()?, info.u32()?);
+		let entity = match ENTITY_TYPES.iter().find(|&&(t, _)| t == entity_type) {
+			Some(&(_, type_name)) => format!("{}.{}", type_name, entity_num),
+			None => format!("{}.{}", entity_type, entity_num),
+		};
+		lockers.push(Locker {
+			entity: entity,
+			cookie: cookie,
+			expiration: if secs == 0 && nsecs == 0 {
+				None
+			} else {
+				Some(UNIX_EPOCH + Duration::new(secs as u64, nsecs))
+			},
+		});
+	}
+	let lock_type = reply.u8()?;
+	let tag = reply.string()?;
+	Ok(LockInfo { lockers: lockers, exclusive: lock_type == LOCK_EXCLUSIVE, tag: tag })
+}
+
+/// Reads the fields of an object class reply, in Ceph's encoding
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], RadosError> {
+		if self.0.len() < len {
+			return Err(RadosError::UnexpectedReply("truncated lock class reply".to_string()));
+		}
+		let (head, rest) = self.0.split_at(len);
+		self.0 = rest;
+		Ok(head)
+	}
+
+	fn u8(&mut self) -> Result<u8, RadosError> {
+		self.take(1).map(|b| b[0])
+	}
+
+	fn u32(&mut self) -> Result<u32, RadosError> {
+		self.take(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+	}
+
+	fn u64(&mut self) -> Result<u64, RadosError> {
+		let low = self.u32()? as u64;
+		let high = self.u32()? as u64;
+		Ok(low | high << 32)
+	}
+
+	fn string(&mut self) -> Result<String, RadosError> {
+		let len = self.u32()? as usize;
+		self.take(len).map(|b| String::from_utf8_lossy(b).into_owned())
+	}
+
+	/// The payload of a `versioned()` struct
+	fn versioned(&mut self) -> Result<&'a [u8], RadosError> {
+		// The struct version and compat version
+		self.take(2)?;
+		let len = self.u32()? as usize;
+		self.take(len)
+	}
+}
+
+/// Wrap `payload` the way Ceph's `ENCODE_START(version, compat)` /
+/// `ENCODE_FINISH` do
+fn versioned(version: u8, compat: u8, payload: &[u8]) -> Vec<u8> {
+	let mut buf = vec![version, compat];
+	encode_u32(&mut buf, payload.len() as u32);
+	buf.extend_from_slice(payload);
+	buf
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn locker(entity_type: u8, num: u64, cookie: &str, secs: u32) -> Vec<u8> {
+		let mut id = vec![entity_type];
+		encode_u32(&mut id, num as u32);
+		encode_u32(&mut id, (num >> 32) as u32);
+		encode_str(&mut id, cookie);
+		let mut info = Vec::new();
+		encode_u32(&mut info, secs);
+		encode_u32(&mut info, 0);
+		// An address and a description, which are skipped
+		info.extend_from_slice(&[1, 1, 1, 0, 0, 0, 0]);
+		encode_str(&mut info, "description");
+		let mut buf = versioned(1, 1, &id);
+		buf.extend(versioned(1, 1, &info));
+		buf
+	}
+
+	#[test]
+	fn decode_get_info_reply() {
+		let mut reply = Vec::new();
+		encode_u32(&mut reply, 2);
+		reply.extend(locker(0x08, 4123, "first", 0));
+		reply.extend(locker(0x40, 7, "second", 1000));
+		reply.push(LOCK_EXCLUSIVE);
+		encode_str(&mut reply, "tag");
+		let info = decode_info(&versioned(1, 1, &reply)).unwrap();
+		assert_eq!(info, LockInfo {
+			lockers: vec![
+				Locker { entity: "client.4123".to_string(), cookie: "first".to_string(), expiration: None },
+				Locker { entity: "64.7".to_string(), cookie: "second".to_string(),
+					expiration: Some(UNIX_EPOCH + Duration::from_secs(1000)) },
+			],
+			exclusive: true,
+			tag: "tag".to_string(),
+		});
+
+		let reply = versioned(1, 1, &reply);
+		for len in 0..reply.len() {
+			match decode_info(&reply[..len]) {
+				Err(RadosError::UnexpectedReply(_)) => {},
+				other => panic!("decoded {:?} from {} bytes", other, len),
+			}
+		}
+	}
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rados::{RadosError, RadosIo};
+
+/// An in-memory stand-in for an `IoCtx`, for testing code written against
+/// `RadosIo` without a cluster.
+///
+/// Objects are kept in a `HashMap`; reading or removing a missing object
+/// fails with `RadosError::ObjectNotFound`, like librados does.
+///
+/// # Examples
+///
+/// ```rust
+/// use ceph::mock::MockIo;
+/// use ceph::rados::{RadosError, RadosIo};
+///
+/// // Code under test only depends on the trait...
+/// fn save_greeting<I: RadosIo>(io: &I, name: &str) -> Result<(), RadosError> {
+/// 	io.write_full(name, format!("Hello, {}.", name).as_bytes())
+/// }
+///
+/// // ...so it can be exercised against the mock
+/// let io = MockIo::new();
+/// save_greeting(&io, "world").unwrap();
+/// assert_eq!(io.get("world"), Some(b"Hello, world.".to_vec()));
+/// assert_eq!(io.remove("nobody"), Err(RadosError::ObjectNotFound));
+/// ```
+pub struct MockIo {
+	objects: Mutex<HashMap<String, Vec<u8>>>,
+	reads: AtomicUsize,
+}
+
+impl MockIo {
+	pub fn new() -> MockIo {
+		MockIo { objects: Mutex::new(HashMap::new()), reads: AtomicUsize::new(0) }
+	}
+
+	/// The current contents of the `oid` object, if it exists
+	pub fn get(&self, oid: &str) -> Option<Vec<u8>> {
+		self.objects.lock().unwrap_or_else(PoisonError::into_inner).get(oid).cloned()
+	}
+
+	/// How many times `read_at()` has been called, e.g. to check that a
+	/// caching layer doesn't read
+	pub fn read_count(&self) -> usize {
+		self.reads.load(Ordering::SeqCst)
+	}
+}
+
+impl RadosIo for MockIo {
+	fn write_full(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		self.objects.lock().unwrap_or_else(PoisonError::into_inner).insert(oid.to_string(), data.to_vec());
+		Ok(())
+	}
+
+	fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError> {
+		let mut objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+		let object = objects.entry(oid.to_string()).or_insert_with(Vec::new);
+		let offset = offset as usize;
+		if object.len() < offset + data.len() {
+			object.resize(offset + data.len(), 0);
+		}
+		object[offset..offset + data.len()].copy_from_slice(data);
+		Ok(())
+	}
+
+	fn append(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		let mut objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+		objects.entry(oid.to_string()).or_insert_with(Vec::new).extend_from_slice(data);
+		Ok(())
+	}
+
+	fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError> {
+		self.reads.fetch_add(1, Ordering::SeqCst);
+		let objects = self.objects.lock().unwrap_or_else(PoisonError::into_inner);
+		let object = match objects.get(oid) {
+			Some(object) => object,
+			None => return Err(RadosError::ObjectNotFound),
+		};
+		let offset = offset as usize;
+		if offset >= object.len() {
+			return Ok(0);
+		}
+		let len = buf.len().min(object.len() - offset);
+		buf[..len].copy_from_slice(&object[offset..offset + len]);
+		Ok(len)
+	}
+
+	fn remove(&self, oid: &str) -> Result<(), RadosError> {
+		match self.objects.lock().unwrap_or_else(PoisonError::into_inner).remove(oid) {
+			Some(_) => Ok(()),
+			None => Err(RadosError::ObjectNotFound),
+		}
+	}
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rados::{Cluster, IoCtx, RadosError, RadosIo};
+
+/// Routes reads and writes to the pools of a cluster by name, opening an
+/// `IoCtx` for each pool the first time it's used and keeping it for
+/// later calls, for applications spread over many pools.
+///
+/// The contexts are opened with `Cluster::create_ioctx()`, so they start
+/// from the defaults (default namespace, no snapshot, no timeout); use
+/// `ioctx()` to change their settings or for operations not covered here.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags};
+/// use ceph::multi_pool::MultiPool;
+///
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// let pools = MultiPool::new(&cluster);
+/// pools.write("users", "alice", b"{}").unwrap();
+/// pools.write("logs", "2016-01-01", b"started").unwrap();
+/// assert_eq!(pools.read("users", "alice", 2).unwrap(), b"{}");
+/// ```
+pub struct MultiPool<'a> {
+	cluster: &'a Cluster,
+	ioctxs: Mutex<HashMap<String, Arc<IoCtx>>>,
+}
+
+impl<'a> MultiPool<'a> {
+	/// Route to the pools of `cluster`, which must stay connected
+	pub fn new(cluster: &'a Cluster) -> MultiPool<'a> {
+		MultiPool { cluster: cluster, ioctxs: Mutex::new(HashMap::new()) }
+	}
+
+	/// Get the context of the `pool` pool, opening it if it isn't yet.
+	///
+	/// # Returns
+	///
+	/// * `Ok(IoCtx)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::PoolNotFound` if there's
+	///   no such pool
+	pub fn ioctx(&self, pool: &str) -> Result<Arc<IoCtx>, RadosError> {
+		if let Some(ioctx) = self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner).get(pool) {
+			return Ok(ioctx.clone());
+		}
+		// Opened without the lock, so a slow pool lookup doesn't hold up
+		// the other pools; if another thread opened it meanwhile, its
+		// context is kept and this one dropped
+		let ioctx = Arc::new(self.cluster.create_ioctx(pool)?);
+		let mut ioctxs = self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner);
+		Ok(ioctxs.entry(pool.to_string()).or_insert(ioctx).clone())
+	}
+
+	/// Replace the contents of the `oid` object of the `pool` pool with
+	/// `data`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn write(&self, pool: &str, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		RadosIo::write_full(&*self.ioctx(pool)?, oid, data)
+	}
+
+	/// Read up to `len` bytes from the start of the `oid` object of the
+	/// `pool` pool.
+	///
+	/// # Returns
+	///
+	/// * `Ok(data)` with the bytes read on success
+	/// * `Err(RadosError)` on failure
+	pub fn read(&self, pool: &str, oid: &str, len: usize) -> Result<Vec<u8>, RadosError> {
+		let mut buf = vec![0u8; len];
+		let n = self.ioctx(pool)?.read_at(oid, &mut buf, 0)?;
+		buf.truncate(n);
+		Ok(buf)
+	}
+
+	/// Close the context of the `pool` pool, if it's open, e.g. after the
+	/// pool was deleted. It's opened again by the next call using it.
+	pub fn forget(&self, pool: &str) {
+		self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner).remove(pool);
+	}
+}
@@ -1,23 +1,201 @@
-use std::iter::repeat;
-use std::ffi::{CStr, CString};
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::mem;
+use std::error::Error;
+use std::ffi::{CStr, CString, NulError};
 use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::env;
+use std::path::PathBuf;
 use std::ptr;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use core::fmt::Debug;
 use core::fmt::Formatter;
 
 use libc::c_void;
 use libc::c_char;
+use libc::c_uchar;
 use libc::c_int;
+use libc::c_uint;
 use libc::size_t;
 use libc::strerror;
 
+#[cfg(feature = "bytes")]
+use bytes::{Buf, Bytes, BytesMut};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use bincode;
+#[cfg(feature = "futures")]
+use futures::{Future, FutureExt, SinkExt, Stream};
+#[cfg(feature = "futures")]
+use futures::channel::{mpsc, oneshot};
+#[cfg(feature = "futures")]
+use futures::executor::block_on;
+use serde_json;
+use sha256;
+use crc32c;
+use serde_json::Value;
+
 #[allow(non_camel_case_types)]
 type c_void_ptr = *const c_void;
 #[allow(non_camel_case_types)]
 type rados_t = c_void_ptr;
 #[allow(non_camel_case_types)]
 type rados_ioctx_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_write_op_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_read_op_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_list_ctx_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_xattrs_iter_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_omap_iter_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_completion_t = c_void_ptr;
+#[allow(non_camel_case_types)]
+type rados_snap_t = u64;
+#[allow(non_camel_case_types)]
+type rados_callback_t = Option<extern "C" fn(cb: rados_completion_t, arg: *mut c_void)>;
+#[allow(non_camel_case_types)]
+type rados_watchcb2_t = Option<extern "C" fn(arg: *mut c_void, notify_id: u64, handle: u64,
+	notifier_id: u64, data: *mut c_void, data_len: size_t)>;
+#[allow(non_camel_case_types)]
+type rados_watcherrcb_t = Option<extern "C" fn(pre: *mut c_void, cookie: u64, err: c_int)>;
+
+bitflags! {
+	/// Hints for the OSDs about how the data of a write will be used
+	/// (`LIBRADOS_OP_FLAG_FADVISE_*`). These are advisory, and don't change
+	/// the result of the write.
+	pub struct WriteFlags: u32 {
+		/// The data will be accessed in random order
+		const FADVISE_RANDOM = 0x4;
+		/// The data will be accessed sequentially
+		const FADVISE_SEQUENTIAL = 0x8;
+		/// The data will be accessed in the near future
+		const FADVISE_WILLNEED = 0x10;
+		/// The data will not be accessed in the near future
+		const FADVISE_DONTNEED = 0x20;
+		/// The data will only be accessed once, so don't cache it
+		const FADVISE_NOCACHE = 0x40;
+	}
+}
+
+bitflags! {
+	/// Hints for the OSDs about how the data of a read will be used
+	/// (`LIBRADOS_OP_FLAG_FADVISE_*`). These are advisory, and don't change
+	/// the data returned.
+	pub struct ReadFlags: u32 {
+		/// The data will be accessed in random order
+		const FADVISE_RANDOM = 0x4;
+		/// The data will be accessed sequentially
+		const FADVISE_SEQUENTIAL = 0x8;
+		/// The data will be accessed in the near future
+		const FADVISE_WILLNEED = 0x10;
+		/// The data will not be accessed in the near future
+		const FADVISE_DONTNEED = 0x20;
+		/// The data will only be accessed once, so don't cache it
+		const FADVISE_NOCACHE = 0x40;
+	}
+}
+
+bitflags! {
+	/// Flags for `Cluster::create()`. librados reserves the argument for
+	/// future expansion and defines no flags yet, so for now this is always
+	/// `CreateFlags::empty()`; flags added by later releases can be passed
+	/// as raw bits with `CreateFlags::from(bits)`.
+	pub struct CreateFlags: u64 {
+	}
+}
+
+impl Default for CreateFlags {
+	fn default() -> CreateFlags {
+		CreateFlags::empty()
+	}
+}
+
+impl From<u64> for CreateFlags {
+	/// Keeps all of `bits`, including ones this crate doesn't know about
+	fn from(bits: u64) -> CreateFlags {
+		// Unknown bits are only passed through to rados_create2()
+		unsafe { CreateFlags::from_bits_unchecked(bits) }
+	}
+}
+
+/// Compare xattr values for equality, see rados_write_op_cmpxattr()
+const LIBRADOS_CMPXATTR_OP_EQ: u8 = 1;
+
+/// Fail a create operation if the object already exists
+const LIBRADOS_CREATE_EXCLUSIVE: c_int = 1;
+/// Don't fail a create operation if the object already exists
+const LIBRADOS_CREATE_IDEMPOTENT: c_int = 0;
+
+/// Usage information for a pool, as filled in by `rados_ioctx_pool_stat()`
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct PoolStat {
+	/// space used in bytes
+	pub num_bytes: u64,
+	/// space used in KB
+	pub num_kb: u64,
+	/// number of objects in the pool
+	pub num_objects: u64,
+	/// number of clones of objects
+	pub num_object_clones: u64,
+	/// num_objects * num_replicas
+	pub num_object_copies: u64,
+	/// number of objects missing on primary
+	pub num_objects_missing_on_primary: u64,
+	/// number of objects found on no OSDs
+	pub num_objects_unfound: u64,
+	/// number of objects replicated fewer times than they should be
+	/// (but found on at least one OSD)
+	pub num_objects_degraded: u64,
+	/// number of objects read
+	pub num_rd: u64,
+	/// objects read in KB
+	pub num_rd_kb: u64,
+	/// number of objects written
+	pub num_wr: u64,
+	/// objects written in KB
+	pub num_wr_kb: u64,
+	/// bytes originally provided by user
+	#[cfg(feature = "nautilus")]
+	pub num_user_bytes: u64,
+	/// bytes passed compression
+	#[cfg(feature = "nautilus")]
+	pub compressed_bytes_orig: u64,
+	/// bytes resulted after compression
+	#[cfg(feature = "nautilus")]
+	pub compressed_bytes: u64,
+	/// bytes allocated at storage
+	#[cfg(feature = "nautilus")]
+	pub compressed_bytes_alloc: u64,
+}
+
+/// Cluster-wide usage information, as filled in by `rados_cluster_stat()`
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct ClusterStat {
+	/// total device size
+	pub kb: u64,
+	/// total used
+	pub kb_used: u64,
+	/// total available/free
+	pub kb_avail: u64,
+	/// number of objects
+	pub num_objects: u64,
+}
 
 #[link(name = "rados")]
 #[allow(dead_code)]
@@ -126,6 +304,37 @@ extern "C" {
 	/// * 0 on success, negative error code on failure
 	fn rados_conf_parse_argv(cluster: rados_t, argc: c_int, argv: *const *const c_char) -> c_int;
 
+	/// Set a configuration option
+	///
+	/// @param cluster cluster handle to configure
+	/// @param option option to set
+	/// @param value value of the option
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -ENOENT when the option is not a Ceph configuration option
+	fn rados_conf_set(cluster: rados_t, option: *const c_char, value: *const c_char) -> c_int;
+
+	/// Get the value of a configuration option
+	///
+	/// @param cluster configuration to read
+	/// @param option which option to read
+	/// @param buf where to write the configuration value
+	/// @param len the size of buf in bytes
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -ENAMETOOLONG if the buffer is too short to contain the
+	/// requested value
+	fn rados_conf_get(cluster: rados_t, option: *const c_char, buf: *mut c_char, len: size_t) -> c_int;
+
+	/// Read usage info about the cluster
+	///
+	/// This tells you total space, space used, space available, and number
+	/// of objects. These are not updated immediately when data is written,
+	/// they are eventually consistent.
+	///
+	/// @param cluster cluster to query
+	/// @param result where to store the results
+	/// @returns 0 on success, negative error code on failure
+	fn rados_cluster_stat(cluster: rados_t, result: *mut ClusterStat) -> c_int;
+
 	/// Get the fsid of the cluster as a hexadecimal string.
 	///
 	/// The fsid is a unique id of an entire Ceph cluster.
@@ -142,7 +351,249 @@ extern "C" {
 	/// * `-ERANGE` if the buffer is too short to contain the fsid
 	fn rados_cluster_fsid(cluster: rados_t, buf: *mut c_char, len: size_t) -> c_int;
 
+	/// Get a global id for current instance
+	///
+	/// This id is a unique representation of current connection to the cluster
+	///
+	/// @param cluster cluster handle
+	/// @returns instance global id
+	fn rados_get_instance_id(cluster: rados_t) -> u64;
+
+	/// Send a monitor command.
+	///
+	/// The result buffers are allocated on the heap; the caller is
+	/// expected to release that memory with rados_buffer_free(). The
+	/// buffer and length pointers can all be NULL, in which case they are
+	/// not filled in.
+	///
+	/// @param cluster cluster handle
+	/// @param cmd an array of char *'s representing the command
+	/// @param cmdlen count of valid entries in cmd
+	/// @param inbuf any bulk input data (crush map, etc.)
+	/// @param inbuflen input buffer length
+	/// @param outbuf double pointer to output buffer
+	/// @param outbuflen pointer to output buffer length
+	/// @param outs double pointer to status string
+	/// @param outslen pointer to status string length
+	/// @returns 0 on success, negative error code on failure
+	fn rados_mon_command(cluster: rados_t, cmd: *const *const c_char, cmdlen: size_t,
+		inbuf: *const c_char, inbuflen: size_t,
+		outbuf: *mut *mut c_char, outbuflen: *mut size_t,
+		outs: *mut *mut c_char, outslen: *mut size_t) -> c_int;
+
+	/// Send a command to a specific placement group
+	///
+	/// This is the same as rados_mon_command(), but the command is sent to
+	/// the primary OSD for the placement group named by pgstr.
+	///
+	/// @param cluster cluster handle
+	/// @param pgstr the placement group to send the command to
+	/// @param cmd an array of char *'s representing the command
+	/// @param cmdlen count of valid entries in cmd
+	/// @param inbuf any bulk input data (crush map, etc.)
+	/// @param inbuflen input buffer length
+	/// @param outbuf double pointer to output buffer
+	/// @param outbuflen pointer to output buffer length
+	/// @param outs double pointer to status string
+	/// @param outslen pointer to status string length
+	/// @returns 0 on success, negative error code on failure
+	fn rados_pg_command(cluster: rados_t, pgstr: *const c_char, cmd: *const *const c_char, cmdlen: size_t,
+		inbuf: *const c_char, inbuflen: size_t,
+		outbuf: *mut *mut c_char, outbuflen: *mut size_t,
+		outs: *mut *mut c_char, outslen: *mut size_t) -> c_int;
+
+	/// Send a command to a specific OSD
+	///
+	/// This is the same as rados_mon_command(), but the command is sent to
+	/// the OSD with id osdid.
+	///
+	/// @param cluster cluster handle
+	/// @param osdid the id of the OSD to send the command to
+	/// @param cmd an array of char *'s representing the command
+	/// @param cmdlen count of valid entries in cmd
+	/// @param inbuf any bulk input data (crush map, etc.)
+	/// @param inbuflen input buffer length
+	/// @param outbuf double pointer to output buffer
+	/// @param outbuflen pointer to output buffer length
+	/// @param outs double pointer to status string
+	/// @param outslen pointer to status string length
+	/// @returns 0 on success, negative error code on failure
+	fn rados_osd_command(cluster: rados_t, osdid: c_int, cmd: *const *const c_char, cmdlen: size_t,
+		inbuf: *const c_char, inbuflen: size_t,
+		outbuf: *mut *mut c_char, outbuflen: *mut size_t,
+		outs: *mut *mut c_char, outslen: *mut size_t) -> c_int;
+
+	/// Free a rados-allocated buffer
+	///
+	/// Release memory allocated by librados calls like rados_mon_command().
+	///
+	/// @param buf buffer pointer
+	fn rados_buffer_free(buf: *mut c_char);
+
+	/// Create a pool with default settings
+	///
+	/// The default crush rule is rule 0.
+	///
+	/// @param cluster the cluster in which the pool will be created
+	/// @param pool_name the name of the new pool
+	/// @returns 0 on success, negative error code on failure
+	fn rados_pool_create(cluster: rados_t, pool_name: *const c_char) -> c_int;
+
 	fn rados_ioctx_create(cluster: c_void_ptr, poolname: *const c_char, ioctx: &rados_ioctx_t) -> c_int;
+
+	/// Create an io context
+	///
+	/// The io context allows you to perform operations within a particular
+	/// pool. For more details see rados_ioctx_t.
+	///
+	/// @param cluster which cluster the pool is in
+	/// @param pool_id which pool to access
+	/// @param ioctx where to store the io context
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_create2(cluster: rados_t, pool_id: i64, ioctx: *mut rados_ioctx_t) -> c_int;
+
+	/// Get the pool id of the io context
+	///
+	/// @param io the io context to query
+	/// @returns the id of the pool the io context uses
+	fn rados_ioctx_get_id(io: rados_ioctx_t) -> i64;
+
+	/// Set the namespace for objects within an io context
+	///
+	/// The namespace specification further refines a pool into different
+	/// domains. The mapping of objects to pgs is also based on this
+	/// value.
+	///
+	/// @param io the io context to change
+	/// @param nspace the name to use as the namespace, or NULL use the
+	/// default namespace
+	fn rados_ioctx_set_namespace(io: rados_ioctx_t, nspace: *const c_char);
+
+	/// Get the namespace for objects within the io context
+	///
+	/// @param io the io context to query
+	/// @param buf pointer to buffer where name will be stored
+	/// @param maxlen size of buffer where name will be stored
+	/// @returns length of string stored, or -ERANGE if buffer too small
+	fn rados_ioctx_get_namespace(io: rados_ioctx_t, buf: *mut c_char, maxlen: c_uint) -> c_int;
+
+	/// Set the io context to try writes even when the cluster or pool is
+	/// full, instead of blocking (or failing with -ENOSPC / -EDQUOT).
+	///
+	/// @param io the io context to change
+	fn rados_set_osdmap_full_try(io: rados_ioctx_t);
+
+	/// Undo rados_set_osdmap_full_try()
+	///
+	/// @param io the io context to change
+	fn rados_unset_osdmap_full_try(io: rados_ioctx_t);
+
+	/// Set the snapshot from which reads are performed.
+	///
+	/// Subsequent reads will return data as it was at the time of that
+	/// snapshot.
+	///
+	/// @param io the io context to change
+	/// @param snap the id of the snapshot to set, or LIBRADOS_SNAP_HEAD for no
+	/// snapshot (i.e. normal operation)
+	fn rados_ioctx_snap_set_read(io: rados_ioctx_t, snap: rados_snap_t);
+
+	/// List all the pool snapshots of a pool
+	///
+	/// @param io the pool to read from
+	/// @param snaps where to store the results
+	/// @param maxlen the number of rados_snap_t that fit in the snaps array
+	/// @returns number of snapshots on success, negative error code on failure
+	/// @returns -ERANGE is returned if the snaps array is too short
+	fn rados_ioctx_snap_list(io: rados_ioctx_t, snaps: *mut rados_snap_t, maxlen: c_int) -> c_int;
+
+	/// Get the name of a pool snapshot
+	///
+	/// @param io the pool to read from
+	/// @param id the snapshot to find
+	/// @param name where to store the result
+	/// @param maxlen the size of the name array
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -ERANGE if the name array is too small
+	fn rados_ioctx_snap_get_name(io: rados_ioctx_t, id: rados_snap_t, name: *mut c_char, maxlen: c_int) -> c_int;
+
+	/// Find when a pool snapshot occurred
+	///
+	/// @param io the pool the snapshot was taken in
+	/// @param id the snapshot to lookup
+	/// @param t where to store the result
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_snap_get_stamp(io: rados_ioctx_t, id: rados_snap_t, t: *mut libc::time_t) -> c_int;
+
+	/// Allocate an ID for a self-managed snapshot
+	///
+	/// Get a unique ID to put in the snaphot context to create a
+	/// snapshot. A clone of an object is not created until a write with
+	/// the new snapshot context is completed.
+	///
+	/// @param io the pool in which the snapshot will exist
+	/// @param snapid where to store the newly allocated snapshot ID
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_selfmanaged_snap_create(io: rados_ioctx_t, snapid: *mut rados_snap_t) -> c_int;
+
+	/// Remove a self-managed snapshot
+	///
+	/// This increases the snapshot sequence number, which will cause
+	/// snapshots to be removed lazily.
+	///
+	/// @param io the pool in which the snapshot will exist
+	/// @param snapid where to store the newly allocated snapshot ID
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_selfmanaged_snap_remove(io: rados_ioctx_t, snapid: rados_snap_t) -> c_int;
+
+	/// Set the snapshot context for use when writing to objects
+	///
+	/// This is stored in the io context, and applies to all future writes.
+	///
+	/// @param io the io context to change
+	/// @param seq the newest snapshot sequence number for the pool
+	/// @param snaps array of snapshots in sorted by descending id
+	/// @param num_snaps how many snaphosts are in the snaps array
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -EINVAL if snaps are not in descending order
+	fn rados_ioctx_selfmanaged_snap_set_write_ctx(io: rados_ioctx_t, seq: rados_snap_t,
+		snaps: *mut rados_snap_t, num_snaps: c_int) -> c_int;
+
+	/// Get pool usage statistics
+	///
+	/// Fills in a rados_pool_stat_t after querying the cluster.
+	///
+	/// @param io determines which pool to query
+	/// @param stats where to store the results
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_pool_stat(io: rados_ioctx_t, stats: *mut PoolStat) -> c_int;
+
+	/// Get the cluster handle used by this rados_ioctx_t
+	/// Note that this is a weak reference, and should not
+	/// be destroyed via rados_shutdown().
+	///
+	/// @param io the io context
+	/// @returns the cluster handle for this io context
+	fn rados_ioctx_get_cluster(io: rados_ioctx_t) -> rados_t;
+
+	/// Get the name of the pool
+	///
+	/// @param io the io context
+	/// @param buf where to store the pool name
+	/// @param maxlen size of buffer where name will be stored
+	/// @returns length of string stored, or -ERANGE if buffer too small
+	fn rados_ioctx_get_pool_name(io: rados_ioctx_t, buf: *mut c_char, maxlen: c_int) -> c_int;
+
+	/// Write *len* bytes from *buf* into the *oid* object, starting at
+	/// offset *off*. The value of *len* must be <= UINT_MAX/2.
+	///
+	/// @note This will never return a positive value not equal to len.
+	/// @param io the io context in which the write will occur
+	/// @param oid name of the object
+	/// @param buf data to write
+	/// @param len length of the data, in bytes
+	/// @param off byte offset in the object to begin writing at
+	/// @returns 0 on success, negative error code on failure
 	fn rados_write(io: rados_ioctx_t, oid: *const c_char, buf: *const c_char, len: size_t, offset: u64) -> c_int;
 
 	/// Write *len* bytes from *buf* into the *oid* object. The value of
@@ -173,6 +624,15 @@ extern "C" {
  	fn rados_read(io: rados_ioctx_t, oid: *const c_char,
  		buf: *mut c_char, len: size_t, offset: u64) -> c_int;
 
+	/// Get object stats (size/mtime)
+	///
+	/// @param io ioctx
+	/// @param o object name
+	/// @param psize where to store object size
+	/// @param pmtime where to store modification time
+	/// @returns 0 on success, negative error code on failure
+	fn rados_stat(io: rados_ioctx_t, o: *const c_char, psize: *mut u64, pmtime: *mut libc::time_t) -> c_int;
+
 	/// Get the value of an extended attribute on an object.
 	///
 	/// @param io the context in which the attribute is read
@@ -195,6 +655,62 @@ extern "C" {
 	fn rados_setxattr(io: rados_ioctx_t, oid: *const c_char,
 		name: *const c_char, buf: *const c_char, len: size_t) -> c_int;
 
+	/// Execute an OSD class method on an object
+	///
+	/// The OSD has a plugin mechanism for performing complicated
+	/// operations on an object atomically. These plugins are called
+	/// classes. This function allows librados users to call the custom
+	/// methods. The input and output formats are defined by the class.
+	/// Classes in ceph.git can be found in src/cls subdirectories
+	///
+	/// @param io the context in which to call the method
+	/// @param oid the object to call the method on
+	/// @param cls the name of the class
+	/// @param method the name of the method
+	/// @param in_buf where to find input
+	/// @param in_len length of in_buf in bytes
+	/// @param buf where to store output
+	/// @param out_len length of buf in bytes
+	/// @returns the length of the output, or
+	/// -ERANGE if out_buf does not have enough space to store it (For methods that return data). For
+	/// methods that don't return data, the return value is
+	/// method-specific.
+	fn rados_exec(io: rados_ioctx_t, oid: *const c_char, cls: *const c_char, method: *const c_char,
+		in_buf: *const c_char, in_len: size_t, buf: *mut c_char, out_len: size_t) -> c_int;
+
+	/// Start iterating over xattrs on an object.
+	///
+	/// @post iter is a valid iterator
+	///
+	/// @param io the context in which to list xattrs
+	/// @param oid name of the object
+	/// @param iter where to store the iterator
+	/// @returns 0 on success, negative error code on failure
+	fn rados_getxattrs(io: rados_ioctx_t, oid: *const c_char, iter: *mut rados_xattrs_iter_t) -> c_int;
+
+	/// Get the next xattr on the object
+	///
+	/// @pre iter is a valid iterator
+	///
+	/// @post name is the NULL-terminated name of the next xattr, and val
+	/// contains the value of the xattr, which is of length len. If the end
+	/// of the list has been reached, name and val are NULL, and len is 0.
+	///
+	/// @param iter iterator to advance
+	/// @param name where to store the name of the next xattr
+	/// @param val where to store the value of the next xattr
+	/// @param len the number of bytes in val
+	/// @returns 0 on success, negative error code on failure
+	fn rados_getxattrs_next(iter: rados_xattrs_iter_t, name: *mut *const c_char,
+		val: *mut *const c_char, len: *mut size_t) -> c_int;
+
+	/// Close the xattr iterator.
+	///
+	/// iter should not be used after this is called.
+	///
+	/// @param iter the iterator to close
+	fn rados_getxattrs_end(iter: rados_xattrs_iter_t);
+
 	/// Delete an object
 	///
 	/// @note This does not delete any snapshots of the object.
@@ -204,347 +720,6491 @@ extern "C" {
 	/// @returns 0 on success, negative error code on failure
   	fn rados_remove(io: rados_ioctx_t, oid: *const c_char) -> c_int;
 
-	fn rados_ioctx_destroy(ioctx: c_void_ptr);
-
-	fn rados_shutdown(cluster: c_void_ptr);
-}
+	/// Constructs a completion to use with asynchronous operations
+	///
+	/// The complete and safe callbacks correspond to operations being
+	/// acked and committed, respectively. The callbacks are called in
+	/// order of receipt, so the safe callback may be triggered before the
+	/// complete callback, and vice versa.
+	///
+	/// @param cb_arg application-defined data passed to the callback functions
+	/// @param cb_complete the function to be called when the operation is
+	/// in memory on all replicas
+	/// @param cb_safe the function to be called when the operation is on
+	/// stable storage on all replicas
+	/// @param pc where to store the completion
+	/// @returns 0
+	fn rados_aio_create_completion(cb_arg: *mut c_void, cb_complete: rados_callback_t,
+		cb_safe: rados_callback_t, pc: *mut rados_completion_t) -> c_int;
 
-/// Get the version of librados.
-///
-/// The version number is `major.minor.extra`. Note that this is
-/// unrelated to the Ceph version number.
-///
-/// # Examples
-///
-/// ```rust
-/// println!("librados version: {}", ceph::rados::version());
-/// ```
-pub fn version() -> String {
-	let mut major: c_int = -1;
-	let mut minor: c_int = -1;
-	let mut extra: c_int = -1;
-	unsafe {
-		rados_version(&mut major, &mut minor, &mut extra);
-	}
-	format!("{}.{}.{}", major, minor, extra)
-}
+	/// Release a completion
+	///
+	/// Call this when you no longer need the completion. It may not be
+	/// freed immediately if the operation is not acked and committed.
+	///
+	/// @param c completion to release
+	fn rados_aio_release(c: rados_completion_t);
 
-pub struct Cluster {
-	handle: rados_t
-}
+	/// Block until an operation completes
+	///
+	/// This means it is in memory on all replicas.
+	///
+	/// @param c operation to wait for
+	/// @returns 0
+	fn rados_aio_wait_for_complete(c: rados_completion_t) -> c_int;
 
-pub struct IoCtx {
-	handle: rados_ioctx_t
-}
+	/// Block until an operation is safe
+	///
+	/// This means it is on stable storage on all replicas.
+	///
+	/// @param c operation to wait for
+	/// @returns 0
+	fn rados_aio_wait_for_safe(c: rados_completion_t) -> c_int;
 
-pub trait StrStringOrNone {
-	fn unwrap(self) -> Option<CString>;
-}
+	/// Is an asynchronous operation safe?
+	///
+	/// @param c async operation to inspect
+	/// @returns whether c is safe
+	fn rados_aio_is_safe(c: rados_completion_t) -> c_int;
 
-impl StrStringOrNone for String {
-	fn unwrap(self) -> Option<CString> {
-		Some(CString::new(self).unwrap())
-	}
-}
+	/// Has an asynchronous operation completed?
+	///
+	/// @param c async operation to inspect
+	/// @returns whether c is complete
+	fn rados_aio_is_complete(c: rados_completion_t) -> c_int;
 
-impl StrStringOrNone for &'static str {
-	fn unwrap(self) -> Option<CString> {
-		Some(CString::new(self).unwrap())
-	}
-}
+	/// Get the return value of an asychronous operation
+	///
+	/// The return value is set when the operation is complete or safe,
+	/// whichever comes first.
+	///
+	/// @param c async operation to inspect
+	/// @returns return value of the operation
+	fn rados_aio_get_return_value(c: rados_completion_t) -> c_int;
 
-impl StrStringOrNone for Option<String> {
-	fn unwrap(self) -> Option<CString> {
-		self.map(|s| CString::new(s).unwrap())
-	}
-}
+	/// Write data to an object asynchronously
+	///
+	/// Queues the write and returns. The return value of the completion
+	/// will be 0 on success, negative error code on failure.
+	///
+	/// @param io the context in which the write will occur
+	/// @param oid name of the object
+	/// @param completion what to do when the write is safe and complete
+	/// @param buf data to write
+	/// @param len length of the data, in bytes
+	/// @param off byte offset in the object to begin writing at
+	/// @returns 0 on success, -EROFS if the io context specifies a snap_seq
+	/// other than LIBRADOS_SNAP_HEAD
+	fn rados_aio_write(io: rados_ioctx_t, oid: *const c_char, completion: rados_completion_t,
+		buf: *const c_char, len: size_t, off: u64) -> c_int;
 
-macro_rules! handle_errors {
-	($x:expr) => {
-		unsafe {
-			let err = $x;
-			if err < 0 {
-				let s = CStr::from_ptr(strerror(err)).to_str().unwrap();
-				println!("strerror({:?}) => {}", err, s);
-				return Err(s);
-			}
-		}
-	}
-}
+	/// Asynchronously read data from an object
+	///
+	/// The return value of the completion will be number of bytes read on
+	/// success, negative error code on failure.
+	///
+	/// @note only the 'complete' callback of the completion will be called.
+	///
+	/// @param io the context in which to perform the read
+	/// @param oid the name of the object to read from
+	/// @param completion what to do when the read is complete
+	/// @param buf where to store the results
+	/// @param len the number of bytes to read
+	/// @param off the offset to start reading from in the object
+	/// @returns 0 on success, negative error code on failure
+	fn rados_aio_read(io: rados_ioctx_t, oid: *const c_char, completion: rados_completion_t,
+		buf: *mut c_char, len: size_t, off: u64) -> c_int;
 
-/// A neat way to allocate a zeroed out array of given size
-///
-/// # Examples
-///
-/// ```rust
-/// let mut buf = zeroed_c_char_buf(100);
-/// ```
-macro_rules! zeroed_c_char_buf {
-	($n:expr) => {
-		repeat(0).take($n).collect::<Vec<c_char>>();
-	}
-}
+	/// Asynchronously get object stats (size/mtime)
+	///
+	/// @param io ioctx
+	/// @param o object name
+	/// @param completion what to do when the stat is complete
+	/// @param psize where to store object size
+	/// @param pmtime where to store modification time
+	/// @returns 0 on success, negative error code on failure
+	fn rados_aio_stat(io: rados_ioctx_t, o: *const c_char, completion: rados_completion_t,
+		psize: *mut u64, pmtime: *mut libc::time_t) -> c_int;
 
-impl Cluster {
+	/// Cancel async operation
+	///
+	/// @param io ioctx
+	/// @param completion completion handle
+	/// @returns 0 on success, negative error code on failure
+	fn rados_aio_cancel(io: rados_ioctx_t, completion: rados_completion_t) -> c_int;
 
-	/// Create a handle for communicating with a RADOS cluster.
+	/// Register an interest in an object
 	///
-	/// Ceph environment variables are read when this is called, so if
-	/// $CEPH_ARGS specifies everything you need to connect, no further
-	/// configuration is necessary.
+	/// A watch operation registers the client as being interested in
+	/// notifications on an object. OSDs keep track of watches on
+	/// persistent storage, so they are preserved across cluster changes by
+	/// the normal recovery process. If the client loses its connection to
+	/// the primary OSD for a watched object, the watch will be removed
+	/// after a timeout configured with osd_client_watch_timeout.
+	/// Watches are automatically reestablished when a new connection is
+	/// made, or a placement group switches OSDs.
 	///
-	/// # Parameters
+	/// @param io the pool the object is in
+	/// @param o the object to watch
+	/// @param cookie where to store the internal id assigned to this watch
+	/// @param watchcb what to do when a notify is received on this object
+	/// @param watcherrcb what to do when the watch session encounters an error
+	/// @param arg opaque value to pass to the callback
+	/// @returns 0 on success, negative error code on failure
+	fn rados_watch2(io: rados_ioctx_t, o: *const c_char, cookie: *mut u64,
+		watchcb: rados_watchcb2_t, watcherrcb: rados_watcherrcb_t, arg: *mut c_void) -> c_int;
+
+	/// Check on the status of a watch
 	///
-	/// * `cluster_name` the cluster name as a string, or `None` (equivalent to `null`)
-	/// * `user_name` the full user name to connect as (i.e. "client.admin"`)
-	/// * `flags` for future expansion
+	/// Return the number of milliseconds since the watch was last confirmed.
+	/// Or, if there has been an error, return that.
 	///
-	/// # Returns
+	/// If there is an error, the watch is no longer valid, and should be
+	/// destroyed with rados_unwatch2().  The the user is still interested
+	/// in the object, a new watch should be created with rados_watch2().
 	///
-	/// * `Ok(Cluster)` on success
-	/// * `Err(message: &str)` on failure
-	pub fn create<'a, A, S>(cluster_name: A, user_name: S, flags: u64) -> Result<Cluster, &'a str>
-		where A: StrStringOrNone,
-		S: Into<Vec<u8>>
-	{
-	    let cluster_name_ptr = match cluster_name.unwrap() {
-	    	None => ptr::null(),
-	    	Some(cs) => cs.as_ptr()
-	    };
-	    let user_name_ptr = CString::new(user_name).unwrap().as_ptr();
-		let handle: c_void_ptr = ptr::null_mut();
-	    handle_errors!(rados_create2(&handle, cluster_name_ptr, user_name_ptr, flags));
-		return Ok(Cluster { handle: handle });
-	}
+	/// @param io the pool the object is in
+	/// @param cookie the watch handle
+	/// @returns ms since last confirmed on success, negative error code on failure
+	fn rados_watch_check(io: rados_ioctx_t, cookie: u64) -> c_int;
 
-
-	/// Connect to the cluster.
+	/// Unregister an interest in an object
 	///
-	/// # Note
+	/// Once this completes, no more notifies will be sent to us for this
+	/// watch. This should be called to clean up unneeded watchers.
 	///
-	/// **BUG:** Before calling this, calling a function that communicates with the
-	/// cluster will crash.
+	/// @param io the pool the object is in
+	/// @param cookie which watch to unregister
+	/// @returns 0 on success, negative error code on failure
+	fn rados_unwatch2(io: rados_ioctx_t, cookie: u64) -> c_int;
+
+	/// Acknolwedge receipt of a notify
 	///
-	/// # Prerequisites
+	/// @param io the pool the object is in
+	/// @param o the name of the object
+	/// @param notify_id the notify_id we got on the watchcb2_t callback
+	/// @param cookie the watcher handle
+	/// @param buf payload to return to notifier (optional)
+	/// @param buf_len payload length
+	/// @returns 0 on success
+	fn rados_notify_ack(io: rados_ioctx_t, o: *const c_char, notify_id: u64, cookie: u64,
+		buf: *const c_char, buf_len: c_int) -> c_int;
+
+	/// Flush watch/notify callbacks
 	///
-	/// The cluster handle is configured with at least a monitor address.
-	/// If cephx is enabled, a client name and secret must also be set.
+	/// This call will block until all pending watch/notify callbacks have
+	/// been executed and the queue is empty. It should usually be called
+	/// after shutting down any watches before shutting down the ioctx or
+	/// librados to ensure that any callbacks do not misuse the ioctx (for
+	/// example by calling rados_notify_ack after the ioctx has been
+	/// destroyed).
 	///
-	/// # Post
+	/// @param cluster the cluster handle
+	fn rados_watch_flush(cluster: rados_t) -> c_int;
+
+	/// Block until all pending writes in an io context are safe
 	///
-	/// If this succeeds, any function in librados may be used
+	/// This is not equivalent to calling rados_aio_wait_for_safe() on all
+	/// write completions, since this waits for the associated callbacks to
+	/// complete as well.
 	///
-	/// # Returns
+	/// @param io the context to flush
+	/// @returns 0 on success, negative error code on failure
+	fn rados_aio_flush(io: rados_ioctx_t) -> c_int;
+
+	/// Test whether the specified pool requires alignment or not.
 	///
-	/// * `Ok(())` on sucess
-	/// * `Err(message: &str)` on failure
-	pub fn connect(&self) -> Result<(), &str> {
-		handle_errors!(rados_connect(self.handle));
+	/// @param io pool to query
+	/// @param req 1 if alignment is supported, 0 if not.
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_pool_requires_alignment2(io: rados_ioctx_t, req: *mut c_int) -> c_int;
+
+	/// Get the alignment flavor of a pool
+	///
+	/// @param io pool to query
+	/// @param alignment where to store the alignment flavor
+	/// @returns 0 on success, negative error code on failure
+	fn rados_ioctx_pool_required_alignment2(io: rados_ioctx_t, alignment: *mut u64) -> c_int;
+
+	/// Create a new rados_write_op_t write operation. This will store all
+	/// actions to be performed atomically. You must call
+	/// rados_release_write_op when you are finished with it.
+	///
+	/// @returns non-NULL on success, NULL on memory allocation error.
+	fn rados_create_write_op() -> rados_write_op_t;
+
+	/// Free a rados_write_op_t, must be called when you're done with it.
+	///
+	/// @param write_op operation to deallocate, created with rados_create_write_op
+	fn rados_release_write_op(write_op: rados_write_op_t);
+
+	/// Create the object
+	///
+	/// @param write_op operation to add this action to
+	/// @param exclusive set to either LIBRADOS_CREATE_EXCLUSIVE or
+	/// LIBRADOS_CREATE_IDEMPOTENT will error if the object already exists.
+	/// @param category category string (DEPRECATED, HAS NO EFFECT)
+	fn rados_write_op_create(write_op: rados_write_op_t, exclusive: c_int, category: *const c_char);
+
+	/// Append to end of object.
+	///
+	/// @param write_op operation to add this action to
+	/// @param buffer bytes to write
+	/// @param len length of buffer
+	fn rados_write_op_append(write_op: rados_write_op_t, buffer: *const c_char, len: size_t);
+
+	/// Set flags for the last operation added to this write_op.
+	/// At least one op must have been added to the write_op.
+	///
+	/// @param write_op operation to add this action to
+	/// @param flags see librados.h constants beginning with LIBRADOS_OP_FLAG
+	fn rados_write_op_set_flags(write_op: rados_write_op_t, flags: c_int);
+
+	/// Write to offset
+	///
+	/// @param write_op operation to add this action to
+	/// @param offset offset to write to
+	/// @param buffer bytes to write
+	/// @param len length of buffer
+	fn rados_write_op_write(write_op: rados_write_op_t, buffer: *const c_char, len: size_t, offset: u64);
+
+	/// Write whole object, atomically replacing it.
+	///
+	/// @param write_op operation to add this action to
+	/// @param buffer bytes to write
+	/// @param len length of buffer
+	fn rados_write_op_write_full(write_op: rados_write_op_t, buffer: *const c_char, len: size_t);
+
+	/// Ensure that the object exists before writing
+	///
+	/// @param write_op operation to add this action to
+	fn rados_write_op_assert_exists(write_op: rados_write_op_t);
+
+	/// Remove object
+	///
+	/// @param write_op operation to add this action to
+	fn rados_write_op_remove(write_op: rados_write_op_t);
+
+	/// Ensure that the object exists and that its internal version
+	/// number is equal to "ver" before writing. "ver" should be a
+	/// version number previously obtained with rados_get_last_version().
+	/// - If the object's version is greater than the asserted version
+	///   then rados_write_op_operate will return -ERANGE instead of
+	///   executing the op.
+	/// - If the object's version is less than the asserted version
+	///   then rados_write_op_operate will return -EOVERFLOW instead
+	///   of executing the op.
+	///
+	/// @param write_op operation to add this action to
+	/// @param ver object version number
+	fn rados_write_op_assert_version(write_op: rados_write_op_t, ver: u64);
+
+	/// Ensure that given xattr satisfies comparison.
+	/// If the comparison is not satisfied, the return code of the
+	/// operation will be -ECANCELED
+	///
+	/// @param write_op operation to add this action to
+	/// @param name name of the xattr to look up
+	/// @param comparison_operator currently undocumented, look for
+	/// LIBRADOS_CMPXATTR_OP_EQ in librados.h
+	/// @param value buffer to compare actual xattr value to
+	/// @param value_len length of buffer to compare actual xattr value to
+	fn rados_write_op_cmpxattr(write_op: rados_write_op_t, name: *const c_char, comparison_operator: u8,
+		value: *const c_char, value_len: size_t);
+
+	/// Set an xattr
+	///
+	/// @param write_op operation to add this action to
+	/// @param name name of the xattr
+	/// @param value buffer to set xattr to
+	/// @param value_len length of buffer to set xattr to
+	fn rados_write_op_setxattr(write_op: rados_write_op_t, name: *const c_char,
+		value: *const c_char, value_len: size_t);
+
+	/// Truncate an object
+	///
+	/// @param write_op operation to add this action to
+	/// @param offset Offset to truncate to
+	fn rados_write_op_truncate(write_op: rados_write_op_t, offset: u64);
+
+	/// Zero part of an object
+	///
+	/// @param write_op operation to add this action to
+	/// @param offset byte offset to zero starting from
+	/// @param len how many zeroes to write
+	fn rados_write_op_zero(write_op: rados_write_op_t, offset: u64, len: u64);
+
+	/// Remove all key/value pairs from an object
+	///
+	/// @param write_op operation to add this action to
+	fn rados_write_op_omap_clear(write_op: rados_write_op_t);
+
+	/// Set key/value pairs on an object
+	///
+	/// @param write_op operation to add this action to
+	/// @param keys array of null-terminated char arrays representing keys to set
+	/// @param vals array of pointers to values to set
+	/// @param lens array of lengths corresponding to each value
+	/// @param num number of key/value pairs to set
+	fn rados_write_op_omap_set(write_op: rados_write_op_t, keys: *const *const c_char,
+		vals: *const *const c_char, lens: *const size_t, num: size_t);
+
+	/// Get the next omap key/value pair on the object
+	///
+	/// @pre iter is a valid iterator
+	///
+	/// @post key and val are the next key/value pair. key is
+	/// null-terminated, and val has length len. If the end of the list has
+	/// been reached, key and val are NULL, and len is 0. key and val will
+	/// not be accessible after rados_omap_get_end() is called on iter, so
+	/// if they are needed after that they should be copied.
+	///
+	/// @param iter iterator to advance
+	/// @param key where to store the key of the next omap entry
+	/// @param val where to store the value of the next omap entry
+	/// @param len where to store the number of bytes in val
+	/// @returns 0 on success, negative error code on failure
+	fn rados_omap_get_next(iter: rados_omap_iter_t, key: *mut *mut c_char,
+		val: *mut *mut c_char, len: *mut size_t) -> c_int;
+
+	/// Close the omap iterator.
+	///
+	/// iter should not be used after this is called.
+	///
+	/// @param iter the iterator to close
+	fn rados_omap_get_end(iter: rados_omap_iter_t);
+
+	/// Perform a write operation synchronously
+	///
+	/// @param write_op operation to perform
+	/// @param io the ioctx that the object is in
+	/// @param oid the object id
+	/// @param mtime the time to set the mtime to, NULL for the current time
+	/// @param flags flags to apply to the entire operation (LIBRADOS_OPERATION_*)
+	/// @returns 0 on success, negative error code on failure
+	fn rados_write_op_operate(write_op: rados_write_op_t, io: rados_ioctx_t, oid: *const c_char,
+		mtime: *mut libc::time_t, flags: c_int) -> c_int;
+
+	/// Perform a write operation asynchronously
+	///
+	/// @param write_op operation to perform
+	/// @param io the ioctx that the object is in
+	/// @param completion what to do when operation has been attempted
+	/// @param oid the object id
+	/// @param mtime the time to set the mtime to, NULL for the current time
+	/// @param flags flags to apply to the entire operation (LIBRADOS_OPERATION_*)
+	/// @returns 0 on success, negative error code on failure
+	fn rados_aio_write_op_operate(write_op: rados_write_op_t, io: rados_ioctx_t,
+		completion: rados_completion_t, oid: *const c_char, mtime: *mut libc::time_t,
+		flags: c_int) -> c_int;
+
+	/// Create a new rados_read_op_t read operation. This will store all
+	/// actions to be performed atomically. You must call
+	/// rados_release_read_op when you are finished with it (after it
+	/// completes, or you decide not to send it in the first place).
+	///
+	/// @returns non-NULL on success, NULL on memory allocation error.
+	fn rados_create_read_op() -> rados_read_op_t;
+
+	/// Free a rados_read_op_t, must be called when you're done with it.
+	///
+	/// @param read_op operation to deallocate, created with rados_create_read_op
+	fn rados_release_read_op(read_op: rados_read_op_t);
+
+	/// Set flags for the last operation added to this read_op.
+	/// At least one op must have been added to the read_op.
+	///
+	/// @param read_op operation to add this action to
+	/// @param flags see librados.h constants beginning with LIBRADOS_OP_FLAG
+	fn rados_read_op_set_flags(read_op: rados_read_op_t, flags: c_int);
+
+	/// Ensure that the object exists and that its internal version
+	/// number is equal to "ver" before reading. "ver" should be a
+	/// version number previously obtained with rados_get_last_version().
+	/// - If the object's version is greater than the asserted version
+	///   then rados_read_op_operate will return -ERANGE instead of
+	///   executing the op.
+	/// - If the object's version is less than the asserted version
+	///   then rados_read_op_operate will return -EOVERFLOW instead
+	///   of executing the op.
+	///
+	/// @param read_op operation to add this action to
+	/// @param ver object version number
+	fn rados_read_op_assert_version(read_op: rados_read_op_t, ver: u64);
+
+	/// Get object size and mtime
+	///
+	/// @param read_op operation to add this action to
+	/// @param psize where to store object size
+	/// @param pmtime where to store modification time
+	/// @param prval where to store the return value of this action
+	fn rados_read_op_stat(read_op: rados_read_op_t, psize: *mut u64, pmtime: *mut libc::time_t, prval: *mut c_int);
+
+	/// Return the version of the last object read or written to.
+	///
+	/// This exposes the internal version number of the last object read or
+	/// written via this io context
+	///
+	/// @param io the io context to check
+	/// @returns last read or written object version
+	fn rados_get_last_version(io: rados_ioctx_t) -> u64;
+
+	/// Start iterating over xattrs on an object.
+	///
+	/// @post iter is a valid iterator
+	///
+	/// @param read_op operation to add this action to
+	/// @param iter where to store the iterator
+	/// @param prval where to store the return value of this action
+	fn rados_read_op_getxattrs(read_op: rados_read_op_t, iter: *mut rados_xattrs_iter_t, prval: *mut c_int);
+
+	/// Read bytes from offset into buffer.
+	///
+	/// prlen will be filled with the number of bytes read if successful.
+	/// A short read can only occur if the read reaches the end of the
+	/// object.
+	///
+	/// @param read_op operation to add this action to
+	/// @param offset offset to read from
+	/// @param len length of buffer
+	/// @param buffer where to put the data
+	/// @param bytes_read where to store the number of bytes read by this action
+	/// @param prval where to store the return value of this action
+	fn rados_read_op_read(read_op: rados_read_op_t, offset: u64, len: size_t, buffer: *mut c_char,
+		bytes_read: *mut size_t, prval: *mut c_int);
+
+	/// Perform a read operation synchronously
+	///
+	/// @param read_op operation to perform
+	/// @param io the ioctx that the object is in
+	/// @param oid the object id
+	/// @param flags flags to apply to the entire operation (LIBRADOS_OPERATION_*)
+	/// @returns 0 on success, negative error code on failure
+	fn rados_read_op_operate(read_op: rados_read_op_t, io: rados_ioctx_t, oid: *const c_char, flags: c_int) -> c_int;
+
+	fn rados_ioctx_destroy(ioctx: c_void_ptr);
+
+	fn rados_shutdown(cluster: c_void_ptr);
+}
+
+// Listing objects, Luminous and later.
+//
+// The `rados_nobjects_list_*` functions replace `rados_objects_list_*`,
+// which were deprecated in Jewel and later removed.
+#[cfg(feature = "luminous")]
+#[link(name = "rados")]
+extern "C" {
+	/// Start listing objects in a pool
+	///
+	/// @param io the pool to list from
+	/// @param ctx the handle to store list context in
+	/// @returns 0 on success, negative error code on failure
+	fn rados_nobjects_list_open(io: rados_ioctx_t, ctx: *mut rados_list_ctx_t) -> c_int;
+
+	/// Get the next object name and locator in the pool
+	///
+	/// *entry and *key are valid until next call to rados_nobjects_list_*
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @param entry where to store the name of the entry
+	/// @param key where to store the object locator (set to NULL to ignore)
+	/// @param nspace where to store the object namespace (set to NULL to ignore)
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -ENOENT when there are no more objects to list
+	fn rados_nobjects_list_next(ctx: rados_list_ctx_t, entry: *mut *const c_char,
+		key: *mut *const c_char, nspace: *mut *const c_char) -> c_int;
+
+	/// Close the object listing handle.
+	///
+	/// This should be called when the handle is no longer needed.
+	/// The handle should not be used after it has been closed.
+	///
+	/// @param ctx the handle to close
+	fn rados_nobjects_list_close(ctx: rados_list_ctx_t);
+
+	/// Return hash position of iterator, rounded to the current PG
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @returns current hash position, rounded to the current pg
+	fn rados_nobjects_list_get_pg_hash_position(ctx: rados_list_ctx_t) -> u32;
+
+	/// Reposition object iterator to a different hash position
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @param pos hash position to move to
+	/// @returns actual (rounded) position we moved to
+	fn rados_nobjects_list_seek(ctx: rados_list_ctx_t, pos: u32) -> u32;
+
+	/// Start iterating over key/value pairs on an object.
+	///
+	/// They will be returned sorted by key.
+	///
+	/// @param read_op operation to add this action to
+	/// @param start_after list keys starting after start_after
+	/// @param filter_prefix list only keys beginning with filter_prefix
+	/// @param max_return list no more than max_return key/value pairs
+	/// @param iter where to store the iterator
+	/// @param pmore flag indicating whether there are more keys to fetch
+	/// @param prval where to store the return value from this action
+	fn rados_read_op_omap_get_vals2(read_op: rados_read_op_t, start_after: *const c_char,
+		filter_prefix: *const c_char, max_return: u64, iter: *mut rados_omap_iter_t,
+		pmore: *mut c_uchar, prval: *mut c_int);
+
+	/// Compute checksum from object data
+	///
+	/// @param read_op operation to add this action to
+	/// @param type the checksum algorithm to utilize
+	/// @param init_value the init value for the algorithm
+	/// @param init_value_len the length of the init value
+	/// @param offset the offset to start checksumming in the object
+	/// @param len the number of bytes to checksum
+	/// @param chunk_size optional length-aligned chunk size for checksums
+	/// @param pchecksum where to store the checksum result for this action
+	/// @param checksum_len the number of bytes available for the result
+	/// @param prval where to store the return value for this action
+	fn rados_read_op_checksum(read_op: rados_read_op_t, checksum_type: c_int,
+		init_value: *const c_char, init_value_len: size_t, offset: u64, len: size_t,
+		chunk_size: size_t, pchecksum: *mut c_char, checksum_len: size_t, prval: *mut c_int);
+}
+
+// Server side copies, which newer librados releases no longer provide.
+#[cfg(not(feature = "nautilus"))]
+#[link(name = "rados")]
+extern "C" {
+	/// Efficiently copy a portion of one object to another
+	///
+	/// If the underlying filesystem on the OSD supports it, this will be a
+	/// copy-on-write clone.
+	///
+	/// The src and dest objects must be in the same pg. To ensure this,
+	/// the io context should have a locator key set (see
+	/// rados_ioctx_locator_set_key()).
+	///
+	/// @param io the context in which the data is cloned
+	/// @param dst the name of the destination object
+	/// @param dst_off the offset within the destination object (in bytes)
+	/// @param src the name of the source object
+	/// @param src_off the offset within the source object (in bytes)
+	/// @param len how much data to copy
+	/// @returns 0 on success, negative error code on failure
+	fn rados_clone_range(io: rados_ioctx_t, dst: *const c_char, dst_off: u64,
+		src: *const c_char, src_off: u64, len: size_t) -> c_int;
+}
+
+// Listing objects, before Luminous.
+#[cfg(not(feature = "luminous"))]
+#[link(name = "rados")]
+extern "C" {
+	/// Start listing objects in a pool
+	///
+	/// @param io the pool to list from
+	/// @param ctx the handle to store list context in
+	/// @returns 0 on success, negative error code on failure
+	fn rados_objects_list_open(io: rados_ioctx_t, ctx: *mut rados_list_ctx_t) -> c_int;
+
+	/// Get the next object name and locator in the pool
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @param entry where to store the name of the entry
+	/// @param key where to store the object locator (set to NULL to ignore)
+	/// @returns 0 on success, negative error code on failure
+	/// @returns -ENOENT when there are no more objects to list
+	fn rados_objects_list_next(ctx: rados_list_ctx_t, entry: *mut *const c_char,
+		key: *mut *const c_char) -> c_int;
+
+	/// Close the object listing handle.
+	///
+	/// @param ctx the handle to close
+	fn rados_objects_list_close(ctx: rados_list_ctx_t);
+
+	/// Return hash position of iterator, rounded to the current PG
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @returns current hash position, rounded to the current pg
+	fn rados_objects_list_get_pg_hash_position(ctx: rados_list_ctx_t) -> u32;
+
+	/// Reposition object iterator to a different hash position
+	///
+	/// @param ctx iterator marking where you are in the listing
+	/// @param pos hash position to move to
+	/// @returns actual (rounded) position we moved to
+	fn rados_objects_list_seek(ctx: rados_list_ctx_t, pos: u32) -> u32;
+
+	/// Start iterating over key/value pairs on an object.
+	///
+	/// They will be returned sorted by key.
+	///
+	/// @param read_op operation to add this action to
+	/// @param start_after list keys starting after start_after
+	/// @param filter_prefix list only keys beginning with filter_prefix
+	/// @param max_return list no more than max_return key/value pairs
+	/// @param iter where to store the iterator
+	/// @param prval where to store the return value from this action
+	fn rados_read_op_omap_get_vals(read_op: rados_read_op_t, start_after: *const c_char,
+		filter_prefix: *const c_char, max_return: u64, iter: *mut rados_omap_iter_t,
+		prval: *mut c_int);
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn list_open(io: rados_ioctx_t, ctx: *mut rados_list_ctx_t) -> c_int {
+	rados_nobjects_list_open(io, ctx)
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn list_next(ctx: rados_list_ctx_t, entry: *mut *const c_char) -> c_int {
+	rados_nobjects_list_next(ctx, entry, ptr::null_mut(), ptr::null_mut())
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn list_close(ctx: rados_list_ctx_t) {
+	rados_nobjects_list_close(ctx)
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn list_position(ctx: rados_list_ctx_t) -> u32 {
+	rados_nobjects_list_get_pg_hash_position(ctx)
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn list_seek(ctx: rados_list_ctx_t, pos: u32) -> u32 {
+	rados_nobjects_list_seek(ctx, pos)
+}
+
+#[cfg(not(feature = "luminous"))]
+unsafe fn list_open(io: rados_ioctx_t, ctx: *mut rados_list_ctx_t) -> c_int {
+	rados_objects_list_open(io, ctx)
+}
+
+#[cfg(not(feature = "luminous"))]
+unsafe fn list_next(ctx: rados_list_ctx_t, entry: *mut *const c_char) -> c_int {
+	rados_objects_list_next(ctx, entry, ptr::null_mut())
+}
+
+#[cfg(not(feature = "luminous"))]
+unsafe fn list_close(ctx: rados_list_ctx_t) {
+	rados_objects_list_close(ctx)
+}
+
+#[cfg(not(feature = "luminous"))]
+unsafe fn list_position(ctx: rados_list_ctx_t) -> u32 {
+	rados_objects_list_get_pg_hash_position(ctx)
+}
+
+#[cfg(not(feature = "luminous"))]
+unsafe fn list_seek(ctx: rados_list_ctx_t, pos: u32) -> u32 {
+	rados_objects_list_seek(ctx, pos)
+}
+
+#[cfg(feature = "luminous")]
+unsafe fn omap_get_vals(op: rados_read_op_t, start_after: *const c_char, max_return: u64,
+	iter: *mut rados_omap_iter_t, more: *mut c_uchar, rval: *mut c_int) {
+	rados_read_op_omap_get_vals2(op, start_after, ptr::null(), max_return, iter, more, rval)
+}
+
+/// Before Luminous there's no flag telling whether the listing is done, so
+/// `more` is always set and callers stop at the first page with no entries.
+#[cfg(not(feature = "luminous"))]
+unsafe fn omap_get_vals(op: rados_read_op_t, start_after: *const c_char, max_return: u64,
+	iter: *mut rados_omap_iter_t, more: *mut c_uchar, rval: *mut c_int) {
+	*more = 1;
+	rados_read_op_omap_get_vals(op, start_after, ptr::null(), max_return, iter, rval)
+}
+
+/// Get the version of librados.
+///
+/// The version number is `major.minor.extra`. Note that this is
+/// unrelated to the Ceph version number.
+///
+/// # Examples
+///
+/// ```rust
+/// println!("librados version: {}", ceph::rados::version());
+/// ```
+pub fn version() -> String {
+	let mut major: c_int = -1;
+	let mut minor: c_int = -1;
+	let mut extra: c_int = -1;
+	unsafe {
+		rados_version(&mut major, &mut minor, &mut extra);
+	}
+	format!("{}.{}.{}", major, minor, extra)
+}
+
+/// Get the version of librados as `(major, minor, extra)`, like `version()`.
+///
+/// `rados_version()` leaves a component untouched if librados doesn't fill
+/// it in, as can happen when linking against a mismatched library, so this
+/// returns `None` if any component is still at its `-1` sentinel.
+///
+/// # Examples
+///
+/// ```rust
+/// assert!(ceph::rados::try_version().is_some());
+/// ```
+pub fn try_version() -> Option<(i32, i32, i32)> {
+	let mut major: c_int = -1;
+	let mut minor: c_int = -1;
+	let mut extra: c_int = -1;
+	unsafe {
+		rados_version(&mut major, &mut minor, &mut extra);
+	}
+	if major == -1 || minor == -1 || extra == -1 {
+		return None;
+	}
+	Some((major as i32, minor as i32, extra as i32))
+}
+
+/// A librados version, e.g. `3.0.0`, ordered component by component so
+/// versions can be compared to gate features at runtime.
+///
+/// This is the version of the librados API, not of the Ceph release:
+/// Jewel ships `0.69.1`, and every release since Luminous `3.0.0`. See
+/// `features::available()` for what a given version supports.
+///
+/// # Examples
+///
+/// ```rust
+/// use ceph::rados::Version;
+///
+/// assert!(Version::new(3, 0, 0) > Version::new(0, 69, 1));
+/// assert!(Version::new(0, 69, 1) < Version::new(0, 70, 0));
+/// assert_eq!(Version::new(3, 0, 0).to_string(), "3.0.0");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+	/// Bumped for incompatible API changes
+	pub major: i32,
+	/// Bumped for API additions
+	pub minor: i32,
+	/// Bumped for fixes
+	pub extra: i32,
+}
+
+impl Version {
+	pub fn new(major: i32, minor: i32, extra: i32) -> Version {
+		Version { major: major, minor: minor, extra: extra }
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "{}.{}.{}", self.major, self.minor, self.extra)
+	}
+}
+
+/// Get the version of librados as a `Version`, or `None` when
+/// `try_version()` can't tell it.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use ceph::rados::{library_version, Version};
+///
+/// if library_version().map_or(false, |v| v >= Version::new(3, 0, 0)) {
+/// 	println!("linked against Luminous or later");
+/// }
+/// ```
+pub fn library_version() -> Option<Version> {
+	try_version().map(|(major, minor, extra)| Version::new(major, minor, extra))
+}
+
+/// An error returned by a librados call.
+///
+/// librados reports failures as negative error codes; `RadosError` sorts the
+/// common ones into classes callers can match on directly, taking into
+/// account what the failing call was operating on (`ENOENT` means a missing
+/// pool when opening an io context, but a missing object when reading).
+/// Every other code is kept as `Other(errno)`, and formatted using
+/// `strerror()`.
+///
+/// Invalid arguments are reported the same way rather than by panicking:
+/// names and values with a NUL byte in them, which can't be passed to
+/// librados, and sizes of `0` where a size is required, all fail with
+/// `Other(EINVAL)`. The only panics left are on failing to allocate (like
+/// `Vec` does), and from callbacks given by the caller.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags, RadosError};
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// match cluster.create_ioctx("data") {
+/// 	Ok(_) => println!("opened pool"),
+/// 	Err(RadosError::PoolNotFound) => println!("no such pool"),
+/// 	Err(RadosError::PermissionDenied) => println!("not allowed"),
+/// 	Err(e) => println!("error: {}", e),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RadosError {
+	/// The pool doesn't exist (`ENOENT` while looking up a pool)
+	PoolNotFound,
+	/// The object doesn't exist (`ENOENT` while operating on an object)
+	ObjectNotFound,
+	/// The object exists, but doesn't have the extended attribute asked
+	/// for (`ENODATA`)
+	AttrNotFound,
+	/// The client isn't allowed to do this (`EPERM` or `EACCES`)
+	PermissionDenied,
+	/// The object or pool to be created already exists (`EEXIST`)
+	AlreadyExists,
+	/// The cluster or pool is full (`ENOSPC`), so a write was refused.
+	///
+	/// By default librados holds writes to a full pool until space is
+	/// freed rather than failing them, so this mostly surfaces for writes
+	/// the OSDs reject themselves; with an operation timeout set, held
+	/// writes fail with `Timeout` instead.
+	NoSpace,
+	/// The operation timed out (`ETIMEDOUT`)
+	Timeout,
+	/// A quota would be exceeded (`EDQUOT`): the pool's, or a size limit
+	/// such as the one of `IoCtx::append_capped()`
+	QuotaExceeded,
+	/// The cluster handle isn't connected (`ENOTCONN`): `Cluster::connect()`
+	/// hasn't succeeded yet, or the handle was shut down
+	NotConnected,
+	/// The cluster reports `HEALTH_ERR`, so a write was refused, see
+	/// `IoCtx::write_when_healthy()`
+	ClusterUnhealthy,
+	/// librados returned any other `-errno`
+	Other(i32),
+	/// A monitor, PG or OSD command failed with `-errno`, explaining why in
+	/// the status string it returned, e.g. `"pool 'data' does not exist"`
+	CommandFailed(i32, String),
+	/// Reading from or writing to a caller supplied stream failed
+	Io(io::ErrorKind, String),
+	/// The object ended before the buffer being read into was filled
+	UnexpectedEof,
+	/// A command succeeded, but its output couldn't be understood
+	UnexpectedReply(String),
+	/// A value couldn't be serialized, or an object's contents couldn't be
+	/// deserialized
+	Serialization(String),
+}
+
+impl RadosError {
+	/// Create a `RadosError` from a negative librados return code of a call
+	/// operating on an object, where `ENOENT` means `ObjectNotFound`
+	pub fn from_return_code(code: c_int) -> RadosError {
+		RadosError::classify(-code as i32, RadosError::ObjectNotFound)
+	}
+
+	/// Create a `RadosError` from a negative librados return code of a call
+	/// looking up a pool, where `ENOENT` means `PoolNotFound`
+	pub fn from_pool_return_code(code: c_int) -> RadosError {
+		RadosError::classify(-code as i32, RadosError::PoolNotFound)
+	}
+
+	/// Create a `RadosError` from a negative librados return code of a call
+	/// involving neither a pool nor an object (configuration, commands), where
+	/// `ENOENT` is left as `Other(ENOENT)`
+	pub fn from_cluster_return_code(code: c_int) -> RadosError {
+		RadosError::classify(-code as i32, RadosError::Other(libc::ENOENT))
+	}
+
+	fn classify(errno: i32, not_found: RadosError) -> RadosError {
+		match errno {
+			libc::ENOENT => not_found,
+			libc::ENODATA => RadosError::AttrNotFound,
+			libc::EPERM | libc::EACCES => RadosError::PermissionDenied,
+			libc::EEXIST => RadosError::AlreadyExists,
+			libc::ENOSPC => RadosError::NoSpace,
+			libc::ETIMEDOUT => RadosError::Timeout,
+			libc::EDQUOT => RadosError::QuotaExceeded,
+			libc::ENOTCONN => RadosError::NotConnected,
+			errno => RadosError::Other(errno),
+		}
+	}
+
+	/// The underlying `errno` value, if any. `PermissionDenied` always
+	/// reports `EPERM`.
+	pub fn errno(&self) -> Option<i32> {
+		match *self {
+			RadosError::PoolNotFound => Some(libc::ENOENT),
+			RadosError::ObjectNotFound => Some(libc::ENOENT),
+			RadosError::AttrNotFound => Some(libc::ENODATA),
+			RadosError::PermissionDenied => Some(libc::EPERM),
+			RadosError::AlreadyExists => Some(libc::EEXIST),
+			RadosError::NoSpace => Some(libc::ENOSPC),
+			RadosError::Timeout => Some(libc::ETIMEDOUT),
+			RadosError::QuotaExceeded => Some(libc::EDQUOT),
+			RadosError::NotConnected => Some(libc::ENOTCONN),
+			RadosError::ClusterUnhealthy => None,
+			RadosError::Other(errno) => Some(errno),
+			RadosError::CommandFailed(errno, _) => Some(errno),
+			RadosError::Io(_, _) => None,
+			RadosError::UnexpectedEof => None,
+			RadosError::UnexpectedReply(_) => None,
+			RadosError::Serialization(_) => None,
+		}
+	}
+}
+
+impl fmt::Display for RadosError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match *self {
+			RadosError::PoolNotFound => f.write_str("pool not found"),
+			RadosError::ObjectNotFound => f.write_str("object not found"),
+			RadosError::AttrNotFound => f.write_str("attribute not found"),
+			RadosError::AlreadyExists => f.write_str("already exists"),
+			RadosError::NotConnected => f.write_str("not connected to the cluster"),
+			RadosError::ClusterUnhealthy => f.write_str("cluster is in HEALTH_ERR"),
+			RadosError::CommandFailed(errno, ref status) => {
+				let s = unsafe { CStr::from_ptr(strerror(errno)) };
+				if status.is_empty() {
+					f.write_str(&s.to_string_lossy())
+				} else {
+					write!(f, "{}: {}", s.to_string_lossy(), status)
+				}
+			},
+			RadosError::Io(_, ref message) => f.write_str(message),
+			RadosError::UnexpectedEof => f.write_str("unexpected end of object"),
+			RadosError::UnexpectedReply(ref message) => write!(f, "unexpected reply: {}", message),
+			RadosError::Serialization(ref message) => write!(f, "serialization error: {}", message),
+			_ => match self.errno() {
+				Some(errno) => {
+					let s = unsafe { CStr::from_ptr(strerror(errno)) };
+					f.write_str(&s.to_string_lossy())
+				},
+				None => f.write_str("librados error"),
+			},
+		}
+	}
+}
+
+impl From<io::Error> for RadosError {
+	fn from(err: io::Error) -> RadosError {
+		RadosError::Io(err.kind(), err.to_string())
+	}
+}
+
+/// Lets librados errors flow through code built on `std::io`. Classified
+/// errors keep the `RadosError` as the inner error; other `errno` values
+/// become OS errors, which `std` maps to the matching `io::ErrorKind`
+/// (`InvalidInput` for `EINVAL`, `BrokenPipe` for `EPIPE`, ...).
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io;
+/// use ceph::rados::RadosError;
+///
+/// let err: io::Error = RadosError::ObjectNotFound.into();
+/// assert_eq!(err.kind(), io::ErrorKind::NotFound);
+/// let err: io::Error = RadosError::Timeout.into();
+/// assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+/// ```
+impl From<RadosError> for io::Error {
+	fn from(err: RadosError) -> io::Error {
+		let kind = match err {
+			RadosError::PoolNotFound => io::ErrorKind::NotFound,
+			RadosError::ObjectNotFound => io::ErrorKind::NotFound,
+			RadosError::AttrNotFound => io::ErrorKind::NotFound,
+			RadosError::PermissionDenied => io::ErrorKind::PermissionDenied,
+			RadosError::AlreadyExists => io::ErrorKind::AlreadyExists,
+			RadosError::NoSpace => io::ErrorKind::Other,
+			RadosError::Timeout => io::ErrorKind::TimedOut,
+			RadosError::QuotaExceeded => io::ErrorKind::Other,
+			RadosError::NotConnected => io::ErrorKind::NotConnected,
+			RadosError::ClusterUnhealthy => io::ErrorKind::Other,
+			RadosError::Other(errno) => return io::Error::from_raw_os_error(errno),
+			RadosError::CommandFailed(errno, _) => io::Error::from_raw_os_error(errno).kind(),
+			RadosError::Io(kind, message) => return io::Error::new(kind, message),
+			RadosError::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+			RadosError::UnexpectedReply(_) => io::ErrorKind::InvalidData,
+			RadosError::Serialization(_) => io::ErrorKind::InvalidData,
+		};
+		io::Error::new(kind, err)
+	}
+}
+
+/// A name or value with a NUL byte in it can't be passed to librados, and
+/// is refused as librados refuses other invalid arguments, with `EINVAL`
+impl From<NulError> for RadosError {
+	fn from(_: NulError) -> RadosError {
+		RadosError::Other(libc::EINVAL)
+	}
+}
+
+impl From<serde_json::Error> for RadosError {
+	fn from(err: serde_json::Error) -> RadosError {
+		RadosError::UnexpectedReply(err.to_string())
+	}
+}
+
+impl Error for RadosError {
+	fn description(&self) -> &str {
+		"librados error"
+	}
+}
+
+/// Describes how to retry an operation that fails with a transient error.
+///
+/// An operation is retried when it fails with one of the `errnos` listed,
+/// waiting `initial_backoff` before the first retry and doubling the delay
+/// (up to `max_backoff`) after each further attempt.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags, RetryPolicy};
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// let ioctx = cluster.create_ioctx("data").unwrap();
+/// let policy = RetryPolicy::default();
+/// ioctx.with_retry(&policy, |io| io.write("hw", "Hello, world.")).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	/// Total number of attempts, including the first one
+	pub max_attempts: u32,
+	/// How long to wait before the first retry
+	pub initial_backoff: Duration,
+	/// The longest to ever wait between two attempts
+	pub max_backoff: Duration,
+	/// The `errno` values considered transient
+	pub errnos: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+	/// Up to 5 attempts, backing off from 10ms to at most 1s, retrying on
+	/// `EAGAIN`, `EBLOCKLISTED` and `ETIMEDOUT`
+	fn default() -> RetryPolicy {
+		RetryPolicy {
+			max_attempts: 5,
+			initial_backoff: Duration::from_millis(10),
+			max_backoff: Duration::from_secs(1),
+			// Ceph defines EBLOCKLISTED as ESHUTDOWN
+			errnos: vec![libc::EAGAIN, libc::ESHUTDOWN, libc::ETIMEDOUT],
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Whether `err` is one of the errors this policy retries
+	pub fn is_transient(&self, err: &RadosError) -> bool {
+		match err.errno() {
+			Some(errno) => self.errnos.contains(&errno),
+			None => false,
+		}
+	}
+
+	/// Run `op`, retrying it according to this policy.
+	///
+	/// # Returns
+	///
+	/// * `Ok(T)` as soon as an attempt succeeds
+	/// * `Err(RadosError)` if an attempt fails with a non transient error,
+	///   or with the last error once `max_attempts` have been made
+	pub fn retry<T, F>(&self, mut op: F) -> Result<T, RadosError>
+		where F: FnMut() -> Result<T, RadosError>
+	{
+		let mut backoff = self.initial_backoff;
+		let mut attempt = 1;
+		loop {
+			match op() {
+				Err(ref e) if attempt < self.max_attempts && self.is_transient(e) => {
+					thread::sleep(backoff);
+					backoff = backoff.checked_mul(2).map_or(self.max_backoff, |b| cmp::min(b, self.max_backoff));
+					attempt += 1;
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+/// How `IoCtx::write_value()` and `IoCtx::read_value()` encode values
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerdeFormat {
+	/// JSON text, easy to inspect with `rados get`
+	Json,
+	/// bincode, compact and fast
+	Bincode,
+}
+
+/// The overall health of a cluster, as `ceph health` reports it, see
+/// `Cluster::health()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+	/// `HEALTH_OK`
+	Ok,
+	/// `HEALTH_WARN`: something needs attention, e.g. placement groups are
+	/// degraded, but the data is still served
+	Warn,
+	/// `HEALTH_ERR`: data may be unavailable or at risk
+	Err,
+}
+
+/// The object classes the OSDs may load, see
+/// `Cluster::list_object_classes()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectClasses {
+	/// Every class installed on the OSDs, configured as `"*"`
+	All,
+	/// Only the classes named
+	Only(Vec<String>),
+}
+
+impl ObjectClasses {
+	/// Whether the `class` object class may be loaded
+	pub fn allows(&self, class: &str) -> bool {
+		match *self {
+			ObjectClasses::All => true,
+			ObjectClasses::Only(ref classes) => classes.iter().any(|c| c == class),
+		}
+	}
+}
+
+/// A pool snapshot, see `IoCtx::list_snaps()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapInfo {
+	/// The id to read the snapshot with, see `IoCtx::set_read_snap()`
+	pub id: u64,
+	/// The name the snapshot was created with
+	pub name: String,
+	/// When the snapshot was taken, to the second
+	pub stamp: SystemTime,
+}
+
+/// How a pool stores its objects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolType {
+	/// Each object is stored as several full copies
+	Replicated,
+	/// Each object is split into data and coding chunks
+	Erasure,
+}
+
+/// The limits set on a pool, see `Cluster::get_pool_quota()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolQuota {
+	/// The most bytes the pool may hold, `None` for no limit
+	pub max_bytes: Option<u64>,
+	/// The most objects the pool may hold, `None` for no limit
+	pub max_objects: Option<u64>,
+}
+
+/// The placement geometry of a pool, see `IoCtx::pool_layout()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolLayout {
+	/// The number of placement groups
+	pub pg_num: u32,
+	/// The number of placement groups used for placement, which trails
+	/// `pg_num` while the pool is being split
+	pub pgp_num: u32,
+	/// The number of replicas, or of data and coding chunks in an erasure
+	/// coded pool
+	pub size: u32,
+	/// The fewest replicas or chunks a placement group accepts writes with
+	pub min_size: u32,
+}
+
+/// The size, modification time and version of an object, see
+/// `IoCtx::object_meta()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectMeta {
+	/// The size of the object, in bytes
+	pub size: u64,
+	/// When the object was last modified, to the second
+	pub mtime: SystemTime,
+	/// The object's version, which changes with every write to it
+	pub version: u64,
+}
+
+/// The data, size, modification time, version and xattrs of an object,
+/// all read at once by `IoCtx::fetch()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSnapshot {
+	/// The contents of the object
+	pub data: Vec<u8>,
+	/// The size of the object, in bytes, the same as `data.len()`
+	pub size: u64,
+	/// When the object was last modified, to the second
+	pub mtime: SystemTime,
+	/// The object's version, which changes with every write to it
+	pub version: u64,
+	/// The extended attributes of the object, by name
+	pub xattrs: HashMap<String, Vec<u8>>,
+}
+
+/// How many objects a namespace holds, and their total size, see
+/// `IoCtx::namespace_stats()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NamespaceStats {
+	/// The number of objects in the namespace
+	pub objects: u64,
+	/// The sum of the sizes of those objects, in bytes
+	pub bytes: u64,
+}
+
+/// What `IoCtx::put()` expects of the object it writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+	/// Create the object, or replace it if it exists
+	CreateOrReplace,
+	/// Only create a new object, failing with `RadosError::AlreadyExists`
+	/// if it exists
+	CreateNew,
+	/// Only replace an existing object, failing with
+	/// `RadosError::ObjectNotFound` if it's missing
+	ReplaceExisting,
+}
+
+/// The algorithms `IoCtx::checksum()` can compute on the OSDs
+#[cfg(feature = "luminous")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumType {
+	/// 32 bit xxHash, seeded with 0
+	XxHash32,
+	/// 64 bit xxHash, seeded with 0
+	XxHash64,
+	/// CRC-32C (Castagnoli), starting from `0xffffffff` without a final
+	/// inversion, like Ceph's own `ceph_crc32c(-1, ...)`
+	Crc32c,
+}
+
+#[cfg(feature = "luminous")]
+impl ChecksumType {
+	/// The `rados_checksum_type_t` value and the size in bytes of the
+	/// checksum (which is also the size of the init value)
+	fn raw(&self) -> (c_int, usize) {
+		match *self {
+			ChecksumType::XxHash32 => (0, 4),
+			ChecksumType::XxHash64 => (1, 8),
+			ChecksumType::Crc32c => (2, 4),
+		}
+	}
+}
+
+/// The output buffer `IoCtx::exec()` starts with
+const EXEC_INITIAL_BUF_SIZE: usize = 4096;
+/// The largest buffer `fill_buffer()` grows to, so `IoCtx::exec()` output
+/// or an xattr value can be up to 64 MiB
+const MAX_BUF_SIZE: usize = 64 * 1024 * 1024;
+
+/// How much `IoCtx::read_full()` and `IoCtx::download()` read at a time,
+/// unless changed with `IoCtx::set_chunk_size()`
+const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The most `rados_write()` and friends accept in one call, `UINT_MAX/2`
+const MAX_WRITE_SIZE: usize = (u32::MAX / 2) as usize;
+
+/// The snapshot id meaning "no snapshot", i.e. the current object
+const LIBRADOS_SNAP_HEAD: rados_snap_t = !1;
+
+/// How long `IoCtx::write_when_healthy()` reuses the cluster health it got,
+/// unless changed with `IoCtx::set_health_ttl()`
+const DEFAULT_HEALTH_TTL_MS: usize = 5000;
+
+/// How many objects `IoCtx::list_objects_with_size()` stats at once
+const STAT_BATCH_SIZE: usize = 32;
+
+/// How many object names `IoCtx::list_objects_stream()` lists ahead of its
+/// consumer
+#[cfg(feature = "futures")]
+const LIST_STREAM_BUFFER: usize = 64;
+
+/// The xattr `IoCtx::set_tag()` and friends keep an object's tags in, as a
+/// JSON array of strings
+const TAGS_XATTR: &'static str = "ceph-rs.tags";
+
+/// The xattr `IoCtx::write_record()` stores the CRC-32C of an object's data
+/// in, as 8 lowercase hex digits
+pub const RECORD_CRC_XATTR: &'static str = "ceph-rs.crc32c";
+
+/// How many omap entries `IoCtx::export_omap()` fetches per read operation
+const OMAP_PAGE_SIZE: u64 = 1024;
+
+/// The buffer `IoCtx::fetch()` first reads an object into
+const FETCH_INITIAL_BUF_SIZE: usize = 64 * 1024;
+
+/// The bytes a stream written by `IoCtx::export()` starts with
+const EXPORT_MAGIC: &'static [u8; 8] = b"ceph-rs1";
+
+/// How much data `IoCtx::copy_data()` moves through the client at a time
+const COPY_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The io contexts created from a cluster handle that are still open,
+/// shared by the `Cluster` and its `IoCtx`s, so the handle is only shut
+/// down once none of them uses it any more
+struct OpenIoCtxs {
+	handles: Vec<usize>,
+	/// Whether the `Cluster` was shut down while contexts were open, so the
+	/// last of them to close shuts the handle down
+	orphaned: bool,
+}
+
+pub struct Cluster {
+	handle: rados_t,
+	/// The io contexts created from this cluster that are still open, so
+	/// `close()` can flush them before shutting down
+	ioctxs: Arc<Mutex<OpenIoCtxs>>,
+	/// Whether `connect()` succeeded (and the handle wasn't shut down since),
+	/// as librados crashes when most calls are made before that
+	connected: AtomicBool,
+	/// Whether dropping the handle leaves it alone instead of shutting it
+	/// down, see `forget_on_drop()`
+	forget_on_drop: AtomicBool,
+	/// Whether `shutdown()` was called, so it's only done once
+	shut_down: AtomicBool,
+}
+
+pub struct IoCtx {
+	handle: rados_ioctx_t,
+	ioctxs: Arc<Mutex<OpenIoCtxs>>,
+	/// Timeout for `read_at()`/`write_at()` in milliseconds, `0` for none
+	op_timeout_ms: AtomicUsize,
+	/// Chunk size for `read_full()`/`download()`, see `set_chunk_size()`
+	chunk_size: AtomicUsize,
+	/// How long the cached cluster health is used, in milliseconds, see
+	/// `set_health_ttl()`
+	health_ttl_ms: AtomicUsize,
+	/// The cluster health `write_when_healthy()` last got, and when
+	health: Mutex<Option<(Instant, HealthStatus)>>,
+}
+
+/// An optional string argument, given as a `&str`, a `String` or an
+/// `Option<String>`
+pub trait StrStringOrNone {
+	/// The string as a `CString`, `None` if there's none, and
+	/// `Err(RadosError::Other(EINVAL))` if it has a NUL byte in it
+	fn into_cstring(self) -> Result<Option<CString>, RadosError>;
+}
+
+impl StrStringOrNone for String {
+	fn into_cstring(self) -> Result<Option<CString>, RadosError> {
+		Ok(Some(CString::new(self)?))
+	}
+}
+
+impl<'a> StrStringOrNone for &'a str {
+	fn into_cstring(self) -> Result<Option<CString>, RadosError> {
+		Ok(Some(CString::new(self)?))
+	}
+}
+
+impl StrStringOrNone for Option<String> {
+	fn into_cstring(self) -> Result<Option<CString>, RadosError> {
+		match self {
+			Some(s) => Ok(Some(CString::new(s)?)),
+			None => Ok(None),
+		}
+	}
+}
+
+macro_rules! handle_errors {
+	($x:expr) => {
+		handle_errors!($x, RadosError::from_return_code)
+	};
+	($x:expr, $from:path) => {
+		unsafe {
+			let err = $x;
+			if err < 0 {
+				let e = $from(err);
+				println!("strerror({:?}) => {}", err, e);
+				return Err(e);
+			}
+			err
+		}
+	}
+}
+
+impl Cluster {
+
+	/// Create a handle for communicating with a RADOS cluster.
+	///
+	/// Ceph environment variables are read when this is called, so if
+	/// $CEPH_ARGS specifies everything you need to connect, no further
+	/// configuration is necessary.
+	///
+	/// # Parameters
+	///
+	/// * `cluster_name` the cluster name as a string, or `None` (equivalent to `null`)
+	/// * `user_name` the full user name to connect as (i.e. "client.admin"`)
+	/// * `flags` for future expansion, `CreateFlags::empty()`
+	///
+	/// # Returns
+	///
+	/// * `Ok(Cluster)` on success
+	/// * `Err(RadosError)` on failure
+	///
+	/// # Examples
+	///
+	/// ```rust,no_run
+	/// use ceph::rados::{Cluster, CreateFlags};
+	///
+	/// let cluster = Cluster::create("ceph", "client.admin", CreateFlags::empty()).unwrap();
+	/// ```
+	pub fn create<A, S>(cluster_name: A, user_name: S, flags: CreateFlags) -> Result<Cluster, RadosError>
+		where A: StrStringOrNone,
+		S: Into<Vec<u8>>
+	{
+	    let cluster_name = cluster_name.into_cstring()?;
+	    let cluster_name_ptr = match cluster_name {
+	    	None => ptr::null(),
+	    	Some(ref cs) => cs.as_ptr()
+	    };
+	    let user_name = CString::new(user_name)?;
+	    let user_name_ptr = user_name.as_ptr();
+		let handle: c_void_ptr = ptr::null_mut();
+	    handle_errors!(rados_create2(&handle, cluster_name_ptr, user_name_ptr, flags.bits()), RadosError::from_cluster_return_code);
+		return Ok(Cluster { handle: handle, ioctxs: Arc::new(Mutex::new(OpenIoCtxs { handles: Vec::new(), orphaned: false })),
+			connected: AtomicBool::new(false), forget_on_drop: AtomicBool::new(false), shut_down: AtomicBool::new(false) });
+	}
+
+	/// Wrap a `rados_t` cluster handle created elsewhere, e.g. by another
+	/// library using librados.
+	///
+	/// The handle counts as connected if librados gave it an instance id
+	/// (see `instance_id()`). The `Cluster` owns the handle, and shuts it
+	/// down when dropped; call `forget_on_drop()` if the handle is only
+	/// borrowed, or get it back with `into_raw()`.
+	///
+	/// # Safety
+	///
+	/// `handle` must be a valid handle from `rados_create()` or
+	/// `rados_create2()`, not shut down, and not used concurrently from
+	/// elsewhere in ways librados doesn't allow.
+	pub unsafe fn from_raw(handle: *const c_void) -> Cluster {
+		let connected = rados_get_instance_id(handle) != 0;
+		Cluster { handle: handle, ioctxs: Arc::new(Mutex::new(OpenIoCtxs { handles: Vec::new(), orphaned: false })),
+			connected: AtomicBool::new(connected), forget_on_drop: AtomicBool::new(false), shut_down: AtomicBool::new(false) }
+	}
+
+	/// Give up the `rados_t` handle without shutting it down, e.g. to hand
+	/// it to another library, which then owns it.
+	///
+	/// Io contexts created from the `Cluster` stay usable for as long as
+	/// the handle isn't shut down.
+	pub fn into_raw(mut self) -> *const c_void {
+		let handle = self.handle;
+		// Keep Drop from shutting it down
+		self.handle = ptr::null();
+		handle
+	}
+
+	/// Leave the handle alone when the `Cluster` is dropped, instead of
+	/// shutting it down, e.g. when it was borrowed with `from_raw()` and
+	/// its owner shuts it down. From then on `shutdown()` does nothing,
+	/// and `close()` only flushes the writes of the open io contexts.
+	pub fn forget_on_drop(&self) {
+		self.forget_on_drop.store(true, Ordering::SeqCst);
+	}
+
+
+	/// Connect to the cluster.
+	///
+	/// # Note
+	///
+	/// **BUG:** Before calling this, calling a function that communicates with the
+	/// cluster will crash. The methods of `Cluster` that need a connection
+	/// check for one first, and fail with `RadosError::NotConnected` instead.
+	///
+	/// # Prerequisites
+	///
+	/// The cluster handle is configured with at least a monitor address.
+	/// If cephx is enabled, a client name and secret must also be set.
+	///
+	/// # Post
+	///
+	/// If this succeeds, any function in librados may be used
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on sucess
+	/// * `Err(RadosError)` on failure
+	pub fn connect(&self) -> Result<(), RadosError> {
+		handle_errors!(rados_connect(self.handle), RadosError::from_cluster_return_code);
+		self.connected.store(true, Ordering::SeqCst);
+		return Ok(());
+	}
+
+	/// Connect to the cluster without blocking the calling task, for async
+	/// code.
+	///
+	/// `rados_connect()` blocks until the monitors answer, which can take
+	/// seconds, so it's run on a thread of its own, named `rados-connect`;
+	/// the returned future resolves with the cluster handle once connected.
+	/// The handle is moved to that thread and back, so it must be fully
+	/// configured first.
+	///
+	/// There's no blocking thread pool: each call starts, and ends, one OS
+	/// thread, which is fine for connecting at startup but not for calling
+	/// in a loop. There's no `ClusterBuilder` either, so this is a method
+	/// of the configured `Cluster` rather than of a builder.
+	///
+	/// # Returns
+	///
+	/// * `Ok(Cluster)` once connected
+	/// * `Err(RadosError)` on failure, `EIO` if the thread couldn't be
+	///   started or panicked
+	///
+	/// # Examples
+	///
+	/// ```rust,no_run
+	/// # extern crate ceph;
+	/// # extern crate futures;
+	/// use ceph::rados::{Cluster, CreateFlags};
+	///
+	/// # fn main() {
+	/// let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	/// cluster.conf_read_file(None).unwrap();
+	/// let cluster = futures::executor::block_on(cluster.connect_async()).unwrap();
+	/// # }
+	/// ```
+	#[cfg(feature = "futures")]
+	pub fn connect_async(self) -> impl Future<Output = Result<Cluster, RadosError>> {
+		let (tx, rx) = oneshot::channel();
+		// If the thread can't be started, the handle and `tx` are dropped
+		// with the closure, and `rx` is canceled
+		let _ = thread::Builder::new().name("rados-connect".to_string()).spawn(move || {
+			let result = self.connect().map(|_| self);
+			let _ = tx.send(result);
+		});
+		rx.map(|result| match result {
+			Ok(result) => result,
+			// The connecting thread panicked, or didn't start
+			Err(_) => Err(RadosError::Other(libc::EIO)),
+		})
+	}
+
+	/// Whether `connect()` succeeded, and the handle wasn't shut down since
+	pub fn is_connected(&self) -> bool {
+		self.connected.load(Ordering::SeqCst)
+	}
+
+	/// Fail with `RadosError::NotConnected` unless `connect()` succeeded
+	fn check_connected(&self) -> Result<(), RadosError> {
+		if !self.is_connected() {
+			return Err(RadosError::NotConnected);
+		}
+		Ok(())
+	}
+
+	/// Configure the cluster handle using a Ceph config file
+	///
+	/// If path is `NULL`, the default locations are searched, and the first
+	/// found is used. The locations are:
+	///
+	/// * `$CEPH_CONF` (environment variable), if set, instead of the others
+	/// * `$data_dir/config`
+	/// * `/etc/ceph/$cluster.conf`
+	/// * `~/.ceph/$cluster.conf`
+	/// * `$cluster.conf` (in the current working directory)
+	///
+	/// where `$cluster` is the cluster name, `ceph` by default. librados
+	/// doesn't say which file it read, so after a successful read the same
+	/// locations are checked here, and the first existing one is returned.
+	///
+	/// # Prerequisites
+	///
+	/// `rados_connect()` has not been called on the cluster handle
+	///
+	/// # Parameters
+	///
+	/// * `cluster` cluster handle to configure
+	/// * `path` path to a Ceph configuration file
+	///
+	/// # Returns
+	///
+	/// * `Ok(Some(path))` on success, with the path of the file read
+	/// * `Ok(None)` on success, if the file read can't be determined
+	/// * `Err(RadosError)` on failure
+	pub fn conf_read_file<S>(&self, config_filename: S) -> Result<Option<PathBuf>, RadosError>
+		where S: StrStringOrNone
+	{
+		let config_filename = config_filename.into_cstring()?;
+	    let config_filename_ptr = match config_filename {
+	    	None => ptr::null(),
+	    	Some(ref cs) => cs.as_ptr()
+	    };
+		handle_errors!(rados_conf_read_file(self.handle, config_filename_ptr), RadosError::from_cluster_return_code);
+		if let Some(cs) = config_filename {
+			return Ok(Some(PathBuf::from(cs.to_string_lossy().into_owned())));
+		}
+		let search = match env::var("CEPH_CONF") {
+			Ok(conf) => conf,
+			Err(_) => "$data_dir/config, /etc/ceph/$cluster.conf, ~/.ceph/$cluster.conf, $cluster.conf".to_string(),
+		};
+		// The file was read: not being able to tell which one it was isn't
+		// a failure
+		let (cluster, data_dir) = match (self.conf_get("cluster"), self.conf_get("data_dir")) {
+			(Ok(cluster), Ok(data_dir)) => (cluster, data_dir),
+			_ => return Ok(None),
+		};
+		let home = env::var("HOME").unwrap_or_default();
+		return Ok(find_conf_file(&search, &cluster, &data_dir, &home));
+	}
+
+	/// Configure the cluster handle from several Ceph config files, read
+	/// in order, e.g. a base configuration followed by local overrides.
+	///
+	/// Options set by a file override the same options set by the files
+	/// before it; options a file doesn't mention keep their values.
+	///
+	/// # Prerequisites
+	///
+	/// `rados_connect()` has not been called on the cluster handle
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err((path, RadosError))` with the first file that couldn't be
+	///   read; the files before it have been applied, the ones after it
+	///   haven't
+	pub fn conf_read_files(&self, paths: &[&str]) -> Result<(), (String, RadosError)> {
+		for path in paths {
+			if let Err(e) = self.conf_read_file(*path) {
+				return Err((path.to_string(), e));
+			}
+		}
+		Ok(())
+	}
+
+	/// Configure the cluster handle with command line arguments
+	///
+	/// argv can contain any common Ceph command line option, including any
+	/// configuration parameter prefixed by '--' and replacing spaces with
+	/// dashes or underscores. For example, the following options are equivalent:
+	///
+	/// * `--mon-host 10.0.0.1:6789`
+	/// * `--mon_host 10.0.0.1:6789`
+	/// * `-m 10.0.0.1:6789`
+	///
+	/// # Prerequisites
+	///
+	/// `rados_connect()` has not been called on the cluster handle
+	///
+	/// # Parameters
+	///
+	/// * `cluster` cluster handle to configure
+	/// * `args` Vec of String arguments, e.g. `env::args().collect()`
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+ 	pub fn conf_parse_argv(&self, args: &Vec<String>) -> Result<(), RadosError> {
+		let argc = args.len() as i32;
+		let args_cs = args.iter().map(|a| CString::new(a.as_str())).collect::<Result<Vec<CString>, NulError>>()?;
+		let argv : Vec<*const c_char> = args_cs.iter().map(|cs| cs.as_ptr()).collect();
+		handle_errors!(rados_conf_parse_argv(self.handle, argc, argv.as_slice().as_ptr()), RadosError::from_cluster_return_code);
+		return Ok(());
+	}
+
+	/// Set a configuration option
+	///
+	/// # Parameters
+	///
+	/// * `option` the option to set, e.g. `"rados_osd_op_timeout"`
+	/// * `value` the value of the option
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `ENOENT` if `option` isn't a Ceph
+	///   configuration option
+	pub fn conf_set(&self, option: &str, value: &str) -> Result<(), RadosError> {
+		let option_cs = CString::new(option)?;
+		let value_cs = CString::new(value)?;
+		handle_errors!(rados_conf_set(self.handle, option_cs.as_ptr(), value_cs.as_ptr()), RadosError::from_cluster_return_code);
+		return Ok(());
+	}
+
+	/// Change which Ceph user, e.g. `"client.backup"`, the cluster handle
+	/// authenticates as, for callers that only know it after `create()`.
+	///
+	/// This sets the `name` option, so it must be done before `connect()`;
+	/// the user's key is then looked up in the configured keyring. If the
+	/// linked librados doesn't allow changing the name at runtime, its
+	/// error is returned and the user must be passed to `create()` instead.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `name` isn't of the form
+	///   `<type>.<id>`, and `EISCONN` if the handle is already connected
+	pub fn set_user(&self, name: &str) -> Result<(), RadosError> {
+		if self.is_connected() {
+			return Err(RadosError::Other(libc::EISCONN));
+		}
+		match name.find('.') {
+			Some(dot) if dot > 0 && dot < name.len() - 1 => {},
+			_ => return Err(RadosError::Other(libc::EINVAL)),
+		}
+		self.conf_set("name", name)
+	}
+
+	/// Authenticate with the base64 encoded cephx `key` given here, rather
+	/// than one read from a keyring file, e.g. for keys kept in a secrets
+	/// manager. This sets the `key` option, so it must be done before
+	/// `connect()`.
+	///
+	/// The key stays in the memory of the process, in librados'
+	/// configuration, for as long as the cluster handle exists, and can be
+	/// read back with `conf_get("key")`. It is not wiped on shutdown, so
+	/// keep the handle away from untrusted code, and avoid logging the
+	/// configuration.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	///
+	/// # Examples
+	///
+	/// ```rust,no_run
+	/// # use ceph::rados::{Cluster, CreateFlags};
+	/// use std::env;
+	///
+	/// let cluster = Cluster::create(None, "client.backup", CreateFlags::empty()).unwrap();
+	/// cluster.conf_set("mon_host", "10.0.0.1").unwrap();
+	/// cluster.set_key(&env::var("CEPH_KEY").unwrap()).unwrap();
+	/// cluster.connect().unwrap();
+	/// ```
+	pub fn set_key(&self, key: &str) -> Result<(), RadosError> {
+		self.conf_set("key", key)
+	}
+
+	/// Read the user's key from the keyring file at `path`, instead of the
+	/// default keyring locations. This sets the `keyring` option, so it
+	/// must be done before `connect()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn set_keyring(&self, path: &str) -> Result<(), RadosError> {
+		self.conf_set("keyring", path)
+	}
+
+	/// Change the name this client identifies as, e.g. `"client.ingest"`,
+	/// as if it had been given to `create()`, so that its sessions can be
+	/// told apart from other clients' on the monitors and OSDs (`ceph
+	/// daemon osd.<id> sessions`). The operations `ceph daemon osd.<id>
+	/// ops` lists are shown under the client's global id instead, as
+	/// `client.<instance_id()>`, which the sessions map back to this name.
+	///
+	/// This is `set_user()`, so it must be done before `connect()`. The
+	/// name is also the user the client authenticates as, so it needs a
+	/// key of its own, see `set_key()` and `set_keyring()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `name` isn't of the form
+	///   `<type>.<id>`, and `EISCONN` if the handle is already connected
+	pub fn set_client_name(&self, name: &str) -> Result<(), RadosError> {
+		self.set_user(name)
+	}
+
+	/// Get the value of a configuration option
+	///
+	/// # Parameters
+	///
+	/// * `option` the option to read, e.g. `"mon_host"`
+	///
+	/// # Returns
+	///
+	/// * `Ok(value)` on success
+	/// * `Err(RadosError)` on failure, `ENOENT` if `option` isn't a Ceph
+	///   configuration option
+	pub fn conf_get(&self, option: &str) -> Result<String, RadosError> {
+		let option_cs = CString::new(option)?;
+		let filled = fill_buffer(256, |buf| unsafe {
+			rados_conf_get(self.handle, option_cs.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len() as size_t)
+		});
+		match filled {
+			Ok((buf, _)) => Ok(c_buf_to_string(&buf)),
+			Err(err) => Err(RadosError::from_cluster_return_code(err)),
+		}
+	}
+
+	/// Get the addresses of the monitors the client contacts, from the
+	/// effective `mon_host` option.
+	///
+	/// `mon_host` may list monitors separated by commas, semicolons or
+	/// spaces, and a monitor may have several addresses, as in
+	/// `[v2:10.0.0.1:3300,v1:10.0.0.1:6789]`. Each address is returned
+	/// separately, without its `v1:`/`v2:` protocol prefix or `/nonce`
+	/// suffix.
+	///
+	/// # Returns
+	///
+	/// * `Ok(addresses)` on success, empty if `mon_host` isn't set (e.g.
+	///   when monitors are looked up in DNS)
+	/// * `Err(RadosError)` on failure
+	pub fn mon_hosts(&self) -> Result<Vec<String>, RadosError> {
+		Ok(parse_mon_hosts(&self.conf_get("mon_host")?))
+	}
+
+	/// Get the fsid of the cluster as a hexadecimal string.
+	///
+	/// The fsid is a unique id of an entire Ceph cluster.
+	///
+	/// # Returns
+	///
+	/// * `Ok(fsid)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::NotConnected` before
+	///   `connect()`
+	pub fn fsid(&self) -> Result<String, RadosError> {
+		self.check_connected()?;
+		// 36 characters for a UUID and a '\0'; grown if ever too short
+		let filled = fill_buffer(37, |buf| unsafe {
+			rados_cluster_fsid(self.handle, buf.as_mut_ptr() as *mut c_char, buf.len() as size_t)
+		});
+		match filled {
+			Ok((buf, _)) => Ok(c_buf_to_string(&buf)),
+			Err(err) => Err(RadosError::from_cluster_return_code(err)),
+		}
+	}
+
+	/// Get the overall health of the cluster, from a `health` mon command.
+	///
+	/// # Returns
+	///
+	/// * `Ok(HealthStatus)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn health(&self) -> Result<HealthStatus, RadosError> {
+		self.check_connected()?;
+		cluster_health(self.handle)
+	}
+
+	/// Read usage information about the whole cluster.
+	///
+	/// The figures are eventually consistent: they aren't updated as soon
+	/// as data is written.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ClusterStat)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn cluster_stat(&self) -> Result<ClusterStat, RadosError> {
+		self.check_connected()?;
+		cluster_stat_raw(self.handle)
+	}
+
+	/// Get the global id the monitors assigned to this client instance.
+	///
+	/// The id uniquely identifies the current connection to the cluster,
+	/// and is what shows up as `client.<id>` in server side logs and in
+	/// blocklist entries.
+	///
+	/// # Prerequisites
+	///
+	/// `connect()` has been called on the cluster handle. Before that the id
+	/// is `0`.
+	pub fn instance_id(&self) -> u64 {
+		unsafe {
+			rados_get_instance_id(self.handle)
+		}
+	}
+
+	/// Send a command to the monitors.
+	///
+	/// `cmd` is the command in the JSON form the monitors expect, e.g.
+	/// `{"prefix": "osd dump", "format": "json"}`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(output)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::CommandFailed` with the
+	///   status string if the command was refused
+	pub fn mon_command(&self, cmd: &str) -> Result<Vec<u8>, RadosError> {
+		self.check_connected()?;
+		mon_command_raw(self.handle, cmd).map(|(out, _)| out)
+	}
+
+	/// Send a command to the primary OSD of the `pgid` placement group.
+	///
+	/// `cmd` is the command in JSON form, e.g. `{"prefix": "query"}`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(output)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::CommandFailed` with the
+	///   status string if the command was refused
+	pub fn pg_command(&self, pgid: &str, cmd: &str) -> Result<Vec<u8>, RadosError> {
+		self.check_connected()?;
+		pg_command_raw(self.handle, pgid, cmd).map(|(out, _)| out)
+	}
+
+	/// Send a command to the `osd` OSD, as `ceph tell osd.<osd>` does.
+	///
+	/// `cmd` is the command in JSON form, e.g. `{"prefix": "version"}`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(output)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::CommandFailed` with the
+	///   status string if the command was refused
+	pub fn osd_command(&self, osd: i32, cmd: &str) -> Result<Vec<u8>, RadosError> {
+		self.check_connected()?;
+		osd_command_raw(self.handle, osd, cmd).map(|(out, _)| out)
+	}
+
+	/// List the object classes, e.g. `"lock"` or `"rbd"`, the OSDs are
+	/// allowed to load, from the `osd_class_load_list` option of the first
+	/// OSD in the cluster. The option is `"*"` for every class installed on
+	/// them, which is returned as `ObjectClasses::All`.
+	///
+	/// This is configuration, not availability: OSDs load classes on first
+	/// use and don't report which ones they have loaded, so a class listed
+	/// here can still be missing from the OSDs, and `IoCtx::exec()` still
+	/// fails with `EOPNOTSUPP` then.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectClasses)` on success
+	/// * `Err(RadosError)` on failure, `ENOENT` if the cluster has no OSDs
+	pub fn list_object_classes(&self) -> Result<ObjectClasses, RadosError> {
+		let osds = self.mon_command(&json!({"prefix": "osd ls", "format": "json"}).to_string())?;
+		let osds: Value = serde_json::from_slice(&osds)?;
+		let osd = match osds.as_array().and_then(|osds| osds.first()).and_then(|osd| osd.as_i64()) {
+			Some(osd) => osd as i32,
+			None => return Err(RadosError::Other(libc::ENOENT)),
+		};
+		let cmd = json!({"prefix": "config get", "var": "osd_class_load_list", "format": "json"});
+		let reply: Value = serde_json::from_slice(&self.osd_command(osd, &cmd.to_string())?)?;
+		match reply["osd_class_load_list"].as_str() {
+			Some(classes) => Ok(parse_object_classes(classes)),
+			None => Err(RadosError::UnexpectedReply(format!("no osd_class_load_list from osd.{}", osd))),
+		}
+	}
+
+	/// Count the OSDs in the cluster, and how many of them are up, from an
+	/// `osd stat` mon command.
+	///
+	/// # Returns
+	///
+	/// * `Ok((total, up))` on success
+	/// * `Err(RadosError)` on failure
+	pub fn osd_count(&self) -> Result<(u32, u32), RadosError> {
+		self.check_connected()?;
+		let reply = mon_command_json(self.handle, &json!({"prefix": "osd stat", "format": "json"}))?;
+		// Before Nautilus the counts are nested in an "osdmap" object
+		let stat = if reply["osdmap"].is_object() { &reply["osdmap"] } else { &reply };
+		let field = |var: &str| match stat[var].as_u64() {
+			Some(value) => Ok(value as u32),
+			None => Err(RadosError::UnexpectedReply(format!("no {} in osd stat", var))),
+		};
+		Ok((field("num_osds")?, field("num_up_osds")?))
+	}
+
+	/// Ask the primary OSD of the `pgid` placement group to scrub it, or
+	/// to deep scrub it (also reading and comparing the object data) when
+	/// `deep` is `true`.
+	///
+	/// The command goes straight to the OSD, as `ceph tell <pgid> scrub`
+	/// does, so it uses the OSD's spellings `scrub` and `deep_scrub`
+	/// rather than the monitor's `pg scrub` and `pg deep-scrub`, which
+	/// would take a round trip through the monitors to reach the same OSD
+	/// command. The scrub itself runs in the background; this returns once
+	/// the OSD has accepted the request.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `pgid` isn't of the form
+	///   `<pool id>.<hex seed>`
+	pub fn scrub_pg(&self, pgid: &str, deep: bool) -> Result<(), RadosError> {
+		if !is_valid_pgid(pgid) {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let prefix = if deep { "deep_scrub" } else { "scrub" };
+		let cmd = json!({"prefix": prefix, "pgid": pgid});
+		self.pg_command(pgid, &cmd.to_string()).map(|_| ())
+	}
+
+	/// Wait until the `pgid` placement group is in `state`, e.g.
+	/// `"active+clean"` once recovery is done.
+	///
+	/// The state is polled with a `query` pg command, waiting 100ms between
+	/// the first polls and doubling that up to 5s. It has to match exactly,
+	/// so `"active+clean"` isn't reached while the PG is
+	/// `"active+clean+scrubbing"`. While the PG has no primary to answer
+	/// (`EAGAIN`), polling goes on.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` once the PG is in `state`
+	/// * `Err(RadosError::Timeout)` if it isn't within `timeout`
+	/// * `Err(RadosError)` on other failures, `EINVAL` if `pgid` isn't of
+	///   the form `<pool id>.<hex seed>`
+	pub fn wait_for_pg_state(&self, pgid: &str, state: &str, timeout: Duration) -> Result<(), RadosError> {
+		if !is_valid_pgid(pgid) {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let cmd = json!({"prefix": "query", "format": "json"}).to_string();
+		let deadline = deadline_after(timeout);
+		let mut backoff = Duration::from_millis(100);
+		loop {
+			match self.pg_command(pgid, &cmd) {
+				Ok(out) => {
+					let reply: Value = serde_json::from_slice(&out)?;
+					match reply["state"].as_str() {
+						Some(current) if current == state => return Ok(()),
+						Some(_) => {},
+						None => return Err(RadosError::UnexpectedReply(format!("no state for pg {}", pgid))),
+					}
+				},
+				Err(RadosError::CommandFailed(libc::EAGAIN, _)) => {},
+				Err(e) => return Err(e),
+			}
+			let now = Instant::now();
+			match deadline {
+				Some(deadline) if now >= deadline => return Err(RadosError::Timeout),
+				Some(deadline) => thread::sleep(cmp::min(backoff, deadline - now)),
+				None => thread::sleep(backoff),
+			}
+			backoff = cmp::min(backoff * 2, Duration::from_secs(5));
+		}
+	}
+
+	// The objects a scrub found inconsistent can't be listed from here:
+	// `rados list-inconsistent-obj` reads them with a CEPH_OSD_OP_SCRUBLS
+	// operation on the PG, which librados only issues from its C++ API
+	// (get_inconsistent_objects()); there's no rados_* function for it, and
+	// no pg command (`query`, `list_unfound`, ...) reports them.
+
+	/// Get the oldest client release the cluster accepts connections from,
+	/// e.g. `"luminous"`, as set by `ceph osd set-require-min-compat-client`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(release_name)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn min_compat_client(&self) -> Result<String, RadosError> {
+		let out = self.mon_command(&json!({"prefix": "osd get-require-min-compat-client"}).to_string())?;
+		let release = String::from_utf8_lossy(&out).trim().to_string();
+		if release.is_empty() {
+			return Err(RadosError::UnexpectedReply("no release name in reply".to_string()));
+		}
+		Ok(release)
+	}
+
+	/// List the ids of the placement groups of the `pool` pool, e.g.
+	/// `"1.0"`, `"1.1"`, ...
+	///
+	/// # Returns
+	///
+	/// * `Ok(pgids)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn list_pgs(&self, pool: &str) -> Result<Vec<String>, RadosError> {
+		self.check_connected()?;
+		let reply = mon_command_json(self.handle, &json!({"prefix": "pg ls-by-pool", "poolstr": pool, "format": "json"}))?;
+		// Nautilus wraps the PG stats in an object, older releases don't
+		let stats = match reply.get("pg_stats") {
+			Some(stats) => stats,
+			None => &reply,
+		};
+		let stats = match stats.as_array() {
+			Some(stats) => stats,
+			None => return Err(RadosError::UnexpectedReply(format!("no PG list for pool {}", pool))),
+		};
+		let mut pgids = Vec::with_capacity(stats.len());
+		for stat in stats {
+			match stat["pgid"].as_str() {
+				Some(pgid) => pgids.push(pgid.to_string()),
+				None => return Err(RadosError::UnexpectedReply(format!("PG without pgid in pool {}", pool))),
+			}
+		}
+		Ok(pgids)
+	}
+
+	/// Create a replicated pool named `name` with the cluster's default
+	/// settings.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `RadosError::AlreadyExists` if the
+	///   pool already exists
+	pub fn create_pool(&self, name: &str) -> Result<(), RadosError> {
+		self.check_connected()?;
+		let name_cs = CString::new(name)?;
+		handle_errors!(rados_pool_create(self.handle, name_cs.as_ptr()), RadosError::from_cluster_return_code);
+		return Ok(());
+	}
+
+	/// Create a pool named `name` with `pg_num` placement groups, using the
+	/// `osd pool create` monitor command.
+	///
+	/// # Parameters
+	///
+	/// * `crush_rule` the name of the CRUSH rule to place the pool with,
+	///   `None` for the cluster's default for `pool_type`
+	/// * `pool_type` whether to replicate the pool or erasure code it; an
+	///   erasure coded pool uses the `default` erasure code profile
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `name` is empty or
+	///   `pg_num` is `0`
+	pub fn create_pool_with(&self, name: &str, pg_num: u32, crush_rule: Option<&str>, pool_type: PoolType) -> Result<(), RadosError> {
+		if name.is_empty() || pg_num == 0 {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let mut cmd = json!({
+			"prefix": "osd pool create",
+			"pool": name,
+			"pg_num": pg_num,
+			"pool_type": match pool_type {
+				PoolType::Replicated => "replicated",
+				PoolType::Erasure => "erasure",
+			},
+		});
+		if let Some(rule) = crush_rule {
+			cmd["rule"] = json!(rule);
+		}
+		self.mon_command(&cmd.to_string()).map(|_| ())
+	}
+
+	/// Limit how much the `pool` pool may hold, with the `osd pool
+	/// set-quota` monitor command.
+	///
+	/// A limit that is `None` is left as it is; setting it to `Some(0)`
+	/// removes it. Writes to a pool over its quota are held (or fail with
+	/// `RadosError::QuotaExceeded` when they ask not to be held) until
+	/// space is freed.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if both limits are `None`
+	pub fn set_pool_quota(&self, pool: &str, max_bytes: Option<u64>, max_objects: Option<u64>) -> Result<(), RadosError> {
+		if max_bytes.is_none() && max_objects.is_none() {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		for &(field, limit) in &[("max_bytes", max_bytes), ("max_objects", max_objects)] {
+			if let Some(limit) = limit {
+				let cmd = json!({"prefix": "osd pool set-quota", "pool": pool, "field": field, "val": limit.to_string()});
+				self.mon_command(&cmd.to_string())?;
+			}
+		}
+		return Ok(());
+	}
+
+	/// Get the limits set on the `pool` pool with `set_pool_quota()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(PoolQuota)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn get_pool_quota(&self, pool: &str) -> Result<PoolQuota, RadosError> {
+		self.check_connected()?;
+		let reply = mon_command_json(self.handle, &json!({"prefix": "osd pool get-quota", "pool": pool, "format": "json"}))?;
+		let limit = |name: &str| match reply[name].as_u64() {
+			Some(0) => Ok(None),
+			Some(limit) => Ok(Some(limit)),
+			None => Err(RadosError::UnexpectedReply(format!("no {} for pool {}", name, pool))),
+		};
+		Ok(PoolQuota { max_bytes: limit("quota_max_bytes")?, max_objects: limit("quota_max_objects")? })
+	}
+
+	/// Open an io context on the `pool_name` pool, to read and write its
+	/// objects.
+	///
+	/// # Returns
+	///
+	/// * `Ok(IoCtx)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::PoolNotFound` if there's
+	///   no such pool, `RadosError::NotConnected` before `connect()`
+	pub fn create_ioctx<S>(&self, pool_name: S) -> Result<IoCtx, RadosError>
+		where S: Into<Vec<u8>>
+	{
+		self.check_connected()?;
+		let pool_name = CString::new(pool_name)?;
+		let pool_name_ptr = pool_name.as_ptr();
+
+		let ioctx_handle: c_void_ptr = ptr::null_mut();
+		handle_errors!(rados_ioctx_create(self.handle, pool_name_ptr, &ioctx_handle), RadosError::from_pool_return_code);
+		self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner).handles.push(ioctx_handle as usize);
+		return Ok(IoCtx { handle: ioctx_handle, ioctxs: self.ioctxs.clone(), op_timeout_ms: AtomicUsize::new(0),
+			chunk_size: AtomicUsize::new(DEFAULT_CHUNK_SIZE), health_ttl_ms: AtomicUsize::new(DEFAULT_HEALTH_TTL_MS),
+			health: Mutex::new(None) });
+	}
+
+	/// Shut down the cluster handle, after which the `Cluster` can't be
+	/// used any more: the methods that need a connection fail with
+	/// `RadosError::NotConnected`.
+	///
+	/// An `IoCtx` isn't tied to the lifetime of its `Cluster`, so while io
+	/// contexts created from it are open, the handle is only shut down once
+	/// the last of them is dropped. Calling this more than once, or
+	/// dropping the `Cluster` afterwards, does nothing, and so does calling
+	/// it after `forget_on_drop()`: the handle belongs to someone else.
+	pub fn shutdown(&self) {
+		if self.forget_on_drop.load(Ordering::SeqCst) || self.shut_down.swap(true, Ordering::SeqCst) {
+			return;
+		}
+		self.connected.store(false, Ordering::SeqCst);
+		let mut open = self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner);
+		if open.handles.is_empty() {
+			unsafe {
+				rados_shutdown(self.handle);
+			}
+		} else {
+			open.orphaned = true;
+		}
+	}
+
+	/// Flush the asynchronous writes of every io context created from the
+	/// cluster, then shut the cluster handle down, reporting whether it
+	/// could be.
+	///
+	/// Unlike letting the `Cluster` go out of scope (where `Drop` leaves
+	/// the shutdown to the last open context), this refuses to shut down
+	/// while contexts are still open, and hands the `Cluster` back: once
+	/// it returns `Ok`, the handle is gone and every write made through it
+	/// is safe. It consumes `self`, so the handle can't be used afterwards.
+	/// After `forget_on_drop()`, the writes are flushed but the handle is
+	/// left to its owner.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err((Cluster, RadosError))` with `EBUSY` if io contexts of this
+	///   cluster are still open; their writes so far were flushed
+	pub fn close(self) -> Result<(), (Cluster, RadosError)> {
+		let busy = {
+			let open = self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner);
+			for &handle in &open.handles {
+				unsafe {
+					rados_aio_flush(handle as rados_ioctx_t);
+				}
+			}
+			!open.handles.is_empty()
+		};
+		if busy {
+			return Err((self, RadosError::Other(libc::EBUSY)));
+		}
+		self.shutdown();
+		return Ok(());
+	}
+
+}
+
+impl Debug for Cluster {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		return f.write_fmt(format_args!("{:?}", self.handle))
+	}
+}
+
+impl Drop for Cluster {
+	fn drop(&mut self) {
+		if self.handle.is_null() || self.forget_on_drop.load(Ordering::SeqCst) {
+			return;
+		}
+		println!("rados_shutdown({:?})", self.handle);
+		self.shutdown();
+	}
+}
+
+#[allow(dead_code)]
+/// Keep this here for debugging for now
+fn dump(msg: &str, buf: *const c_char, len: isize) {
+	print!("{}: ({}) [", msg, len);
+	for i in 0..len {
+		let c = unsafe {*buf.offset(i)};
+		print!("{:02x}", c);
+		if c >= 32 {
+			print!(" '{}'", c as u8 as char);
+		}
+		if i < len - 1 {
+			print!(",");
+		}
+	}
+	println!("]");
+}
+
+/// Read the xattrs `wanted` accepts out of `iter`, then close it
+fn collect_xattrs<F>(iter: rados_xattrs_iter_t, wanted: F) -> Result<HashMap<String, Vec<u8>>, RadosError>
+	where F: Fn(&str) -> bool
+{
+	let mut attrs = HashMap::new();
+	let mut result = Ok(());
+	loop {
+		let mut name: *const c_char = ptr::null();
+		let mut val: *const c_char = ptr::null();
+		let mut len: size_t = 0;
+		let err = unsafe { rados_getxattrs_next(iter, &mut name, &mut val, &mut len) };
+		if err < 0 {
+			result = Err(RadosError::from_return_code(err));
+			break;
+		}
+		if name.is_null() {
+			break;
+		}
+		let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+		if !wanted(&name) {
+			continue;
+		}
+		let value = if val.is_null() {
+			Vec::new()
+		} else {
+			unsafe { ::std::slice::from_raw_parts(val as *const u8, len as usize) }.to_vec()
+		};
+		attrs.insert(name, value);
+	}
+	unsafe {
+		rados_getxattrs_end(iter);
+	}
+	result.map(|_| attrs)
+}
+
+/// The classes of an `osd_class_load_list` option, separated by spaces,
+/// commas or semicolons, as Ceph parses it
+fn parse_object_classes(list: &str) -> ObjectClasses {
+	let classes: Vec<String> = list.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+		.filter(|c| !c.is_empty())
+		.map(|c| c.to_string())
+		.collect();
+	if classes.iter().any(|c| c == "*") {
+		return ObjectClasses::All;
+	}
+	ObjectClasses::Only(classes)
+}
+
+/// The `SystemTime` of an object's `mtime`, in seconds since the epoch
+fn mtime_to_system_time(mtime: libc::time_t) -> SystemTime {
+	if mtime < 0 {
+		UNIX_EPOCH - Duration::from_secs(mtime.unsigned_abs() as u64)
+	} else {
+		UNIX_EPOCH + Duration::from_secs(mtime as u64)
+	}
+}
+
+/// `duration` in whole milliseconds, or `usize::MAX` if it's longer
+fn duration_to_ms(duration: Duration) -> usize {
+	let ms = duration.as_secs().saturating_mul(1000).saturating_add((duration.subsec_nanos() / 1000000) as u64);
+	cmp::min(ms, usize::max_value() as u64) as usize
+}
+
+/// When `timeout` from now is, `None` if that's too far off to represent,
+/// which is as good as never
+fn deadline_after(timeout: Duration) -> Option<Instant> {
+	Instant::now().checked_add(timeout)
+}
+
+/// The first existing file of the `search` list of configuration files,
+/// as librados looks for them: separated by commas, semicolons or spaces,
+/// with `$cluster` and `$data_dir` expanded and `~/` relative to `home`
+fn find_conf_file(search: &str, cluster: &str, data_dir: &str, home: &str) -> Option<PathBuf> {
+	for candidate in search.split(|c: char| c == ',' || c == ' ' || c == ';').filter(|c| !c.is_empty()) {
+		let mut path = candidate.replace("$cluster", cluster).replace("$data_dir", data_dir);
+		if path.starts_with("~/") {
+			path = format!("{}{}", home, &path[1..]);
+		}
+		let path = PathBuf::from(path);
+		if path.is_file() {
+			return Some(path);
+		}
+	}
+	None
+}
+
+/// Split a `mon_host` value into addresses, see `Cluster::mon_hosts()`
+fn parse_mon_hosts(mon_host: &str) -> Vec<String> {
+	mon_host.split(|c: char| c == ',' || c == ';' || c == ' ' || c == '[' || c == ']')
+		.map(|addr| addr.trim())
+		.filter(|addr| !addr.is_empty())
+		.map(|addr| {
+			let addr = if addr.starts_with("v1:") || addr.starts_with("v2:") { &addr[3..] } else { addr };
+			// Drop the nonce of addresses like 10.0.0.1:6789/0
+			match addr.find('/') {
+				Some(slash) => addr[..slash].to_string(),
+				None => addr.to_string(),
+			}
+		})
+		.collect()
+}
+
+fn cluster_stat_raw(cluster: rados_t) -> Result<ClusterStat, RadosError> {
+	let mut stat = ClusterStat::default();
+	handle_errors!(rados_cluster_stat(cluster, &mut stat), RadosError::from_cluster_return_code);
+	Ok(stat)
+}
+
+/// Send the JSON `cmd` to the monitors of `cluster`, returning the output
+/// buffer and the status string
+fn mon_command_raw(cluster: rados_t, cmd: &str) -> Result<(Vec<u8>, String), RadosError> {
+	let cmd_cs = CString::new(cmd)?;
+	let cmds = [cmd_cs.as_ptr()];
+	command_outputs(|outbuf, outbuflen, outs, outslen| unsafe {
+		rados_mon_command(cluster, cmds.as_ptr(), 1, ptr::null(), 0, outbuf, outbuflen, outs, outslen)
+	})
+}
+
+/// Send the JSON `cmd` to the primary OSD of the `pgid` placement group,
+/// returning the output buffer and the status string
+fn pg_command_raw(cluster: rados_t, pgid: &str, cmd: &str) -> Result<(Vec<u8>, String), RadosError> {
+	let pgid_cs = CString::new(pgid)?;
+	let cmd_cs = CString::new(cmd)?;
+	let cmds = [cmd_cs.as_ptr()];
+	command_outputs(|outbuf, outbuflen, outs, outslen| unsafe {
+		rados_pg_command(cluster, pgid_cs.as_ptr(), cmds.as_ptr(), 1, ptr::null(), 0, outbuf, outbuflen, outs, outslen)
+	})
+}
+
+/// Send the JSON `cmd` to the `osd` OSD, returning the output buffer and
+/// the status string
+fn osd_command_raw(cluster: rados_t, osd: i32, cmd: &str) -> Result<(Vec<u8>, String), RadosError> {
+	let cmd_cs = CString::new(cmd)?;
+	let cmds = [cmd_cs.as_ptr()];
+	command_outputs(|outbuf, outbuflen, outs, outslen| unsafe {
+		rados_osd_command(cluster, osd as c_int, cmds.as_ptr(), 1, ptr::null(), 0, outbuf, outbuflen, outs, outslen)
+	})
+}
+
+/// Run one of the `rados_*_command()` functions through `f`, copying and
+/// freeing the output buffer and status string it allocates. A failure is
+/// returned as `RadosError::CommandFailed`, with the status string.
+fn command_outputs<F>(f: F) -> Result<(Vec<u8>, String), RadosError>
+	where F: FnOnce(*mut *mut c_char, *mut size_t, *mut *mut c_char, *mut size_t) -> c_int
+{
+	let mut outbuf: *mut c_char = ptr::null_mut();
+	let mut outbuflen: size_t = 0;
+	let mut outs: *mut c_char = ptr::null_mut();
+	let mut outslen: size_t = 0;
+	let err = f(&mut outbuf, &mut outbuflen, &mut outs, &mut outslen);
+	let out = if outbuf.is_null() {
+		Vec::new()
+	} else {
+		unsafe { ::std::slice::from_raw_parts(outbuf as *const u8, outbuflen as usize).to_vec() }
+	};
+	let status = if outs.is_null() {
+		String::new()
+	} else {
+		unsafe { String::from_utf8_lossy(::std::slice::from_raw_parts(outs as *const u8, outslen as usize)).into_owned() }
+	};
+	unsafe {
+		if !outbuf.is_null() {
+			rados_buffer_free(outbuf);
+		}
+		if !outs.is_null() {
+			rados_buffer_free(outs);
+		}
+	}
+	if err < 0 {
+		return Err(RadosError::CommandFailed(-err, status));
+	}
+	Ok((out, status))
+}
+
+/// Whether `pgid` looks like a placement group id: a decimal pool id and a
+/// hexadecimal placement seed, separated by a dot, e.g. `"1.2f"`
+fn is_valid_pgid(pgid: &str) -> bool {
+	let mut parts = pgid.splitn(2, '.');
+	let pool = parts.next().unwrap_or("");
+	let seed = parts.next().unwrap_or("");
+	!pool.is_empty() && pool.chars().all(|c| c.is_digit(10)) &&
+		!seed.is_empty() && seed.chars().all(|c| c.is_digit(16))
+}
+
+/// Get the health of `cluster`, see `Cluster::health()`
+fn cluster_health(cluster: rados_t) -> Result<HealthStatus, RadosError> {
+	let reply = mon_command_json(cluster, &json!({"prefix": "health", "format": "json"}))?;
+	// Before Luminous the status is reported as "overall_status"
+	let status = reply["status"].as_str().or_else(|| reply["overall_status"].as_str());
+	match status {
+		Some("HEALTH_OK") => Ok(HealthStatus::Ok),
+		Some("HEALTH_WARN") => Ok(HealthStatus::Warn),
+		Some("HEALTH_ERR") => Ok(HealthStatus::Err),
+		other => Err(RadosError::UnexpectedReply(format!("unknown health status {:?}", other))),
+	}
+}
+
+/// Send the JSON `cmd` to the monitors of `cluster`, and parse its output
+/// as JSON. The command should ask for `"format": "json"`.
+fn mon_command_json(cluster: rados_t, cmd: &Value) -> Result<Value, RadosError> {
+	let (out, _) = mon_command_raw(cluster, &cmd.to_string())?;
+	Ok(serde_json::from_slice(&out)?)
+}
+
+/// Read from `reader` until `buf` is full or the end of the stream is
+/// reached, returning the number of bytes read
+fn fill_from<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+	let mut filled = 0;
+	while filled < buf.len() {
+		match reader.read(&mut buf[filled..]) {
+			Ok(0) => break,
+			Ok(n) => filled += n,
+			Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(filled)
+}
+
+/// Write what `reader` yields to the `oid` object of `io`, `chunk_size`
+/// bytes at a time, replacing its contents, see
+/// `IoCtx::upload_with_progress()`
+fn upload_chunks<I, R, F>(io: &I, oid: &str, mut reader: R, chunk_size: usize, mut progress: F) -> Result<u64, RadosError>
+	where I: RadosIo + ?Sized, R: Read, F: FnMut(u64, Option<u64>)
+{
+	let mut buf = vec![0u8; chunk_size];
+	let mut offset: u64 = 0;
+	loop {
+		let len = fill_from(&mut reader, &mut buf)?;
+		if offset == 0 {
+			io.write_full(oid, &buf[..len])?;
+		} else if len > 0 {
+			io.write_at(oid, &buf[..len], offset)?;
+		}
+		offset += len as u64;
+		if len > 0 {
+			progress(offset, None);
+		}
+		if len < chunk_size {
+			return Ok(offset);
+		}
+	}
+}
+
+/// Check that `bytes` is a chunk size librados can read or write in a
+/// single operation, see `IoCtx::set_chunk_size()`
+fn check_chunk_size(bytes: usize) -> Result<(), RadosError> {
+	if bytes == 0 || bytes as u64 >= (u32::max_value() / 2) as u64 {
+		return Err(RadosError::Other(libc::EINVAL));
+	}
+	return Ok(());
+}
+
+/// Read the whole `oid` object of `io`, `size` bytes as far as known up
+/// front, at most `chunk_size` bytes at a time, see `IoCtx::read_full()`
+fn read_chunks<I>(io: &I, oid: &str, size: usize, chunk_size: usize) -> Result<Vec<u8>, RadosError>
+	where I: RadosIo + ?Sized
+{
+	let mut buf = Vec::with_capacity(size);
+	loop {
+		let filled = buf.len();
+		let want = if filled < size { cmp::min(size - filled, chunk_size) } else { chunk_size };
+		buf.resize(filled + want, 0);
+		let len = io.read_at(oid, &mut buf[filled..], filled as u64)?;
+		buf.truncate(filled + len);
+		if len == 0 {
+			return Ok(buf);
+		}
+	}
+}
+
+/// Write the `oid` object of `io` to `writer`, reading `chunk_size` bytes
+/// at a time, see `IoCtx::download_with_progress()`
+fn download_chunks<I, W, F>(io: &I, oid: &str, mut writer: W, chunk_size: usize, total: Option<u64>, mut progress: F) -> Result<u64, RadosError>
+	where I: RadosIo + ?Sized, W: Write, F: FnMut(u64, Option<u64>)
+{
+	let mut buf = vec![0u8; chunk_size];
+	let mut offset: u64 = 0;
+	loop {
+		let len = io.read_at(oid, &mut buf, offset)?;
+		if len == 0 {
+			writer.flush()?;
+			return Ok(offset);
+		}
+		writer.write_all(&buf[..len])?;
+		offset += len as u64;
+		progress(offset, total);
+	}
+}
+
+/// Write `bytes` to `writer` after their length, as a little-endian `u32`
+/// (`u64` if `wide`), returning the number of bytes written, see
+/// `IoCtx::export()`
+fn write_field<W: Write>(writer: &mut W, bytes: &[u8], wide: bool) -> Result<u64, RadosError> {
+	let prefix = if wide {
+		writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+		8
+	} else {
+		if bytes.len() > u32::max_value() as usize {
+			return Err(RadosError::Other(libc::EFBIG));
+		}
+		writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+		4
+	};
+	writer.write_all(bytes)?;
+	Ok(prefix + bytes.len() as u64)
+}
+
+/// Write the `(name, value)` pairs of `entries` to `writer` after their
+/// count, as a little-endian `u32`, see `IoCtx::export()`
+fn write_entries<'a, W, I>(writer: &mut W, entries: I) -> Result<u64, RadosError>
+	where W: Write, I: ExactSizeIterator<Item = (&'a str, &'a [u8])>
+{
+	if entries.len() > u32::max_value() as usize {
+		return Err(RadosError::Other(libc::EFBIG));
+	}
+	writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+	let mut written = 4;
+	for (name, value) in entries {
+		written += write_field(writer, name.as_bytes(), false)?;
+		written += write_field(writer, value, false)?;
+	}
+	Ok(written)
+}
+
+/// Read a little-endian `u32`, or `u64` if `wide`, from `reader`, `None`
+/// if the stream ended right away
+fn read_length<R: Read>(reader: &mut R, wide: bool) -> Result<Option<u64>, RadosError> {
+	let mut buf = [0u8; 8];
+	let len = if wide { 8 } else { 4 };
+	match fill_from(reader, &mut buf[..len])? {
+		0 => Ok(None),
+		n if n < len => Err(truncated_export()),
+		_ => Ok(Some(u64::from_le_bytes(buf))),
+	}
+}
+
+/// Read a field written by `write_field()` from `reader`. The field is
+/// read as it comes rather than into a buffer of the length announced, so
+/// a corrupt length fails with a truncated stream instead of allocating
+/// whatever it says.
+fn read_field<R: Read>(reader: &mut R, wide: bool) -> Result<Vec<u8>, RadosError> {
+	let len = read_length(reader, wide)?.ok_or_else(truncated_export)?;
+	let mut field = Vec::new();
+	reader.take(len).read_to_end(&mut field)?;
+	if (field.len() as u64) < len {
+		return Err(truncated_export());
+	}
+	Ok(field)
+}
+
+/// Read the entries written by `write_entries()` from `reader`
+fn read_entries<R: Read>(reader: &mut R) -> Result<Vec<(String, Vec<u8>)>, RadosError> {
+	let count = read_length(reader, false)?.ok_or_else(truncated_export)?;
+	let mut entries = Vec::new();
+	for _ in 0..count {
+		let name = String::from_utf8(read_field(reader, false)?)
+			.map_err(|e| RadosError::Io(io::ErrorKind::InvalidData, e.to_string()))?;
+		entries.push((name, read_field(reader, false)?));
+	}
+	Ok(entries)
+}
+
+/// Read the start of an export stream from `reader`, failing with
+/// `io::ErrorKind::InvalidData` if it isn't `EXPORT_MAGIC`
+fn read_export_magic<R: Read>(reader: &mut R) -> Result<(), RadosError> {
+	let mut magic = [0u8; 8];
+	if fill_from(reader, &mut magic)? < magic.len() || &magic != EXPORT_MAGIC {
+		return Err(RadosError::Io(io::ErrorKind::InvalidData, "not a ceph-rs export stream".to_string()));
+	}
+	Ok(())
+}
+
+/// The error of an export stream ending in the middle of a record
+fn truncated_export() -> RadosError {
+	RadosError::Io(io::ErrorKind::UnexpectedEof, "export stream ends in the middle of a record".to_string())
+}
+
+/// Call `f` with a zeroed buffer of `initial` bytes for librados to fill,
+/// and for as long as it returns that the buffer is too small (`-ERANGE`,
+/// or `-ENAMETOOLONG` for configuration values), call it again with a
+/// buffer twice as large, up to `MAX_BUF_SIZE`.
+///
+/// Returns the whole buffer and what `f` returned on success, as calls
+/// differ in how they report the length filled in, and the negative error
+/// code `f` returned otherwise.
+fn fill_buffer<F>(initial: usize, mut f: F) -> Result<(Vec<u8>, usize), c_int>
+	where F: FnMut(&mut [u8]) -> c_int
+{
+	let mut buf_size = cmp::max(initial, 1);
+	loop {
+		let mut buf = vec![0u8; buf_size];
+		let ret = f(&mut buf);
+		if (ret == -libc::ERANGE || ret == -libc::ENAMETOOLONG) && buf_size < MAX_BUF_SIZE {
+			buf_size = cmp::min(buf_size * 2, MAX_BUF_SIZE);
+			continue;
+		}
+		if ret < 0 {
+			return Err(ret);
+		}
+		return Ok((buf, ret as usize));
+	}
+}
+
+/// The bytes of `buf` before the first `'\0'`, as a string
+fn c_buf_to_string(buf: &[u8]) -> String {
+	let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+	String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Whether `name` matches the glob `pattern`, where `*` matches any run of
+/// characters (including none), `?` any single character, and `\` makes
+/// the next character match only itself
+fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+	let (mut p, mut n) = (0, 0);
+	// where to resume after the last `*`: its pattern index, and the name
+	// index it's currently matched up to
+	let mut star: Option<(usize, usize)> = None;
+	while n < name.len() {
+		if p < pattern.len() && pattern[p] == '*' {
+			p += 1;
+			star = Some((p, n));
+			continue;
+		}
+		if p < pattern.len() {
+			let (literal, width) = match pattern[p] {
+				'\\' if p + 1 < pattern.len() => (Some(pattern[p + 1]), 2),
+				'?' => (None, 1),
+				c => (Some(c), 1),
+			};
+			if literal.map_or(true, |c| c == name[n]) {
+				p += width;
+				n += 1;
+				continue;
+			}
+		}
+		match star {
+			Some((star_p, star_n)) => {
+				p = star_p;
+				n = star_n + 1;
+				star = Some((star_p, star_n + 1));
+			},
+			None => return false,
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl IoCtx {
+	/// Block until all pending asynchronous writes on this context are safe
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn aio_flush(&self) -> Result<(), RadosError> {
+		handle_errors!(rados_aio_flush(self.handle));
+		return Ok(());
+	}
+
+	/// Start writing `data` into the `oid` object at `offset`, without
+	/// waiting for the write to finish.
+	///
+	/// The returned `WriteCompletion` can wait for the write to be
+	/// acknowledged (`wait_complete()`), or for the OSDs to report it's on
+	/// stable storage on all replicas (`wait_safe()`), which is what a
+	/// write-ahead log needs before it considers an entry durable.
+	///
+	/// # Returns
+	///
+	/// * `Ok(WriteCompletion)` if the write was queued
+	/// * `Err(RadosError)` on failure
+	pub fn aio_write_safe(&self, oid: &str, data: &[u8], offset: u64) -> Result<WriteCompletion<'_>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		WriteCompletion::start(self.handle, data.to_vec(), |c, data| unsafe {
+			rados_aio_write(self.handle, oid_cs.as_ptr(), c, data.as_ptr() as *const c_char, data.len() as size_t, offset)
+		})
+	}
+
+	/// Start reading up to `len` bytes from the `oid` object at `offset`,
+	/// without waiting for the read to finish.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ReadCompletion)` if the read was queued; `ReadCompletion::wait()`
+	///   returns the data
+	/// * `Err(RadosError)` on failure
+	pub fn aio_read(&self, oid: &str, len: usize, offset: u64) -> Result<ReadCompletion<'_>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut completion = ReadCompletion { completion: ptr::null(), io: self.handle, buf: vec![0u8; len],
+			started: false, ioctx: PhantomData };
+		handle_errors!(rados_aio_create_completion(ptr::null_mut(), None, None, &mut completion.completion));
+		handle_errors!(rados_aio_read(self.handle, oid_cs.as_ptr(), completion.completion,
+			completion.buf.as_mut_ptr() as *mut c_char, len as size_t, offset));
+		completion.started = true;
+		return Ok(completion);
+	}
+
+	/// Replace the contents of the `oid` object with `data`, unless the
+	/// cluster is in `HEALTH_ERR`, e.g. for ingesters that would rather
+	/// hold data back than write it into a failing cluster. `HEALTH_WARN`
+	/// doesn't stop writes.
+	///
+	/// The health is asked from the monitors at most once every
+	/// `set_health_ttl()` (5 seconds by default) per context, so a cluster
+	/// that just turned unhealthy may still be written to for that long.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError::ClusterUnhealthy)` if the cluster is in
+	///   `HEALTH_ERR`, in which case nothing was written
+	/// * `Err(RadosError)` on other failures, including getting the health
+	pub fn write_when_healthy(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		let ttl = Duration::from_millis(self.health_ttl_ms.load(Ordering::SeqCst) as u64);
+		let status = {
+			let mut cached = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+			match *cached {
+				Some((at, status)) if at.elapsed() < ttl => status,
+				_ => {
+					let status = cluster_health(unsafe { rados_ioctx_get_cluster(self.handle) })?;
+					*cached = Some((Instant::now(), status));
+					status
+				},
+			}
+		};
+		if status == HealthStatus::Err {
+			return Err(RadosError::ClusterUnhealthy);
+		}
+		RadosIo::write_full(self, oid, data)
+	}
+
+	/// Block until the writes made so far to the `oid` object are on stable
+	/// storage on all replicas, like `fsync()` for a single object.
+	///
+	/// This sends the object an empty write operation (only checking the
+	/// object exists) and waits for it to be safe. The OSDs apply the
+	/// operations on an object in order, so once it's safe, every write
+	/// this client sent to the object before it is too. Unlike
+	/// `aio_flush()`, it doesn't wait on writes to other objects.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `RadosError::ObjectNotFound` if the
+	///   object doesn't exist
+	pub fn sync(&self, oid: &str) -> Result<(), RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut op = WriteOp::new();
+		op.assert_exists();
+		op.check()?;
+		let completion = WriteCompletion::start(self.handle, Vec::new(), |c, _| unsafe {
+			rados_aio_write_op_operate(op.handle, self.handle, c, oid_cs.as_ptr(), ptr::null_mut(), 0)
+		})?;
+		completion.wait_safe()
+	}
+
+	/// Run one or more operations on this context, retrying on transient
+	/// errors as described by `policy`.
+	///
+	/// The `op` closure is given this `IoCtx` and is called again in full
+	/// for every retry, so it should be safe to repeat.
+	pub fn with_retry<T, F>(&self, policy: &RetryPolicy, mut op: F) -> Result<T, RadosError>
+		where F: FnMut(&IoCtx) -> Result<T, RadosError>
+	{
+		policy.retry(|| op(self))
+	}
+
+	pub fn write<S, T>(&self, oid: S, data: T) -> Result<(), RadosError>
+		where S: Into<Vec<u8>>, T: Into<String>
+	{
+		let oid_cs = CString::new(oid)?;
+		let s : String = data.into();
+		if s.len() > MAX_WRITE_SIZE {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let len : size_t = s.len() as size_t;
+		let buf = CString::new(s)?;
+		handle_errors!(rados_write_full(self.handle, oid_cs.as_ptr(), buf.as_ptr(), len));
+		return Ok(());
+	}
+
+	/// Write `data` into the `oid` object, starting at `offset`.
+	///
+	/// The length of `data` must be <= `UINT_MAX/2`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `data` is too long,
+	///   `RadosError::NoSpace` if the cluster is full
+	pub fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError> {
+		if data.len() > MAX_WRITE_SIZE {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let oid_cs = CString::new(oid)?;
+		let buf_ptr = data.as_ptr() as *const c_char;
+		match self.op_timeout() {
+			None => {
+				handle_errors!(rados_write(self.handle, oid_cs.as_ptr(), buf_ptr, data.len() as size_t, offset));
+			},
+			Some(timeout) => {
+				self.aio_with_timeout(timeout, |c| unsafe {
+					rados_aio_write(self.handle, oid_cs.as_ptr(), c, buf_ptr, data.len() as size_t, offset)
+				})?;
+			},
+		}
+		return Ok(());
+	}
+
+	/// Make `read_at()` and `write_at()` on this context, and everything
+	/// built on them (`upload()`, `download()`, `read_many()`, ...), fail
+	/// with `RadosError::Timeout` if they take longer than `timeout`,
+	/// instead of hanging when OSDs are unresponsive. A zero `timeout`
+	/// removes the limit.
+	///
+	/// The timed operations are issued asynchronously and canceled once the
+	/// timeout expires. This is independent of the `rados_osd_op_timeout`
+	/// option, which applies to every operation of a cluster handle but is
+	/// only read by librados when it connects; set it with
+	/// `Cluster::conf_set()` before `connect()` for a cluster-wide timeout.
+	///
+	/// The timeout is kept in milliseconds: a non-zero `timeout` below 1ms
+	/// is rounded up to 1ms, rather than down to no limit at all.
+	pub fn set_op_timeout(&self, timeout: Duration) {
+		let mut ms = duration_to_ms(timeout);
+		if ms == 0 && timeout != Duration::from_secs(0) {
+			ms = 1;
+		}
+		self.op_timeout_ms.store(ms, Ordering::SeqCst);
+	}
+
+	/// Set how long `write_when_healthy()` reuses the cluster health it
+	/// got before asking the monitors again; the default is 5 seconds. A
+	/// zero `ttl` checks the health before every write.
+	pub fn set_health_ttl(&self, ttl: Duration) {
+		self.health_ttl_ms.store(duration_to_ms(ttl), Ordering::SeqCst);
+		*self.health.lock().unwrap_or_else(PoisonError::into_inner) = None;
+	}
+
+	/// The timeout set with `set_op_timeout()`, if any
+	pub fn op_timeout(&self) -> Option<Duration> {
+		match self.op_timeout_ms.load(Ordering::SeqCst) {
+			0 => None,
+			ms => Some(Duration::from_millis(ms as u64)),
+		}
+	}
+
+	/// Set how many bytes `read_full()` and `download()` read at a time on
+	/// this context; the default is 4 MiB. Small chunks keep each read
+	/// short, large ones need fewer round trips.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` with `EINVAL` if `bytes` is `0`, or not below
+	///   `UINT_MAX / 2`, the most librados accepts for a single operation
+	pub fn set_chunk_size(&self, bytes: usize) -> Result<(), RadosError> {
+		check_chunk_size(bytes)?;
+		self.chunk_size.store(bytes, Ordering::SeqCst);
+		return Ok(());
+	}
+
+	/// The chunk size set with `set_chunk_size()`
+	pub fn chunk_size(&self) -> usize {
+		self.chunk_size.load(Ordering::SeqCst)
+	}
+
+	/// Start an asynchronous operation with `start`, and wait at most
+	/// `timeout` for it to complete, canceling it otherwise.
+	///
+	/// Buffers the operation uses must outlive this call; they do, since it
+	/// only returns once the operation completed or was canceled.
+	fn aio_with_timeout<F>(&self, timeout: Duration, start: F) -> Result<c_int, RadosError>
+		where F: FnOnce(rados_completion_t) -> c_int
+	{
+		// Woken by the completion callback. The callback owns a reference,
+		// as librados may run it after rados_aio_wait_for_complete() returns
+		let waiter = Arc::new(AioWaiter { complete: Mutex::new(false), cond: Condvar::new() });
+		let arg = Arc::into_raw(waiter.clone()) as *mut c_void;
+		let mut c: rados_completion_t = ptr::null();
+		let err = unsafe { rados_aio_create_completion(arg, Some(aio_waiter_callback), None, &mut c) };
+		if err < 0 {
+			unsafe { drop(Arc::from_raw(arg as *const AioWaiter)) };
+			return Err(RadosError::from_return_code(err));
+		}
+		let err = start(c);
+		if err < 0 {
+			// The operation never started, so neither does the callback
+			unsafe {
+				rados_aio_release(c);
+				drop(Arc::from_raw(arg as *const AioWaiter));
+			}
+			return Err(RadosError::from_return_code(err));
+		}
+		let deadline = deadline_after(timeout);
+		let mut timed_out = false;
+		{
+			let mut complete = waiter.complete.lock().unwrap_or_else(PoisonError::into_inner);
+			while !*complete {
+				let deadline = match deadline {
+					Some(deadline) => deadline,
+					None => {
+						complete = waiter.cond.wait(complete).unwrap_or_else(PoisonError::into_inner);
+						continue;
+					},
+				};
+				let now = Instant::now();
+				if now >= deadline {
+					timed_out = true;
+					break;
+				}
+				complete = waiter.cond.wait_timeout(complete, deadline - now).unwrap_or_else(PoisonError::into_inner).0;
+			}
+		}
+		if timed_out {
+			unsafe {
+				rados_aio_cancel(self.handle, c);
+				rados_aio_wait_for_complete(c);
+			}
+		}
+		let ret = unsafe { rados_aio_get_return_value(c) };
+		unsafe { rados_aio_release(c) };
+		if ret >= 0 {
+			return Ok(ret);
+		}
+		if timed_out {
+			return Err(RadosError::Timeout);
+		}
+		Err(RadosError::from_return_code(ret))
+	}
+
+	/// Write `data` into the `oid` object, starting at `offset`, passing
+	/// `flags` as hints to the OSDs.
+	///
+	/// For example, a large sequential writer can use
+	/// `WriteFlags::FADVISE_DONTNEED` to keep the data out of the OSD cache.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn write_with_flags(&self, oid: &str, data: &[u8], offset: u64, flags: WriteFlags) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.write(data, offset).set_flags(flags);
+		return self.operate(oid, &op);
+	}
+
+	/// Remove the `oid` object as the last action of `op`, with `flags`
+	/// applied to the removal, so it only happens if the assertions already
+	/// in `op` hold.
+	///
+	/// # Examples
+	///
+	/// Removing an object only if no one wrote to it since it was read:
+	///
+	/// ```rust,no_run
+	/// # use ceph::rados::{Cluster, CreateFlags, WriteFlags, WriteOp};
+	/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	/// # let ioctx = cluster.create_ioctx("data").unwrap();
+	/// let meta = ioctx.object_meta("hw").unwrap();
+	/// let mut op = WriteOp::new();
+	/// op.assert_version(meta.version);
+	/// ioctx.remove_with("hw", &mut op, WriteFlags::empty()).unwrap();
+	/// ```
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case the object is left
+	///   as it was
+	pub fn remove_with(&self, oid: &str, op: &mut WriteOp, flags: WriteFlags) -> Result<(), RadosError> {
+		op.remove().set_flags(flags);
+		return self.operate(oid, op);
+	}
+
+	/// Get the alignment writes to this pool must respect, if any.
+	///
+	/// Erasure coded pools without overwrite support only accept appends in
+	/// multiples of their stripe width.
+	///
+	/// # Returns
+	///
+	/// * `Ok(None)` if the pool doesn't require aligned writes
+	/// * `Ok(Some(alignment))` if it does
+	/// * `Err(RadosError)` on failure
+	pub fn required_alignment(&self) -> Result<Option<u64>, RadosError> {
+		let mut requires: c_int = 0;
+		handle_errors!(rados_ioctx_pool_requires_alignment2(self.handle, &mut requires));
+		if requires == 0 {
+			return Ok(None);
+		}
+		let mut alignment: u64 = 0;
+		handle_errors!(rados_ioctx_pool_required_alignment2(self.handle, &mut alignment));
+		return Ok(Some(alignment));
+	}
+
+	/// The longest buffer a single write to this pool may pass: librados
+	/// takes at most `UINT_MAX/2` bytes per call, rounded down here to a
+	/// multiple of the pool's required alignment, if any.
+	pub fn max_write_size(&self) -> usize {
+		match self.required_alignment() {
+			Ok(Some(alignment)) if alignment > 0 => {
+				let alignment = alignment as usize;
+				MAX_WRITE_SIZE / alignment * alignment
+			},
+			_ => MAX_WRITE_SIZE,
+		}
+	}
+
+	/// Open a new context on the same pool, for example to hand to another
+	/// thread.
+	///
+	/// The new context starts from the defaults: it has its own namespace,
+	/// locator and snapshot settings, and no operation timeout, so changing
+	/// them on one context doesn't affect the other.
+	///
+	/// # Returns
+	///
+	/// * `Ok(IoCtx)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn try_clone(&self) -> Result<IoCtx, RadosError> {
+		let mut ioctx_handle: rados_ioctx_t = ptr::null();
+		handle_errors!(rados_ioctx_create2(rados_ioctx_get_cluster(self.handle), rados_ioctx_get_id(self.handle), &mut ioctx_handle), RadosError::from_pool_return_code);
+		self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner).handles.push(ioctx_handle as usize);
+		return Ok(IoCtx { handle: ioctx_handle, ioctxs: self.ioctxs.clone(), op_timeout_ms: AtomicUsize::new(0),
+			chunk_size: AtomicUsize::new(DEFAULT_CHUNK_SIZE), health_ttl_ms: AtomicUsize::new(DEFAULT_HEALTH_TTL_MS),
+			health: Mutex::new(None) });
+	}
+
+	/// Set the namespace objects are read from and written to through this
+	/// context, or go back to the default namespace with `None`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError::Other(EINVAL))` if `nspace` has a NUL byte in it
+	pub fn set_namespace(&self, nspace: Option<&str>) -> Result<(), RadosError> {
+		let nspace_cs = match nspace {
+			Some(n) => Some(CString::new(n)?),
+			None => None,
+		};
+		let nspace_ptr = match nspace_cs {
+			None => ptr::null(),
+			Some(ref cs) => cs.as_ptr()
+		};
+		unsafe {
+			rados_ioctx_set_namespace(self.handle, nspace_ptr);
+		}
+		return Ok(());
+	}
+
+	/// Get the namespace set with `set_namespace()`, `""` for the default
+	/// namespace.
+	///
+	/// # Returns
+	///
+	/// * `Ok(nspace)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn namespace(&self) -> Result<String, RadosError> {
+		let filled = fill_buffer(64, |buf| unsafe {
+			rados_ioctx_get_namespace(self.handle, buf.as_mut_ptr() as *mut c_char, buf.len() as c_uint)
+		});
+		match filled {
+			Ok((buf, len)) => Ok(String::from_utf8_lossy(&buf[..len]).into_owned()),
+			Err(err) => Err(RadosError::from_return_code(err)),
+		}
+	}
+
+	/// Try writes through this context even when the cluster or the pool
+	/// is full, instead of holding them until space is freed.
+	///
+	/// Use this only for the operations that free space, like removing
+	/// objects: the OSDs keep a reserve of space for them, and writes that
+	/// add data under this flag eat into it. Normal writers should leave
+	/// it off, which is the default. Binds `rados_set_osdmap_full_try()`,
+	/// which later releases rename `rados_set_pool_full_try()`.
+	pub fn set_full_try(&self, enabled: bool) {
+		unsafe {
+			if enabled {
+				rados_set_osdmap_full_try(self.handle);
+			} else {
+				rados_unset_osdmap_full_try(self.handle);
+			}
+		}
+	}
+
+	/// Allocate the id of a new self-managed snapshot of the pool.
+	///
+	/// Nothing is cloned until an object is written with a snapshot
+	/// context (see `set_snap_write_context()`) that includes the id.
+	///
+	/// # Returns
+	///
+	/// * `Ok(snap_id)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn selfmanaged_snap_create(&self) -> Result<u64, RadosError> {
+		let mut snap_id: rados_snap_t = 0;
+		handle_errors!(rados_ioctx_selfmanaged_snap_create(self.handle, &mut snap_id), RadosError::from_pool_return_code);
+		return Ok(snap_id);
+	}
+
+	/// Remove the self-managed snapshot `snap_id`; its clones are trimmed
+	/// lazily by the OSDs.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn selfmanaged_snap_remove(&self, snap_id: u64) -> Result<(), RadosError> {
+		handle_errors!(rados_ioctx_selfmanaged_snap_remove(self.handle, snap_id), RadosError::from_pool_return_code);
+		return Ok(());
+	}
+
+	/// Set the snapshot context every following write through this context
+	/// carries, so the OSDs clone objects before changing them when they
+	/// haven't been since the newest of `snaps`.
+	///
+	/// # Parameters
+	///
+	/// * `seq` the newest snapshot sequence number, at least the first of
+	///   `snaps`
+	/// * `snaps` the existing snapshot ids, newest (highest) first
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `snaps` isn't in
+	///   descending order
+	pub fn set_snap_write_context(&self, seq: u64, snaps: &[u64]) -> Result<(), RadosError> {
+		// librados takes a mutable array, but copies it before returning
+		let mut snaps = snaps.to_vec();
+		handle_errors!(rados_ioctx_selfmanaged_snap_set_write_ctx(self.handle, seq, snaps.as_mut_ptr(), snaps.len() as c_int));
+		return Ok(());
+	}
+
+	/// Write `data` into the `oid` object at `offset`, under the snapshot
+	/// context `seq`/`snaps` (see `set_snap_write_context()`).
+	///
+	/// The snapshot context is stored in the io context, so it stays set
+	/// for later writes, and other threads writing through the same context
+	/// meanwhile pick it up too; give each thread its own context with
+	/// `try_clone()` when they use different snapshot contexts.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn write_with_snap_context(&self, oid: &str, data: &[u8], offset: u64, seq: u64, snaps: &[u64]) -> Result<(), RadosError> {
+		self.set_snap_write_context(seq, snaps)?;
+		self.write_at(oid, data, offset)
+	}
+
+	/// Read objects as they were at snapshot `snap_id`, or as they are now
+	/// with `None`.
+	pub fn set_read_snap(&self, snap_id: Option<u64>) {
+		unsafe {
+			rados_ioctx_snap_set_read(self.handle, snap_id.unwrap_or(LIBRADOS_SNAP_HEAD));
+		}
+	}
+
+	/// List the snapshots of the pool, as taken with `ceph osd pool
+	/// mksnap`, from the oldest. Self-managed snapshots aren't listed.
+	///
+	/// # Returns
+	///
+	/// * `Ok(snaps)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn list_snaps(&self) -> Result<Vec<SnapInfo>, RadosError> {
+		let max_len = MAX_BUF_SIZE / mem::size_of::<rados_snap_t>();
+		let mut ids: Vec<rados_snap_t> = vec![0; 16];
+		let count = loop {
+			let ret = unsafe { rados_ioctx_snap_list(self.handle, ids.as_mut_ptr(), ids.len() as c_int) };
+			if ret == -libc::ERANGE && ids.len() < max_len {
+				let len = cmp::min(ids.len() * 2, max_len);
+				ids.resize(len, 0);
+				continue;
+			}
+			if ret < 0 {
+				return Err(RadosError::from_pool_return_code(ret));
+			}
+			break ret as usize;
+		};
+		ids.truncate(count);
+		ids.sort();
+		let mut snaps = Vec::with_capacity(count);
+		for id in ids {
+			let name = match fill_buffer(64, |buf| unsafe {
+				rados_ioctx_snap_get_name(self.handle, id, buf.as_mut_ptr() as *mut c_char, buf.len() as c_int)
+			}) {
+				Ok((buf, _)) => c_buf_to_string(&buf),
+				Err(err) => return Err(RadosError::from_return_code(err)),
+			};
+			let mut stamp: libc::time_t = 0;
+			handle_errors!(rados_ioctx_snap_get_stamp(self.handle, id, &mut stamp));
+			snaps.push(SnapInfo { id: id, name: name, stamp: mtime_to_system_time(stamp) });
+		}
+		Ok(snaps)
+	}
+
+	/// List the snapshots of the pool, as `list_snaps()` does, each with
+	/// an estimate of the space it holds, in bytes.
+	///
+	/// librados doesn't report how much space each snapshot holds, only
+	/// how many object clones the pool has (see `pool_stat()`). So this is
+	/// an approximation: the clones are taken to be as large as the
+	/// pool's objects on average, and to be shared out evenly between the
+	/// snapshots. A clone is kept for as long as any snapshot refers to
+	/// it, so removing a single snapshot frees less than its share when
+	/// its clones are shared with others.
+	///
+	/// # Returns
+	///
+	/// * `Ok(snaps)` with `(snapshot, estimated_bytes)` pairs on success
+	/// * `Err(RadosError)` on failure
+	pub fn snap_usage(&self) -> Result<Vec<(SnapInfo, u64)>, RadosError> {
+		let snaps = self.list_snaps()?;
+		if snaps.is_empty() {
+			return Ok(Vec::new());
+		}
+		let stat = self.pool_stat()?;
+		let clone_bytes = if stat.num_objects == 0 {
+			0
+		} else {
+			// num_objects counts the clones too
+			(stat.num_bytes as u128 * stat.num_object_clones as u128 / stat.num_objects as u128) as u64
+		};
+		let per_snap = clone_bytes / snaps.len() as u64;
+		Ok(snaps.into_iter().map(|snap| (snap, per_snap)).collect())
+	}
+
+	/// Get the name of the pool this context operates on.
+	///
+	/// # Returns
+	///
+	/// * `Ok(name)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn pool_name(&self) -> Result<String, RadosError> {
+		let filled = fill_buffer(64, |buf| unsafe {
+			rados_ioctx_get_pool_name(self.handle, buf.as_mut_ptr() as *mut c_char, buf.len() as c_int)
+		});
+		match filled {
+			Ok((buf, _)) => Ok(c_buf_to_string(&buf)),
+			Err(err) => Err(RadosError::from_return_code(err)),
+		}
+	}
+
+	/// Get usage statistics for the pool.
+	///
+	/// # Returns
+	///
+	/// * `Ok(PoolStat)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn pool_stat(&self) -> Result<PoolStat, RadosError> {
+		let mut stat = PoolStat::default();
+		handle_errors!(rados_ioctx_pool_stat(self.handle, &mut stat), RadosError::from_pool_return_code);
+		return Ok(stat);
+	}
+
+	/// Estimate whether writing another `bytes_needed` bytes to this pool
+	/// is likely to fit, so bulk imports can fail fast instead of halfway.
+	///
+	/// The raw space the write needs is `bytes_needed` times the pool's
+	/// `size` (its replica count; for erasure coded pools this overestimates
+	/// the real overhead). It must fit in the cluster's available space
+	/// while leaving 5% of the total capacity free, as a margin below the
+	/// OSD full ratio. The space is shared with the other pools, and the
+	/// usage figures are only eventually consistent, so this is an
+	/// estimate, not a reservation.
+	///
+	/// # Returns
+	///
+	/// * `Ok(true)` if the write should fit
+	/// * `Ok(false)` if it likely won't
+	/// * `Err(RadosError)` on failure
+	pub fn check_space(&self, bytes_needed: u64) -> Result<bool, RadosError> {
+		let name = self.pool_name()?;
+		let cluster = unsafe { rados_ioctx_get_cluster(self.handle) };
+		let reply = mon_command_json(cluster, &json!({"prefix": "osd pool get", "pool": name, "var": "size", "format": "json"}))?;
+		let size = match reply["size"].as_u64() {
+			Some(size) => size,
+			None => return Err(RadosError::UnexpectedReply(format!("no size for pool {}", name))),
+		};
+		let stat = cluster_stat_raw(cluster)?;
+		let avail = stat.kb_avail.saturating_mul(1024);
+		let margin = stat.kb.saturating_mul(1024) / 20;
+		Ok(bytes_needed.saturating_mul(size).saturating_add(margin) <= avail)
+	}
+
+	/// Get the number of placement groups and replicas of the pool, with a
+	/// single `osd pool get <pool> all` mon command.
+	///
+	/// # Returns
+	///
+	/// * `Ok(PoolLayout)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn pool_layout(&self) -> Result<PoolLayout, RadosError> {
+		let name = self.pool_name()?;
+		let cluster = unsafe { rados_ioctx_get_cluster(self.handle) };
+		let reply = mon_command_json(cluster, &json!({"prefix": "osd pool get", "pool": name, "var": "all", "format": "json"}))?;
+		let field = |var: &str| match reply[var].as_u64() {
+			Some(value) => Ok(value as u32),
+			None => Err(RadosError::UnexpectedReply(format!("no {} for pool {}", var, name))),
+		};
+		Ok(PoolLayout {
+			pg_num: field("pg_num")?,
+			pgp_num: field("pgp_num")?,
+			size: field("size")?,
+			min_size: field("min_size")?,
+		})
+	}
+
+	/// Find out whether the pool is replicated or erasure coded.
+	///
+	/// Erasure coded pools don't support omap, and restrict appends and
+	/// partial writes, so callers may need a different code path for them.
+	/// This is looked up in the OSD map with an `osd dump` mon command.
+	///
+	/// # Returns
+	///
+	/// * `Ok(PoolType)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn pool_type(&self) -> Result<PoolType, RadosError> {
+		let name = self.pool_name()?;
+		let cluster = unsafe { rados_ioctx_get_cluster(self.handle) };
+		let dump = mon_command_json(cluster, &json!({"prefix": "osd dump", "format": "json"}))?;
+		let pools = dump["pools"].as_array().map(|pools| pools.as_slice()).unwrap_or(&[]);
+		for pool in pools {
+			if pool["pool_name"].as_str() == Some(name.as_str()) {
+				// pg_pool_t::TYPE_REPLICATED and pg_pool_t::TYPE_ERASURE
+				return match pool["type"].as_u64() {
+					Some(1) => Ok(PoolType::Replicated),
+					Some(3) => Ok(PoolType::Erasure),
+					other => Err(RadosError::UnexpectedReply(format!("pool {} has unknown type {:?}", name, other))),
+				};
+			}
+		}
+		Err(RadosError::UnexpectedReply(format!("pool {} not found in osd dump", name)))
+	}
+
+	/// Stream everything `reader` produces into the `oid` object.
+	///
+	/// The data is written in pieces of `chunk_size` bytes, at increasing
+	/// offsets. If the pool requires aligned writes, `chunk_size` is rounded
+	/// up to a multiple of the alignment, and it's capped at
+	/// `max_write_size()`. The first chunk replaces any existing content of
+	/// the object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_written)` on success
+	/// * `Err(RadosError)` on failure, including reading from `reader`;
+	///   `RadosError::NoSpace` if the cluster filled up, in which case the
+	///   object holds the chunks written so far; `EINVAL` if `chunk_size`
+	///   is `0`
+	pub fn upload<R>(&self, oid: &str, reader: R, chunk_size: usize) -> Result<u64, RadosError>
+		where R: Read
+	{
+		self.upload_with_progress(oid, reader, chunk_size, |_, _| {})
+	}
+
+	/// Like `upload()`, calling `progress(bytes_written, None)` after each
+	/// chunk has been written to the object. The total is never known up
+	/// front, since `reader` is only read as the upload goes.
+	///
+	/// # Panics
+	///
+	/// `progress` is only called between chunks, so if it panics the panic
+	/// propagates and the upload stops there, with the object holding
+	/// exactly the bytes last reported.
+	pub fn upload_with_progress<R, F>(&self, oid: &str, reader: R, chunk_size: usize, progress: F) -> Result<u64, RadosError>
+		where R: Read, F: FnMut(u64, Option<u64>)
+	{
+		let chunk_size = self.upload_chunk_size(chunk_size)?;
+		upload_chunks(self, oid, reader, chunk_size, progress)
+	}
+
+	/// Like `upload()`, with up to `inflight` chunks being written at once,
+	/// for throughput on large uploads.
+	///
+	/// The first chunk is written synchronously, replacing any existing
+	/// content of the object; the following ones are read from `reader` and
+	/// written with asynchronous writes at increasing offsets, waiting for
+	/// the oldest write once `inflight` are pending, so at most `inflight`
+	/// chunks are held in memory. All the writes have finished when this
+	/// returns, whether it succeeds or not.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_written)` on success
+	/// * `Err(RadosError)` on failure, including reading from `reader`, in
+	///   which case the object may hold any subset of the chunks; `EINVAL`
+	///   if `chunk_size` or `inflight` is `0`
+	pub fn upload_parallel<R>(&self, oid: &str, mut reader: R, chunk_size: usize, inflight: usize) -> Result<u64, RadosError>
+		where R: Read
+	{
+		if inflight == 0 {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let chunk_size = self.upload_chunk_size(chunk_size)?;
+		let oid_cs = CString::new(oid)?;
+		let mut buf = vec![0u8; chunk_size];
+		let len = fill_from(&mut reader, &mut buf)?;
+		RadosIo::write_full(self, oid, &buf[..len])?;
+		let mut offset = len as u64;
+		if len < chunk_size {
+			return Ok(offset);
+		}
+		// Each completion owns the chunk it writes, and waits for the write
+		// when dropped, so no buffer is freed while librados still reads it
+		let mut pending: VecDeque<WriteCompletion> = VecDeque::with_capacity(inflight);
+		loop {
+			let mut buf = vec![0u8; chunk_size];
+			let len = fill_from(&mut reader, &mut buf)?;
+			if len > 0 {
+				if pending.len() == inflight {
+					if let Some(oldest) = pending.pop_front() {
+						oldest.wait_complete()?;
+					}
+				}
+				buf.truncate(len);
+				let completion = WriteCompletion::start(self.handle, buf, |c, data| unsafe {
+					rados_aio_write(self.handle, oid_cs.as_ptr(), c, data.as_ptr() as *const c_char, data.len() as size_t, offset)
+				})?;
+				pending.push_back(completion);
+				offset += len as u64;
+			}
+			if len < chunk_size {
+				break;
+			}
+		}
+		for completion in pending.drain(..) {
+			completion.wait_complete()?;
+		}
+		return Ok(offset);
+	}
+
+	/// The chunk size `upload()` really uses when asked for `chunk_size`:
+	/// rounded up to the pool's alignment, and at most `max_write_size()`
+	fn upload_chunk_size(&self, chunk_size: usize) -> Result<usize, RadosError> {
+		if chunk_size == 0 {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let chunk_size = match self.required_alignment()? {
+			Some(alignment) if alignment > 0 => {
+				let alignment = alignment as usize;
+				chunk_size.saturating_add(alignment - 1) / alignment * alignment
+			},
+			_ => chunk_size,
+		};
+		Ok(cmp::min(chunk_size, self.max_write_size()))
+	}
+
+	/// Read up to `buf.len()` bytes from the `oid` object, starting at
+	/// `offset`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_read)` on success, `0` once `offset` is at or past the end
+	///   of the object
+	/// * `Err(RadosError)` on failure
+	pub fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let buf_ptr = buf.as_mut_ptr() as *mut c_char;
+		let n = match self.op_timeout() {
+			None => handle_errors!(rados_read(self.handle, oid_cs.as_ptr(), buf_ptr, buf.len() as size_t, offset)),
+			Some(timeout) => self.aio_with_timeout(timeout, |c| unsafe {
+				rados_aio_read(self.handle, oid_cs.as_ptr(), c, buf_ptr, buf.len() as size_t, offset)
+			})?,
+		};
+		return Ok(n as usize);
+	}
+
+	/// Fill `buf` with the bytes of the `oid` object starting at `offset`,
+	/// like `Read::read_exact()`, e.g. to read a precise region into a large
+	/// preallocated buffer or a memory map.
+	///
+	/// The region is read with as many reads as needed, each at most
+	/// `chunk_size()` bytes.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` once `buf` is full
+	/// * `Err(RadosError::UnexpectedEof)` if the object ends first, in
+	///   which case the contents of `buf` are unspecified
+	/// * `Err(RadosError)` on other failures
+	pub fn read_exact_into(&self, oid: &str, offset: u64, buf: &mut [u8]) -> Result<(), RadosError> {
+		let chunk_size = self.chunk_size();
+		let mut filled = 0;
+		while filled < buf.len() {
+			let end = cmp::min(buf.len(), filled + chunk_size);
+			let n = self.read_at(oid, &mut buf[filled..end], offset + filled as u64)?;
+			if n == 0 {
+				return Err(RadosError::UnexpectedEof);
+			}
+			filled += n;
+		}
+		Ok(())
+	}
+
+	/// Read up to `len` bytes from the `oid` object, starting at `offset`,
+	/// into a `Bytes` buffer.
+	///
+	/// # Returns
+	///
+	/// * `Ok(Bytes)` on success, shorter than `len` if the object ends first
+	/// * `Err(RadosError)` on failure
+	#[cfg(feature = "bytes")]
+	pub fn read_bytes(&self, oid: &str, len: usize, offset: u64) -> Result<Bytes, RadosError> {
+		let mut buf = BytesMut::zeroed(len);
+		let n = self.read_at(oid, &mut buf, offset)?;
+		buf.truncate(n);
+		Ok(buf.freeze())
+	}
+
+	/// Replace the contents of the `oid` object with everything remaining
+	/// in `buf`, writing each of its chunks in turn without copying them
+	/// into a single buffer first.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_written)` on success
+	/// * `Err(RadosError)` on failure
+	#[cfg(feature = "bytes")]
+	pub fn write_bytes_buf<B>(&self, oid: &str, mut buf: B) -> Result<u64, RadosError>
+		where B: Buf
+	{
+		RadosIo::write_full(self, oid, &[])?;
+		let mut offset: u64 = 0;
+		while buf.has_remaining() {
+			let len = {
+				let chunk = buf.chunk();
+				self.write_at(oid, chunk, offset)?;
+				chunk.len()
+			};
+			buf.advance(len);
+			offset += len as u64;
+		}
+		Ok(offset)
+	}
+
+	/// Serialize `value` in the given `format` and store it as the contents
+	/// of the `oid` object, replacing whatever was there.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError::Serialization)` if `value` couldn't be serialized
+	/// * `Err(RadosError)` on other failures
+	#[cfg(feature = "serde")]
+	pub fn write_value<T>(&self, oid: &str, value: &T, format: SerdeFormat) -> Result<(), RadosError>
+		where T: Serialize
+	{
+		let data = match format {
+			SerdeFormat::Json => serde_json::to_vec(value).map_err(|e| RadosError::Serialization(e.to_string()))?,
+			SerdeFormat::Bincode => bincode::serialize(value).map_err(|e| RadosError::Serialization(e.to_string()))?,
+		};
+		RadosIo::write_full(self, oid, &data)
+	}
+
+	/// Read the `oid` object and deserialize its contents from the given
+	/// `format`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(T)` on success
+	/// * `Err(RadosError::Serialization)` if the contents couldn't be
+	///   deserialized
+	/// * `Err(RadosError)` on other failures
+	#[cfg(feature = "serde")]
+	pub fn read_value<T>(&self, oid: &str, format: SerdeFormat) -> Result<T, RadosError>
+		where T: DeserializeOwned
+	{
+		let data = self.read_full(oid)?;
+		match format {
+			SerdeFormat::Json => serde_json::from_slice(&data).map_err(|e| RadosError::Serialization(e.to_string())),
+			SerdeFormat::Bincode => bincode::deserialize(&data).map_err(|e| RadosError::Serialization(e.to_string())),
+		}
+	}
+
+	/// Get the size and modification time of the `oid` object.
+	///
+	/// # Returns
+	///
+	/// * `Ok((size, mtime))` on success
+	/// * `Err(RadosError)` on failure
+	pub fn stat(&self, oid: &str) -> Result<(u64, SystemTime), RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut size: u64 = 0;
+		let mut mtime: libc::time_t = 0;
+		handle_errors!(rados_stat(self.handle, oid_cs.as_ptr(), &mut size, &mut mtime));
+		return Ok((size, mtime_to_system_time(mtime)));
+	}
+
+	/// Get the size, modification time and version of the `oid` object, in
+	/// a single read operation.
+	///
+	/// The modification time only has a resolution of a second, so two
+	/// writes within the same second leave it unchanged; the version
+	/// changes with every write, making it the better key for caches. As
+	/// with `read_if_changed()`, the version is picked up with
+	/// `rados_get_last_version()`, so don't call this concurrently on one
+	/// context.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectMeta)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::ObjectNotFound` if the
+	///   object doesn't exist
+	pub fn object_meta(&self, oid: &str) -> Result<ObjectMeta, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let op = ReadOp::new();
+		let mut size: u64 = 0;
+		let mut mtime: libc::time_t = 0;
+		let mut rval: c_int = 0;
+		unsafe {
+			rados_read_op_stat(op.handle, &mut size, &mut mtime, &mut rval);
+		}
+		handle_errors!(rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0));
+		if rval < 0 {
+			return Err(RadosError::from_return_code(rval));
+		}
+		let version = unsafe { rados_get_last_version(self.handle) };
+		return Ok(ObjectMeta {
+			size: size,
+			mtime: mtime_to_system_time(mtime),
+			version: version,
+		});
+	}
+
+	/// Read all of the `oid` object: its data, size, modification time,
+	/// version and xattrs, in a single read operation, so they all come
+	/// from the same version of the object, unlike separate `stat()`,
+	/// `read_full()` and `getxattrs()` calls.
+	///
+	/// The data is first read into a 64 KiB buffer. If the object turns out
+	/// to be larger, it's read again with a buffer of the right size, in a
+	/// read operation asserting the version just seen, until both reads
+	/// agree. As with `object_meta()`, don't call this concurrently on one
+	/// context.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectSnapshot)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::ObjectNotFound` if the
+	///   object doesn't exist
+	pub fn fetch(&self, oid: &str) -> Result<ObjectSnapshot, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut buf_size = FETCH_INITIAL_BUF_SIZE;
+		let mut expected_version = None;
+		loop {
+			let op = ReadOp::new();
+			let mut size: u64 = 0;
+			let mut mtime: libc::time_t = 0;
+			let mut stat_rval: c_int = 0;
+			let mut buf = vec![0u8; buf_size];
+			let mut bytes_read: size_t = 0;
+			let mut read_rval: c_int = 0;
+			let mut iter: rados_xattrs_iter_t = ptr::null();
+			let mut xattrs_rval: c_int = 0;
+			unsafe {
+				if let Some(version) = expected_version {
+					rados_read_op_assert_version(op.handle, version);
+				}
+				rados_read_op_stat(op.handle, &mut size, &mut mtime, &mut stat_rval);
+				rados_read_op_read(op.handle, 0, buf.len() as size_t, buf.as_mut_ptr() as *mut c_char,
+					&mut bytes_read, &mut read_rval);
+				rados_read_op_getxattrs(op.handle, &mut iter, &mut xattrs_rval);
+			}
+			let err = unsafe { rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0) };
+			let err = [err, stat_rval, read_rval, xattrs_rval].iter().cloned().find(|&err| err < 0).unwrap_or(0);
+			if err < 0 || size as usize > buf.len() {
+				if !iter.is_null() {
+					unsafe { rados_getxattrs_end(iter) };
+				}
+				if err == -libc::ERANGE || err == -libc::EOVERFLOW {
+					// Changed since the previous read, start over
+					expected_version = None;
+					continue;
+				}
+				if err < 0 {
+					return Err(RadosError::from_return_code(err));
+				}
+				// Larger than the buffer: read it again, all of it
+				buf_size = size as usize;
+				expected_version = Some(unsafe { rados_get_last_version(self.handle) });
+				continue;
+			}
+			let version = unsafe { rados_get_last_version(self.handle) };
+			let xattrs = collect_xattrs(iter, |_| true)?;
+			buf.truncate(bytes_read as usize);
+			return Ok(ObjectSnapshot {
+				data: buf,
+				size: size,
+				mtime: mtime_to_system_time(mtime),
+				version: version,
+				xattrs: xattrs,
+			});
+		}
+	}
+
+	/// Read the whole `oid` object, unless it's still at `known_version`,
+	/// e.g. to revalidate a cached copy.
+	///
+	/// The object's version is checked first, with a `stat`; only if it
+	/// changed is the data read, in a read operation asserting the version
+	/// just seen, so the data and version returned always match. Versions
+	/// are picked up with `rados_get_last_version()`, which is shared by
+	/// the whole context, so don't call this concurrently on one context.
+	///
+	/// # Returns
+	///
+	/// * `Ok(None)` if the object is at `known_version`
+	/// * `Ok(Some((version, data)))` with its current contents otherwise
+	/// * `Err(RadosError)` on failure
+	pub fn read_if_changed(&self, oid: &str, known_version: u64) -> Result<Option<(u64, Vec<u8>)>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		loop {
+			let (size, _) = self.stat(oid)?;
+			let version = unsafe { rados_get_last_version(self.handle) };
+			if version == known_version {
+				return Ok(None);
+			}
+			let op = ReadOp::new();
+			let mut buf = vec![0u8; size as usize];
+			let mut bytes_read: size_t = 0;
+			let mut rval: c_int = 0;
+			unsafe {
+				rados_read_op_assert_version(op.handle, version);
+				rados_read_op_read(op.handle, 0, buf.len() as size_t, buf.as_mut_ptr() as *mut c_char,
+					&mut bytes_read, &mut rval);
+			}
+			let err = unsafe { rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0) };
+			if err == -libc::ERANGE || err == -libc::EOVERFLOW {
+				// Changed again since the stat
+				continue;
+			}
+			if err < 0 {
+				return Err(RadosError::from_return_code(err));
+			}
+			if rval < 0 {
+				return Err(RadosError::from_return_code(rval));
+			}
+			buf.truncate(bytes_read as usize);
+			return Ok(Some((version, buf)));
+		}
+	}
+
+	/// Wait until the `oid` object is at least `at_least` bytes long, e.g.
+	/// to let a reader catch up with a concurrent appender.
+	///
+	/// The object is polled with `stat()`, waiting 10ms between the first
+	/// polls and doubling that up to 500ms. A missing object counts as
+	/// empty, so this can be called before the object is created.
+	///
+	/// # Returns
+	///
+	/// * `Ok(size)` with the object's size once it's large enough
+	/// * `Err(RadosError::Timeout)` if it isn't within `timeout`
+	/// * `Err(RadosError)` on other failures
+	pub fn wait_for_size(&self, oid: &str, at_least: u64, timeout: Duration) -> Result<u64, RadosError> {
+		let deadline = deadline_after(timeout);
+		let mut backoff = Duration::from_millis(10);
+		loop {
+			let size = match self.stat(oid) {
+				Ok((size, _)) => size,
+				Err(RadosError::ObjectNotFound) => 0,
+				Err(e) => return Err(e),
+			};
+			if size >= at_least {
+				return Ok(size);
+			}
+			let now = Instant::now();
+			match deadline {
+				Some(deadline) if now >= deadline => return Err(RadosError::Timeout),
+				Some(deadline) => thread::sleep(cmp::min(backoff, deadline - now)),
+				None => thread::sleep(backoff),
+			}
+			backoff = cmp::min(backoff * 2, Duration::from_millis(500));
+		}
+	}
+
+	/// Read each of the `oids` objects in full.
+	///
+	/// Every object is read with `read_full()`. A failure for one
+	/// object doesn't stop the others from being read.
+	///
+	/// # Returns
+	///
+	/// A `(oid, result)` pair for each of `oids`, in the same order.
+	pub fn read_many(&self, oids: &[&str]) -> Vec<(String, Result<Vec<u8>, RadosError>)> {
+		oids.iter().map(|oid| (oid.to_string(), self.read_full(oid))).collect()
+	}
+
+	/// Read the whole `oid` object.
+	///
+	/// The object is read `chunk_size()` bytes at a time until a read
+	/// returns no data. A read can legitimately return less than asked for
+	/// before the end of the object, so reading continues from wherever the
+	/// last one stopped. The buffer is sized up front with `stat()`, but
+	/// data appended since is read too.
+	///
+	/// # Returns
+	///
+	/// * `Ok(contents)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn read_full(&self, oid: &str) -> Result<Vec<u8>, RadosError> {
+		let (size, _) = self.stat(oid)?;
+		read_chunks(self, oid, size as usize, self.chunk_size())
+	}
+
+	/// Get a handle on the `oid` object, to operate on it without repeating
+	/// its name on every call.
+	///
+	/// # Examples
+	///
+	/// ```rust,no_run
+	/// # use ceph::rados::{Cluster, CreateFlags};
+	/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	/// # let ioctx = cluster.create_ioctx("data").unwrap();
+	/// let hw = ioctx.object("hw");
+	/// hw.write(b"Hello, world.").unwrap();
+	/// hw.setxattr("lang", "en_US").unwrap();
+	/// assert_eq!(hw.read().unwrap(), b"Hello, world.");
+	/// ```
+	pub fn object<'a>(&'a self, oid: &str) -> RadosObject<'a> {
+		RadosObject { ioctx: self, oid: oid.to_string() }
+	}
+
+	/// Read up to `buf.len()` bytes from the `oid` object, starting at
+	/// `offset`, passing `flags` as hints to the OSDs.
+	///
+	/// For example, a scan that reads every object once can use
+	/// `ReadFlags::FADVISE_NOCACHE` to avoid polluting the OSD cache.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_read)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn read_with_flags(&self, oid: &str, buf: &mut [u8], offset: u64, flags: ReadFlags) -> Result<usize, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let op = ReadOp::new();
+		let mut bytes_read: size_t = 0;
+		let mut rval: c_int = 0;
+		unsafe {
+			rados_read_op_read(op.handle, offset, buf.len() as size_t, buf.as_mut_ptr() as *mut c_char,
+				&mut bytes_read, &mut rval);
+			rados_read_op_set_flags(op.handle, flags.bits() as c_int);
+		}
+		handle_errors!(rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0));
+		if rval < 0 {
+			return Err(RadosError::from_return_code(rval));
+		}
+		return Ok(bytes_read as usize);
+	}
+
+	/// Compute a checksum of `len` bytes of the `oid` object, starting at
+	/// `offset`, on the OSDs, without transferring the data. `offset` and
+	/// `len` both `0` checksum the whole object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(checksum)` on success, in little endian byte order (4 bytes for
+	///   `XxHash32` and `Crc32c`, 8 for `XxHash64`)
+	/// * `Err(RadosError)` on failure
+	#[cfg(feature = "luminous")]
+	pub fn checksum(&self, oid: &str, checksum_type: ChecksumType, offset: u64, len: usize) -> Result<Vec<u8>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let (raw_type, size) = checksum_type.raw();
+		let init_value: Vec<u8> = match checksum_type {
+			ChecksumType::Crc32c => vec![0xff; size],
+			_ => vec![0; size],
+		};
+		// The result is encoded as a u32 count of checksums, followed by
+		// the checksums; without a chunk size there's exactly one
+		let mut out = vec![0u8; 4 + size];
+		let op = ReadOp::new();
+		let mut rval: c_int = 0;
+		unsafe {
+			rados_read_op_checksum(op.handle, raw_type, init_value.as_ptr() as *const c_char, size as size_t,
+				offset, len as size_t, 0, out.as_mut_ptr() as *mut c_char, out.len() as size_t, &mut rval);
+		}
+		handle_errors!(rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0));
+		if rval < 0 {
+			return Err(RadosError::from_return_code(rval));
+		}
+		let count = out[0] as u32 | (out[1] as u32) << 8 | (out[2] as u32) << 16 | (out[3] as u32) << 24;
+		if count != 1 {
+			return Err(RadosError::UnexpectedReply(format!("expected 1 checksum, got {}", count)));
+		}
+		return Ok(out.split_off(4));
+	}
+
+	/// Stream the contents of the `oid` object into `writer`.
+	///
+	/// The object is read `chunk_size` bytes at a time until a read returns
+	/// no data, and each piece is written to `writer` as it arrives. A
+	/// `chunk_size` of `0` uses the context's `chunk_size()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(bytes_written)` on success
+	/// * `Err(RadosError)` on failure, including writing to `writer`
+	pub fn download<W>(&self, oid: &str, writer: W, chunk_size: usize) -> Result<u64, RadosError>
+		where W: Write
+	{
+		self.download_chunks(oid, writer, chunk_size, None, |_, _| {})
+	}
+
+	/// Like `download()`, calling `progress(bytes_written, Some(total))`
+	/// after each chunk has been written to `writer`. The total is the
+	/// object's size when the download starts, so it's only an estimate if
+	/// the object is being written concurrently.
+	///
+	/// # Panics
+	///
+	/// `progress` is only called between chunks, so if it panics the panic
+	/// propagates and the download stops there, with `writer` having
+	/// received exactly the bytes last reported.
+	pub fn download_with_progress<W, F>(&self, oid: &str, writer: W, chunk_size: usize, progress: F) -> Result<u64, RadosError>
+		where W: Write, F: FnMut(u64, Option<u64>)
+	{
+		let (size, _) = self.stat(oid)?;
+		self.download_chunks(oid, writer, chunk_size, Some(size), progress)
+	}
+
+	fn download_chunks<W, F>(&self, oid: &str, writer: W, chunk_size: usize, total: Option<u64>, progress: F) -> Result<u64, RadosError>
+		where W: Write, F: FnMut(u64, Option<u64>)
+	{
+		let chunk_size = if chunk_size == 0 { self.chunk_size() } else { chunk_size };
+		download_chunks(self, oid, writer, chunk_size, total, progress)
+	}
+
+	/// Read up to `len` bytes from the start of the `oid` object, as a
+	/// string. Reading less than the whole object is not an error; use
+	/// `read_full()` to read all of it.
+	///
+	/// # Returns
+	///
+	/// * `Ok(contents)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn read(&self, oid: &str, len: usize) -> Result<String, RadosError> {
+		// Need to hang on the the CString, can immediately do as_ptr()
+		// see https://github.com/rust-lang/rust/issues/16035
+		let oid_cs = CString::new(oid)?;
+		let mut buf = vec![0u8; len];
+		let bytes_read = handle_errors!(rados_read(self.handle, oid_cs.as_ptr(), buf.as_mut_ptr() as *mut c_char, len as size_t, 0));
+		buf.truncate(bytes_read as usize);
+		return Ok(c_buf_to_string(&buf));
+	}
+
+	/// Get the `name` extended attribute of the `oid` object, as a string.
+	///
+	/// `len` is the expected length of the value. If the value turns out
+	/// to be longer, it's read again with a larger buffer, so the whole
+	/// value is always returned.
+	///
+	/// # Returns
+	///
+	/// * `Ok(value)` on success
+	/// * `Err(RadosError::ObjectNotFound)` if the object doesn't exist
+	/// * `Err(RadosError::AttrNotFound)` if the object exists without the
+	///   attribute
+	/// * `Err(RadosError)` on other failures
+	pub fn getxattr<S>(&self, oid: S, name: S, len: usize) -> Result<String, RadosError>
+		where S: Into<Vec<u8>>
+	{
+		// Need to hang on the the CString, can't immediately do as_ptr()
+		// see https://github.com/rust-lang/rust/issues/16035
+		let oid_cs = CString::new(oid)?;
+		let name_cs = CString::new(name)?;
+		let filled = fill_buffer(len, |buf| unsafe {
+			rados_getxattr(self.handle, oid_cs.as_ptr(), name_cs.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len() as size_t)
+		});
+		match filled {
+			Ok((mut buf, len)) => {
+				buf.truncate(len);
+				Ok(c_buf_to_string(&buf))
+			},
+			Err(err) => Err(RadosError::from_return_code(err)),
+		}
+	}
+
+	pub fn setxattr<S, T>(&self, oid: S, name: S, value: T) -> Result<(), RadosError>
+		where S: Into<Vec<u8>>, T: Into<String>
+	{
+		// Need to hang on the the CString, can't immediately do as_ptr()
+		// see https://github.com/rust-lang/rust/issues/16035
+		let oid_cs = CString::new(oid)?;
+		let name_cs = CString::new(name)?;
+		// allow for terminating '\0' (not really needed)
+		let s : String = value.into();
+		let len : size_t = s.len() as size_t;
+		let buf = CString::new(s)?;
+		handle_errors!(rados_setxattr(self.handle, oid_cs.as_ptr(), name_cs.as_ptr(), buf.as_ptr(), len));
 		return Ok(());
 	}
 
-	/// Configure the cluster handle using a Ceph config file
+	/// Atomically perform all the actions in `op` on the `oid` object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case none of the actions
+	///   were applied; `RadosError::NoSpace` if the cluster is full
+	pub fn operate(&self, oid: &str, op: &WriteOp) -> Result<(), RadosError> {
+		let oid_cs = CString::new(oid)?;
+		op.check()?;
+		handle_errors!(rados_write_op_operate(op.handle, self.handle, oid_cs.as_ptr(), ptr::null_mut(), 0));
+		return Ok(());
+	}
+
+	/// Write `data` as the whole contents of the `oid` object, with its
+	/// CRC-32C in the `RECORD_CRC_XATTR` xattr and `mtime` as its
+	/// modification time, all in a single write operation, e.g. to restore
+	/// a backed up object as it was.
+	///
+	/// The modification time is stored to the second.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case the object is left as
+	///   it was; `EINVAL` if `mtime` is before the Unix epoch
+	pub fn write_record(&self, oid: &str, data: &[u8], mtime: SystemTime) -> Result<(), RadosError> {
+		let mut mtime = match mtime.duration_since(UNIX_EPOCH) {
+			Ok(since_epoch) => since_epoch.as_secs() as libc::time_t,
+			Err(_) => return Err(RadosError::Other(libc::EINVAL)),
+		};
+		let crc = format!("{:08x}", crc32c::checksum(data));
+		let mut op = WriteOp::new();
+		op.write_full(data).setxattr(RECORD_CRC_XATTR, crc.as_bytes());
+		let oid_cs = CString::new(oid)?;
+		op.check()?;
+		handle_errors!(rados_write_op_operate(op.handle, self.handle, oid_cs.as_ptr(), &mut mtime, 0));
+		return Ok(());
+	}
+
+	/// Append `data` to the `oid` object, creating it first if it doesn't
+	/// exist.
+	///
+	/// Both steps happen in a single write operation, so concurrent writers
+	/// appending to the same object never race between checking for the
+	/// object and appending to it.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn append_create(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.create(false).append(data);
+		return self.operate(oid, &op);
+	}
+
+	/// Append `data` to the `oid` object, creating it if it doesn't exist,
+	/// unless that would make it larger than `max_size` bytes, e.g. for
+	/// bounded log objects.
+	///
+	/// The object's size and version are read first, and the append is
+	/// made in a write operation asserting that version, so it can't race
+	/// with other writers: if the object changed in between, the whole
+	/// cycle is retried. As with `object_meta()`, don't call this
+	/// concurrently on one context.
+	///
+	/// # Returns
+	///
+	/// * `Ok(size)` with the object's new size on success
+	/// * `Err(RadosError::QuotaExceeded)` if the object would grow past
+	///   `max_size`, in which case it is left unchanged
+	/// * `Err(RadosError)` on other failures
+	pub fn append_capped(&self, oid: &str, data: &[u8], max_size: u64) -> Result<u64, RadosError> {
+		loop {
+			let meta = match self.object_meta(oid) {
+				Ok(meta) => Some(meta),
+				Err(RadosError::ObjectNotFound) => None,
+				Err(e) => return Err(e),
+			};
+			let size = meta.map_or(0, |meta| meta.size);
+			let new_size = size + data.len() as u64;
+			if new_size > max_size {
+				return Err(RadosError::QuotaExceeded);
+			}
+			let mut op = WriteOp::new();
+			match meta {
+				Some(meta) => { op.assert_version(meta.version); },
+				None => { op.create(true); },
+			}
+			op.append(data);
+			match self.operate(oid, &op) {
+				Ok(()) => return Ok(new_size),
+				// Written to, created or removed by someone else in between
+				Err(RadosError::Other(libc::ERANGE)) | Err(RadosError::Other(libc::EOVERFLOW)) |
+				Err(RadosError::AlreadyExists) | Err(RadosError::ObjectNotFound) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Replace the contents of the `oid` object with `data`, creating it
+	/// if it doesn't exist, but only while the object is smaller than
+	/// `max_current_size` bytes, e.g. to keep accumulating into an object
+	/// until it's big enough to roll over to the next one.
+	///
+	/// RADOS has no operation comparing an object's size, so the size and
+	/// version are read first, and the write is made in a write operation
+	/// asserting that version, as `append_capped()` does: if the object
+	/// changed in between, the check is made again rather than writing on
+	/// a stale size. As with `object_meta()`, don't call this concurrently
+	/// on one context.
+	///
+	/// # Returns
+	///
+	/// * `Ok(true)` if the object was written
+	/// * `Ok(false)` if it was already `max_current_size` bytes or larger,
+	///   in which case it is left unchanged
+	/// * `Err(RadosError)` on failure
+	pub fn write_if_smaller_than(&self, oid: &str, data: &[u8], max_current_size: u64) -> Result<bool, RadosError> {
+		loop {
+			let meta = match self.object_meta(oid) {
+				Ok(meta) => Some(meta),
+				Err(RadosError::ObjectNotFound) => None,
+				Err(e) => return Err(e),
+			};
+			if meta.map_or(0, |meta| meta.size) >= max_current_size {
+				return Ok(false);
+			}
+			let mut op = WriteOp::new();
+			match meta {
+				Some(meta) => { op.assert_version(meta.version); },
+				None => { op.create(true); },
+			}
+			op.write_full(data);
+			match self.operate(oid, &op) {
+				Ok(()) => return Ok(true),
+				// Written to, created or removed by someone else in between
+				Err(RadosError::Other(libc::ERANGE)) | Err(RadosError::Other(libc::EOVERFLOW)) |
+				Err(RadosError::AlreadyExists) | Err(RadosError::ObjectNotFound) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Create the `oid` object in `category` and write `data` as its
+	/// contents, in a single write operation so the object never exists
+	/// without its data. If `exclusive` is `true`, this fails with
+	/// `RadosError::AlreadyExists` when the object already exists.
+	///
+	/// Object categories are a legacy feature: current clusters accept the
+	/// category but ignore it, so it can't be read back. It is still sent,
+	/// for tools migrating from older Ceph releases.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn create_full(&self, oid: &str, data: &[u8], exclusive: bool, category: Option<&str>) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		match category {
+			Some(category) => op.create_in_category(exclusive, category),
+			None => op.create(exclusive),
+		};
+		op.write_full(data);
+		return self.operate(oid, &op);
+	}
+
+	/// Make sure the `oid` object exists and is empty, creating it if it's
+	/// missing and truncating it otherwise, like opening a file with
+	/// `O_CREAT | O_TRUNC`. Both happen in a single write operation.
+	/// Extended attributes and omap entries of an existing object are kept.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn create_or_truncate(&self, oid: &str) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.create(false).truncate(0);
+		return self.operate(oid, &op);
+	}
+
+	/// Zero `len` bytes of the `oid` object, starting at `offset`.
+	///
+	/// This happens on the OSDs, so no zero bytes are sent over the wire, and
+	/// the range may be deallocated, leaving a sparse object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn zero(&self, oid: &str, offset: u64, len: u64) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.zero(offset, len);
+		return self.operate(oid, &op);
+	}
+
+	/// Remove all the omap key/value pairs of the `oid` object in a single
+	/// operation.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn omap_clear(&self, oid: &str) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.omap_clear();
+		return self.operate(oid, &op);
+	}
+
+	/// Get all the omap key/value pairs of the `oid` object, sorted by key.
+	///
+	/// The entries are fetched a page at a time, so this works for objects
+	/// with more entries than a single read operation may return. Pages are
+	/// separate reads, so concurrent updates may be seen partially.
+	///
+	/// # Returns
+	///
+	/// * `Ok(entries)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn export_omap(&self, oid: &str) -> Result<Vec<(String, Vec<u8>)>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+		loop {
+			let start_after = CString::new(entries.last().map(|e| e.0.as_str()).unwrap_or(""))?;
+			let op = ReadOp::new();
+			let mut iter: rados_omap_iter_t = ptr::null();
+			let mut more: c_uchar = 0;
+			let mut rval: c_int = 0;
+			let err = unsafe {
+				omap_get_vals(op.handle, start_after.as_ptr(), OMAP_PAGE_SIZE, &mut iter, &mut more, &mut rval);
+				rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0)
+			};
+			let err = if err < 0 { err } else { rval };
+			if err < 0 {
+				if !iter.is_null() {
+					unsafe { rados_omap_get_end(iter) };
+				}
+				return Err(RadosError::from_return_code(err));
+			}
+			let mut count = 0;
+			let mut result = Ok(());
+			loop {
+				let mut key: *mut c_char = ptr::null_mut();
+				let mut val: *mut c_char = ptr::null_mut();
+				let mut len: size_t = 0;
+				let err = unsafe { rados_omap_get_next(iter, &mut key, &mut val, &mut len) };
+				if err < 0 {
+					result = Err(RadosError::from_return_code(err));
+					break;
+				}
+				if key.is_null() {
+					break;
+				}
+				let key = unsafe { CStr::from_ptr(key) }.to_string_lossy().into_owned();
+				let value = if val.is_null() {
+					Vec::new()
+				} else {
+					unsafe { ::std::slice::from_raw_parts(val as *const u8, len as usize) }.to_vec()
+				};
+				entries.push((key, value));
+				count += 1;
+			}
+			unsafe {
+				rados_omap_get_end(iter);
+			}
+			result?;
+			if more == 0 || count == 0 {
+				return Ok(entries);
+			}
+		}
+	}
+
+	/// Replace all the omap key/value pairs of the `oid` object with
+	/// `entries`, e.g. as returned by `export_omap()`.
+	///
+	/// The omap is cleared and repopulated in a single write operation, so
+	/// it's never seen half restored. The object is created if it doesn't
+	/// exist.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case the omap is unchanged
+	pub fn import_omap<K, V>(&self, oid: &str, entries: &[(K, V)]) -> Result<(), RadosError>
+		where K: AsRef<str>, V: AsRef<[u8]>
+	{
+		let mut op = WriteOp::new();
+		op.create(false).omap_clear().omap_set(entries);
+		return self.operate(oid, &op);
+	}
+
+	// The omap header can't be read or written from here: librados only
+	// exposes omap_set_header() and omap_get_header() in its C++ API, with
+	// no rados_write_op_* / rados_read_op_* equivalent to bind to.
+
+	/// List the names of the objects in the pool.
+	///
+	/// Uses `rados_nobjects_list_*` with the `luminous` feature (the
+	/// default), and the older `rados_objects_list_*` otherwise.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectIterator)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn list_objects(&self) -> Result<ObjectIterator<'_>, RadosError> {
+		let mut ctx: rados_list_ctx_t = ptr::null();
+		handle_errors!(list_open(self.handle, &mut ctx));
+		return Ok(ObjectIterator { ctx: ctx, end: None, position: 0, in_pg: 0, pending: None, ioctx: PhantomData });
+	}
+
+	/// List the names of the objects in the pool matching the glob
+	/// `pattern`: `*` matches any run of characters, `?` any single
+	/// character, and `\` escapes the character after it. All the objects
+	/// under `logs/` are listed by `logs/*`.
+	///
+	/// RADOS can't filter object names, so this lists the whole pool and
+	/// filters the names on the client; it takes as long as listing every
+	/// object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(names)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn list_objects_matching(&self, pattern: &str) -> Result<Vec<String>, RadosError> {
+		let mut names = Vec::new();
+		for name in self.list_objects()? {
+			let name = name?;
+			if glob_match(pattern, &name) {
+				names.push(name);
+			}
+		}
+		Ok(names)
+	}
+
+	/// Count the objects in the `nspace` namespace of the pool and add up
+	/// their sizes, e.g. to report how much each tenant uses when they each
+	/// have a namespace. `""` is the default namespace.
+	///
+	/// RADOS keeps no statistics per namespace, so this lists the objects
+	/// in the namespace and stats them one at a time: it takes time in
+	/// proportion to the number of objects in the whole pool, and isn't a
+	/// consistent snapshot if objects are written meanwhile. Objects
+	/// removed while it runs are skipped. The listing goes through a new
+	/// context (see `try_clone()`), so the namespace of this one is left
+	/// alone.
+	///
+	/// # Returns
+	///
+	/// * `Ok(NamespaceStats)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn namespace_stats(&self, nspace: &str) -> Result<NamespaceStats, RadosError> {
+		let ioctx = self.try_clone()?;
+		ioctx.set_namespace(Some(nspace))?;
+		let mut stats = NamespaceStats::default();
+		for oid in ioctx.list_objects()? {
+			match ioctx.stat(&oid?) {
+				Ok((size, _)) => {
+					stats.objects += 1;
+					stats.bytes += size;
+				},
+				Err(RadosError::ObjectNotFound) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(stats)
+	}
+
+	/// List the names of the objects in the pool with their sizes, e.g.
+	/// for `du`-style reports.
+	///
+	/// RADOS doesn't list sizes, so each object is stat'ed as well: that's
+	/// a round trip to an OSD per object on top of the listing. To keep
+	/// that from adding up, up to 32 stats are kept in flight as
+	/// asynchronous operations while the listing goes on, and the results
+	/// come back in listing order. Objects removed before they're stat'ed
+	/// are skipped.
+	///
+	/// # Returns
+	///
+	/// An iterator over `(name, size)` pairs; if the listing can't be
+	/// started, it yields the error.
+	pub fn list_objects_with_size(&self) -> ObjectSizeIterator<'_> {
+		let (names, error) = match self.list_objects() {
+			Ok(names) => (Some(names), None),
+			Err(e) => (None, Some(e)),
+		};
+		ObjectSizeIterator { ioctx: self, names: names, error: error, pending: VecDeque::with_capacity(STAT_BATCH_SIZE) }
+	}
+
+	/// Remove every object in the namespace of this context (see
+	/// `set_namespace()`), e.g. to clean up after tests. Objects in other
+	/// namespaces of the pool are left alone.
+	///
+	/// **This is destructive**, and not atomic: objects are listed and
+	/// removed one at a time, so a failure leaves some of them removed, and
+	/// objects written while the purge runs may or may not survive it.
+	/// Objects removed by someone else in the meantime are skipped.
+	///
+	/// # Returns
+	///
+	/// * `Ok(count)` with the number of objects removed, on success
+	/// * `Err(RadosError)` on failure
+	pub fn purge(&self) -> Result<u64, RadosError> {
+		let mut removed = 0;
+		for oid in self.list_objects()? {
+			match self.remove(&oid?) {
+				Ok(()) => removed += 1,
+				Err(RadosError::ObjectNotFound) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(removed)
+	}
+
+	/// List the names of the objects in the pool as a `Stream`, for async
+	/// code.
+	///
+	/// librados only lists objects synchronously, so the listing runs on a
+	/// thread of its own, through a new context on the pool set to the
+	/// namespace of this one, and hands the names over a bounded channel;
+	/// it stays at most a few dozen names ahead of the consumer. Dropping
+	/// the stream stops the listing.
+	#[cfg(feature = "futures")]
+	pub fn list_objects_stream(&self) -> impl Stream<Item = Result<String, RadosError>> {
+		let (mut tx, rx) = mpsc::channel(LIST_STREAM_BUFFER);
+		// try_clone() starts from the default namespace
+		let ioctx = self.namespace().and_then(|nspace| {
+			let ioctx = self.try_clone()?;
+			ioctx.set_namespace(Some(&nspace))?;
+			Ok(ioctx)
+		});
+		thread::spawn(move || {
+			let ioctx = match ioctx {
+				Ok(ioctx) => ioctx,
+				Err(e) => {
+					let _ = block_on(tx.send(Err(e)));
+					return;
+				},
+			};
+			let objects = match ioctx.list_objects() {
+				Ok(objects) => objects,
+				Err(e) => {
+					let _ = block_on(tx.send(Err(e)));
+					return;
+				},
+			};
+			for object in objects {
+				// Fails once the stream was dropped
+				if block_on(tx.send(object)).is_err() {
+					break;
+				}
+			}
+		});
+		rx
+	}
+
+	/// Resume listing the names of the objects in the pool where the
+	/// listing that returned `cursor` from `ObjectIterator::cursor()` left
+	/// off, e.g. after a restart.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectIterator)` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` if `cursor` isn't a cursor
+	pub fn list_objects_from(&self, cursor: &[u8]) -> Result<ObjectIterator<'_>, RadosError> {
+		let (position, in_pg) = decode_cursor(cursor)?;
+		let mut ctx: rados_list_ctx_t = ptr::null();
+		handle_errors!(list_open(self.handle, &mut ctx));
+		let mut iter = ObjectIterator { ctx: ctx, end: None, position: 0, in_pg: 0, pending: None, ioctx: PhantomData };
+		unsafe { list_seek(ctx, position) };
+		// Skip what was already listed of the placement group
+		while iter.in_pg < in_pg {
+			match iter.next() {
+				Some(Ok(_)) if iter.position == position => {},
+				Some(Ok(name)) => {
+					// That placement group shrank: the first object of the
+					// next one mustn't be skipped
+					iter.pending = Some(name);
+					break;
+				},
+				Some(Err(e)) => return Err(e),
+				None => break,
+			}
+		}
+		return Ok(iter);
+	}
+
+	/// List the names of the objects in shard `shard` of `of` of the pool,
+	/// so `of` workers can each list a disjoint part of the pool in
+	/// parallel.
+	///
+	/// The pool's placement groups are split into `of` contiguous ranges of
+	/// hash positions, and this lists the objects of the `shard`th range.
+	/// Together the shards list every object exactly once, as long as the
+	/// pool's `pg_num` doesn't change meanwhile. With more shards than
+	/// placement groups, some shards are empty.
+	///
+	/// # Returns
+	///
+	/// * `Ok(ObjectIterator)` on success
+	/// * `Err(RadosError)` on failure, `EINVAL` unless `shard < of`
+	pub fn list_objects_sharded(&self, shard: u32, of: u32) -> Result<ObjectIterator<'_>, RadosError> {
+		if shard >= of {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		let name = self.pool_name()?;
+		let cluster = unsafe { rados_ioctx_get_cluster(self.handle) };
+		let reply = mon_command_json(cluster, &json!({"prefix": "osd pool get", "pool": name, "var": "pg_num", "format": "json"}))?;
+		let pg_num = match reply["pg_num"].as_u64() {
+			Some(pg_num) => pg_num,
+			None => return Err(RadosError::UnexpectedReply(format!("no pg_num for pool {}", name))),
+		};
+		let start = (pg_num * shard as u64 / of as u64) as u32;
+		let end = (pg_num * (shard as u64 + 1) / of as u64) as u32;
+		let mut ctx: rados_list_ctx_t = ptr::null();
+		handle_errors!(list_open(self.handle, &mut ctx));
+		let iter = ObjectIterator { ctx: ctx, end: Some(end), position: 0, in_pg: 0, pending: None, ioctx: PhantomData };
+		if start > 0 {
+			unsafe { list_seek(ctx, start) };
+		}
+		return Ok(iter);
+	}
+
+	/// Write `data` as the whole contents of the `oid` object, subject to
+	/// `mode`.
+	///
+	/// The check on whether the object exists is part of the same write
+	/// operation, so it can't race with other writers.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, including when the object doesn't
+	///   meet the expectation of `mode`; `RadosError::NoSpace` if the
+	///   cluster is full
+	pub fn put(&self, oid: &str, data: &[u8], mode: WriteMode) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		match mode {
+			WriteMode::CreateOrReplace => {},
+			WriteMode::CreateNew => { op.create(true); },
+			WriteMode::ReplaceExisting => { op.assert_exists(); },
+		}
+		op.write_full(data);
+		return self.operate(oid, &op);
+	}
+
+	/// Write `data` as the contents of a new `oid` object that can never be
+	/// written again through this call.
+	///
+	/// The object is created exclusively, in the same write operation as
+	/// its data, so of several writers racing on the same `oid` exactly one
+	/// succeeds. Useful for content-addressed objects named after a hash of
+	/// their contents. Nothing stops other calls (`write()`, `remove()`,
+	/// ...) from changing the object later.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `RadosError::AlreadyExists` if the
+	///   object was written before, in which case it is left untouched
+	pub fn write_immutable(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		return self.put(oid, data, WriteMode::CreateNew);
+	}
+
+	/// Store `data` in an object named after its SHA-256 digest, as 64
+	/// lowercase hex digits, making the pool a content-addressed store.
+	///
+	/// The object is written with `write_immutable()`. If it already exists
+	/// it holds the same data, so storing the same data twice just returns
+	/// the same name again.
+	///
+	/// # Returns
+	///
+	/// * `Ok(oid)` on success, with the name of the object holding `data`
+	/// * `Err(RadosError)` on failure
+	pub fn put_cas(&self, data: &[u8]) -> Result<String, RadosError> {
+		let oid = sha256::hex_digest(data);
+		match self.write_immutable(&oid, data) {
+			Ok(()) | Err(RadosError::AlreadyExists) => return Ok(oid),
+			Err(e) => return Err(e),
+		}
+	}
+
+	/// Replace the contents of the `oid` object with `data`.
+	///
+	/// The object is truncated and rewritten in a single write operation, so
+	/// readers see either the old or the new contents, never a partially
+	/// truncated object. Use `replace_with_xattrs()` to update extended
+	/// attributes in the same operation.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn replace(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		self.replace_with_xattrs(oid, data, &[])
+	}
+
+	/// Like `replace()`, also setting the extended attributes in `attrs` as
+	/// part of the same write operation.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case neither the contents
+	///   nor the attributes were changed
+	pub fn replace_with_xattrs(&self, oid: &str, data: &[u8], attrs: &[(&str, &[u8])]) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		op.truncate(0).write(data, 0);
+		for &(name, value) in attrs {
+			op.setxattr(name, value);
+		}
+		return self.operate(oid, &op);
+	}
+
+	/// Set several extended attributes of the `oid` object at once.
+	///
+	/// All the attributes are set in a single write operation, so readers
+	/// see either none or all of the changes. To only update an object that
+	/// already exists, build the operation by hand with
+	/// `WriteOp::assert_exists()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, in which case no attribute was set
+	pub fn setxattrs(&self, oid: &str, attrs: &[(&str, &[u8])]) -> Result<(), RadosError> {
+		let mut op = WriteOp::new();
+		for &(name, value) in attrs {
+			op.setxattr(name, value);
+		}
+		return self.operate(oid, &op);
+	}
+
+	/// Get all the extended attributes of the `oid` object.
+	///
+	/// # Returns
+	///
+	/// * `Ok(HashMap<name, value>)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn getxattrs(&self, oid: &str) -> Result<HashMap<String, Vec<u8>>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let mut iter: rados_xattrs_iter_t = ptr::null();
+		handle_errors!(rados_getxattrs(self.handle, oid_cs.as_ptr(), &mut iter));
+		collect_xattrs(iter, |_| true)
+	}
+
+	/// Get the extended attributes of the `oid` object named in `names`.
+	///
+	/// They're all read by one read operation, so they come from the same
+	/// version of the object. The C API has no action reading a single
+	/// xattr, so all of them are fetched and the others dropped.
+	///
+	/// # Returns
+	///
+	/// * `Ok(HashMap<name, value>)` on success, without the attributes the
+	///   object doesn't have
+	/// * `Err(RadosError)` on failure
+	pub fn getxattrs_by_name(&self, oid: &str, names: &[&str]) -> Result<HashMap<String, Vec<u8>>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let op = ReadOp::new();
+		let mut iter: rados_xattrs_iter_t = ptr::null();
+		let mut rval: c_int = 0;
+		unsafe {
+			rados_read_op_getxattrs(op.handle, &mut iter, &mut rval);
+		}
+		let err = unsafe { rados_read_op_operate(op.handle, self.handle, oid_cs.as_ptr(), 0) };
+		let err = if err < 0 { err } else { rval };
+		if err < 0 {
+			if !iter.is_null() {
+				unsafe { rados_getxattrs_end(iter) };
+			}
+			return Err(RadosError::from_return_code(err));
+		}
+		collect_xattrs(iter, |name| names.contains(&name))
+	}
+
+	/// Copy the first `len` bytes of the `src` object over the `dst` object
+	///
+	/// Uses the server side `rados_clone_range()` when librados has it and
+	/// both objects are in the same placement group, and copies the data
+	/// through the client otherwise.
+	fn copy_data(&self, src: &str, dst: &str, len: u64) -> Result<(), RadosError> {
+		if self.clone_range(src, dst, len).is_ok() {
+			return Ok(());
+		}
+		let mut buf = vec![0u8; COPY_CHUNK_SIZE];
+		let mut offset: u64 = 0;
+		RadosIo::write_full(self, dst, &[])?;
+		while offset < len {
+			let n = self.read_at(src, &mut buf, offset)?;
+			if n == 0 {
+				break;
+			}
+			self.write_at(dst, &buf[..n], offset)?;
+			offset += n as u64;
+		}
+		Ok(())
+	}
+
+	#[cfg(not(feature = "nautilus"))]
+	fn clone_range(&self, src: &str, dst: &str, len: u64) -> Result<(), RadosError> {
+		let src_cs = CString::new(src)?;
+		let dst_cs = CString::new(dst)?;
+		// Start from an empty destination, as clone_range() only overwrites
+		RadosIo::write_full(self, dst, &[])?;
+		handle_errors!(rados_clone_range(self.handle, dst_cs.as_ptr(), 0, src_cs.as_ptr(), 0, len as size_t));
+		Ok(())
+	}
+
+	#[cfg(feature = "nautilus")]
+	fn clone_range(&self, _src: &str, _dst: &str, _len: u64) -> Result<(), RadosError> {
+		Err(RadosError::Other(libc::EOPNOTSUPP))
+	}
+
+	/// Rename the `src` object to `dst`, within this pool.
 	///
-	/// If path is `NULL`, the default locations are searched, and the first
-	/// found is used. The locations are:
+	/// RADOS has no native rename, so this copies the data (server side
+	/// when possible) and the extended attributes over to `dst`, and then
+	/// removes `src`. It is **not** atomic: other clients can observe `dst`
+	/// before `src` is gone. If anything fails before `src` is removed,
+	/// `src` is left untouched and the partially written `dst` is removed
+	/// again. omap entries are not copied, see `clone_object()`.
 	///
-	/// * `$CEPH_CONF` (environment variable)
-	/// * `/etc/ceph/ceph.conf`
-	/// * `~/.ceph/config`
-	/// * `ceph.conf` (in the current working directory)
+	/// # Returns
 	///
-	/// # Prerequisites
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn rename(&self, src: &str, dst: &str) -> Result<(), RadosError> {
+		let (size, _) = self.stat(src)?;
+		let attrs = self.getxattrs(src)?;
+		let copied = self.copy_data(src, dst, size).and_then(|_| {
+			let attrs: Vec<(&str, &[u8])> = attrs.iter().map(|(k, v)| (k.as_str(), v.as_slice())).collect();
+			self.setxattrs(dst, &attrs)
+		});
+		if let Err(e) = copied {
+			let _ = self.remove(dst);
+			return Err(e);
+		}
+		self.remove(src)
+	}
+
+	/// Copy the `src` object to `dst`, within this pool, with its data,
+	/// extended attributes and omap entries, e.g. to back up or fork it.
 	///
-	/// `rados_connect()` has not been called on the cluster handle
+	/// The data is copied server side when possible, as in `rename()`; the
+	/// attributes and omap entries are then written in a single write
+	/// operation, replacing the omap `dst` had. Extended attributes of an
+	/// existing `dst` that `src` doesn't have are kept. The copy is not
+	/// atomic, and `src` may change while it's copied; if anything fails,
+	/// `dst` is removed.
 	///
-	/// # Parameters
+	/// # Returns
 	///
-	/// * `cluster` cluster handle to configure
-	/// * `path` path to a Ceph configuration file
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn clone_object(&self, src: &str, dst: &str) -> Result<(), RadosError> {
+		let (size, _) = self.stat(src)?;
+		let attrs = self.getxattrs(src)?;
+		let omap = self.export_omap(src)?;
+		let cloned = self.copy_data(src, dst, size).and_then(|_| {
+			let mut op = WriteOp::new();
+			for (name, value) in &attrs {
+				op.setxattr(name, value);
+			}
+			op.omap_clear().omap_set(&omap);
+			self.operate(dst, &op)
+		});
+		if let Err(e) = cloned {
+			let _ = self.remove(dst);
+			return Err(e);
+		}
+		Ok(())
+	}
+
+	/// Write every object in the pool (in the namespace of this context)
+	/// to `writer`, with its xattrs and omap, e.g. to back up the pool, or
+	/// move it to another cluster with `import()`.
+	///
+	/// The stream starts with the 8 bytes `ceph-rs1`, followed by a record
+	/// per object, and ends with the last record. Each record is made of:
+	///
+	/// * the object name, as its length in bytes (a `u32`) and then the
+	///   name itself
+	/// * its xattrs: their count (a `u32`), then for each one its name and
+	///   its value, each as a `u32` length and the bytes
+	/// * its omap entries, laid out like the xattrs
+	/// * its data, as its length (a `u64`) and then the bytes
+	///
+	/// All integers are little-endian. Each object's data and xattrs are
+	/// read together, with `fetch()`, and its omap separately; the pool as
+	/// a whole isn't a consistent snapshot if it's written to meanwhile.
+	/// Objects removed during the export are skipped. Objects are read
+	/// whole into memory, one at a time.
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` on success
-	/// * `Err(message: &str)` on failure
-	pub fn conf_read_file<S>(&self, config_filename: S) -> Result<(), &str>
-		where S: StrStringOrNone
+	/// * `Ok(bytes_written)` on success
+	/// * `Err(RadosError)` on failure, including writing to `writer`
+	pub fn export<W>(&self, mut writer: W) -> Result<u64, RadosError>
+		where W: Write
 	{
-	    let config_filename_ptr = match config_filename.unwrap() {
-	    	None => ptr::null(),
-	    	Some(cs) => cs.as_ptr()
-	    };
-		handle_errors!(rados_conf_read_file(self.handle, config_filename_ptr));
+		writer.write_all(EXPORT_MAGIC)?;
+		let mut written = EXPORT_MAGIC.len() as u64;
+		for oid in self.list_objects()? {
+			let oid = oid?;
+			let snapshot = match self.fetch(&oid) {
+				Ok(snapshot) => snapshot,
+				Err(RadosError::ObjectNotFound) => continue,
+				Err(e) => return Err(e),
+			};
+			let omap = match self.export_omap(&oid) {
+				Ok(omap) => omap,
+				Err(RadosError::ObjectNotFound) => continue,
+				// Erasure coded pools have no omap
+				Err(RadosError::Other(libc::EOPNOTSUPP)) => Vec::new(),
+				Err(e) => return Err(e),
+			};
+			written += write_field(&mut writer, oid.as_bytes(), false)?;
+			written += write_entries(&mut writer, snapshot.xattrs.iter().map(|(k, v)| (k.as_str(), v.as_slice())))?;
+			written += write_entries(&mut writer, omap.iter().map(|&(ref k, ref v)| (k.as_str(), v.as_slice())))?;
+			written += write_field(&mut writer, &snapshot.data, true)?;
+		}
+		writer.flush()?;
+		Ok(written)
+	}
+
+	/// Write the objects of a stream made by `export()` to the pool (in the
+	/// namespace of this context).
+	///
+	/// Each object is written in a single write operation, replacing its
+	/// data and setting its xattrs and omap entries, so it's either
+	/// imported whole or not at all; xattrs and omap entries an existing
+	/// object has besides those in the stream are kept, so this is best
+	/// imported into an empty pool. An object's data must fit in a single
+	/// write (see `max_write_size()`).
+	///
+	/// # Returns
+	///
+	/// * `Ok(objects)` with the number of objects imported, on success
+	/// * `Err(RadosError)` on failure, including reading from `reader`; an
+	///   `io::ErrorKind::InvalidData` error if the stream isn't an
+	///   export, and `io::ErrorKind::UnexpectedEof` if it's truncated.
+	///   The objects before the failing one are imported.
+	pub fn import<R>(&self, mut reader: R) -> Result<u64, RadosError>
+		where R: Read
+	{
+		read_export_magic(&mut reader)?;
+		let mut imported = 0;
+		loop {
+			let name_len = match read_length(&mut reader, false)? {
+				Some(len) => len,
+				None => return Ok(imported),
+			};
+			let mut name = Vec::new();
+			(&mut reader).take(name_len).read_to_end(&mut name)?;
+			if (name.len() as u64) < name_len {
+				return Err(truncated_export());
+			}
+			let oid = String::from_utf8(name).map_err(|e| RadosError::Io(io::ErrorKind::InvalidData, e.to_string()))?;
+			let xattrs = read_entries(&mut reader)?;
+			let omap = read_entries(&mut reader)?;
+			let data = read_field(&mut reader, true)?;
+			let mut op = WriteOp::new();
+			op.write_full(&data);
+			for &(ref name, ref value) in &xattrs {
+				op.setxattr(name, value);
+			}
+			if !omap.is_empty() {
+				op.omap_set(&omap);
+			}
+			self.operate(&oid, &op)?;
+			imported += 1;
+		}
+	}
+
+	/// Atomically add `delta` to the counter stored in the `key` xattr of
+	/// the `oid` object, creating the object and the counter (starting
+	/// from `0`) if needed.
+	///
+	/// The counter is read, then written back in a write operation that
+	/// only applies if the xattr still holds the value that was read. If
+	/// another client changed it in between, the whole cycle is retried.
+	/// Comparing the xattr itself rather than asserting the object version
+	/// keeps concurrent increments through the same `IoCtx` safe, since the
+	/// last seen version is shared by the whole context.
+	///
+	/// The counter is stored as a decimal string.
+	///
+	/// # Returns
+	///
+	/// * `Ok(value)` the counter value after the increment
+	/// * `Err(RadosError)` on failure, `EINVAL` if the xattr isn't a number,
+	///   and `ERANGE` if the increment would overflow an `i64`
+	pub fn incr(&self, oid: &str, key: &str, delta: i64) -> Result<i64, RadosError> {
+		loop {
+			let current = match self.xattr_value(oid, key) {
+				Ok(value) => value.unwrap_or_else(Vec::new),
+				Err(RadosError::ObjectNotFound) => Vec::new(),
+				Err(e) => return Err(e),
+			};
+			let value = if current.is_empty() {
+				0
+			} else {
+				match String::from_utf8_lossy(&current).parse::<i64>() {
+					Ok(value) => value,
+					Err(_) => return Err(RadosError::Other(libc::EINVAL)),
+				}
+			};
+			let new_value = match value.checked_add(delta) {
+				Some(new_value) => new_value,
+				None => return Err(RadosError::Other(libc::ERANGE)),
+			};
+			let mut op = WriteOp::new();
+			op.create(false)
+				.assert_xattr_eq(key, &current)
+				.setxattr(key, new_value.to_string().as_bytes());
+			match self.operate(oid, &op) {
+				Ok(()) => return Ok(new_value),
+				Err(RadosError::Other(libc::ECANCELED)) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Get the whole value of the `name` xattr of the `oid` object, or
+	/// `None` if the object has no such xattr.
+	fn xattr_value(&self, oid: &str, name: &str) -> Result<Option<Vec<u8>>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let name_cs = CString::new(name)?;
+		let filled = fill_buffer(256, |buf| unsafe {
+			rados_getxattr(self.handle, oid_cs.as_ptr(), name_cs.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len() as size_t)
+		});
+		match filled {
+			Ok((mut buf, len)) => {
+				buf.truncate(len);
+				Ok(Some(buf))
+			},
+			Err(err) if err == -libc::ENODATA => Ok(None),
+			Err(err) => Err(RadosError::from_return_code(err)),
+		}
+	}
+
+	/// The raw tags xattr of the `oid` object, and the tags it holds
+	fn read_tags(&self, oid: &str) -> Result<(Vec<u8>, Vec<String>), RadosError> {
+		let raw = self.xattr_value(oid, TAGS_XATTR)?.unwrap_or_else(Vec::new);
+		if raw.is_empty() {
+			return Ok((raw, Vec::new()));
+		}
+		let tags = serde_json::from_slice(&raw).map_err(|e| RadosError::Serialization(e.to_string()))?;
+		Ok((raw, tags))
+	}
+
+	/// Rewrite the tags of the `oid` object with `change`, retrying if
+	/// another client changes them concurrently, like `incr()`
+	fn update_tags<F>(&self, oid: &str, mut change: F) -> Result<(), RadosError>
+		where F: FnMut(&mut Vec<String>) -> bool
+	{
+		loop {
+			let (raw, mut tags) = self.read_tags(oid)?;
+			if !change(&mut tags) {
+				return Ok(());
+			}
+			let mut op = WriteOp::new();
+			op.assert_exists()
+				.assert_xattr_eq(TAGS_XATTR, &raw)
+				.setxattr(TAGS_XATTR, json!(tags).to_string().as_bytes());
+			match self.operate(oid, &op) {
+				Ok(()) => return Ok(()),
+				Err(RadosError::Other(libc::ECANCELED)) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Tag the `oid` object with `tag`, if it isn't already.
+	///
+	/// An object's tags are kept together in its `ceph-rs.tags` xattr, as
+	/// a JSON array; concurrent changes to the tags are safe.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure, `RadosError::ObjectNotFound` if the
+	///   object doesn't exist
+	pub fn set_tag(&self, oid: &str, tag: &str) -> Result<(), RadosError> {
+		self.update_tags(oid, |tags| {
+			if tags.iter().any(|t| t == tag) {
+				return false;
+			}
+			tags.push(tag.to_string());
+			true
+		})
+	}
+
+	/// Remove the `tag` tag from the `oid` object, if it has it.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` on success
+	/// * `Err(RadosError)` on failure
+	pub fn remove_tag(&self, oid: &str, tag: &str) -> Result<(), RadosError> {
+		self.update_tags(oid, |tags| {
+			let len = tags.len();
+			tags.retain(|t| t != tag);
+			tags.len() != len
+		})
+	}
+
+	/// Get the tags of the `oid` object, in the order they were set.
+	///
+	/// # Returns
+	///
+	/// * `Ok(tags)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn get_tags(&self, oid: &str) -> Result<Vec<String>, RadosError> {
+		self.read_tags(oid).map(|(_, tags)| tags)
+	}
+
+	/// Find the objects of the pool tagged with `tag`.
+	///
+	/// There's no index of tags: this lists every object of the pool and
+	/// reads its tags, so it takes one round trip per object and is only
+	/// suited to small pools or occasional maintenance. Objects removed
+	/// during the scan are skipped.
+	///
+	/// # Returns
+	///
+	/// * `Ok(oids)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn find_by_tag(&self, tag: &str) -> Result<Vec<String>, RadosError> {
+		let mut found = Vec::new();
+		for oid in self.list_objects()? {
+			let oid = oid?;
+			match self.get_tags(&oid) {
+				Ok(ref tags) if tags.iter().any(|t| t == tag) => found.push(oid),
+				Ok(_) | Err(RadosError::ObjectNotFound) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(found)
+	}
+
+	/// Call the `method` method of the `cls` object class on the `oid`
+	/// object, passing it `input`.
+	///
+	/// The size of the output isn't known in advance: it's first read into
+	/// a 4 KiB buffer, and whenever librados reports it doesn't fit
+	/// (`-ERANGE`) the call is repeated with a buffer twice as large, up to
+	/// 64 MiB. Note that this runs the method again, so methods with side
+	/// effects and large outputs may be applied more than once.
+	///
+	/// # Returns
+	///
+	/// * `Ok(output)` on success
+	/// * `Err(RadosError)` on failure, `ERANGE` if the output is larger
+	///   than 64 MiB
+	pub fn exec(&self, oid: &str, cls: &str, method: &str, input: &[u8]) -> Result<Vec<u8>, RadosError> {
+		let oid_cs = CString::new(oid)?;
+		let cls_cs = CString::new(cls)?;
+		let method_cs = CString::new(method)?;
+		let filled = fill_buffer(EXEC_INITIAL_BUF_SIZE, |buf| unsafe {
+			rados_exec(self.handle, oid_cs.as_ptr(), cls_cs.as_ptr(), method_cs.as_ptr(),
+				input.as_ptr() as *const c_char, input.len() as size_t,
+				buf.as_mut_ptr() as *mut c_char, buf.len() as size_t)
+		});
+		match filled {
+			Ok((mut buf, len)) => {
+				buf.truncate(len);
+				Ok(buf)
+			},
+			Err(err) => Err(RadosError::from_return_code(err)),
+		}
+	}
+
+	pub fn remove(&self, oid: &str) -> Result<(), RadosError> {
+		let oid = CString::new(oid)?;
+		let oid_ptr = oid.as_ptr();
+		handle_errors!(rados_remove(self.handle, oid_ptr));
 		return Ok(());
 	}
+}
 
-	/// Configure the cluster handle with command line arguments
+/// Iterates over the names of the objects in a pool, see
+/// `IoCtx::list_objects()`.
+pub struct ObjectIterator<'a> {
+	ctx: rados_list_ctx_t,
+	/// Hash position to stop at, for sharded listings; `Some(0)` once the
+	/// listing is over, so it stays over
+	end: Option<u32>,
+	/// Hash position of the last object returned, and how many objects of
+	/// that placement group have been returned, for `cursor()`
+	position: u32,
+	in_pg: u64,
+	/// An object already read from librados, to return first
+	pending: Option<String>,
+	ioctx: PhantomData<&'a IoCtx>,
+}
+
+impl<'a> Iterator for ObjectIterator<'a> {
+	type Item = Result<String, RadosError>;
+
+	fn next(&mut self) -> Option<Result<String, RadosError>> {
+		if let Some(name) = self.pending.take() {
+			return Some(Ok(name));
+		}
+		if self.end == Some(0) {
+			return None;
+		}
+		let mut entry: *const c_char = ptr::null();
+		let err = unsafe { list_next(self.ctx, &mut entry) };
+		if err == -libc::ENOENT {
+			self.end = Some(0);
+			return None;
+		}
+		if err < 0 {
+			return Some(Err(RadosError::from_return_code(err)));
+		}
+		let position = unsafe { list_position(self.ctx) };
+		if let Some(end) = self.end {
+			if position >= end {
+				self.end = Some(0);
+				return None;
+			}
+		}
+		if position != self.position {
+			self.position = position;
+			self.in_pg = 0;
+		}
+		self.in_pg += 1;
+		let name = unsafe { CStr::from_ptr(entry) };
+		Some(Ok(name.to_string_lossy().into_owned()))
+	}
+}
+
+impl<'a> ObjectIterator<'a> {
+	/// An opaque token recording how far the listing got, to persist and
+	/// pass to `IoCtx::list_objects_from()` later to list the remaining
+	/// objects.
 	///
-	/// argv can contain any common Ceph command line option, including any
-	/// configuration parameter prefixed by '--' and replacing spaces with
-	/// dashes or underscores. For example, the following options are equivalent:
+	/// librados' own listing cursors can't be serialized through its C API,
+	/// so the token holds the hash position of the current placement group
+	/// and how many of its objects were already returned. Resuming skips
+	/// that many objects of the placement group, so objects created or
+	/// removed in it meanwhile may be missed or returned twice; objects of
+	/// other placement groups are unaffected.
+	pub fn cursor(&self) -> Vec<u8> {
+		encode_cursor(self.position, self.in_pg)
+	}
+}
+
+/// The token of `ObjectIterator::cursor()`: the hash position of the
+/// placement group, then how many of its objects were returned, both
+/// little-endian
+fn encode_cursor(position: u32, in_pg: u64) -> Vec<u8> {
+	let mut cursor = Vec::with_capacity(12);
+	for i in 0..4 {
+		cursor.push((position >> (8 * i)) as u8);
+	}
+	for i in 0..8 {
+		cursor.push((in_pg >> (8 * i)) as u8);
+	}
+	cursor
+}
+
+/// Get back the position and count `encode_cursor()` encoded, or `EINVAL`
+/// if `cursor` isn't 12 bytes long
+fn decode_cursor(cursor: &[u8]) -> Result<(u32, u64), RadosError> {
+	if cursor.len() != 12 {
+		return Err(RadosError::Other(libc::EINVAL));
+	}
+	let mut position: u32 = 0;
+	for (i, b) in cursor[..4].iter().enumerate() {
+		position |= (*b as u32) << (8 * i);
+	}
+	let mut in_pg: u64 = 0;
+	for (i, b) in cursor[4..].iter().enumerate() {
+		in_pg |= (*b as u64) << (8 * i);
+	}
+	Ok((position, in_pg))
+}
+
+impl<'a> Drop for ObjectIterator<'a> {
+	fn drop(&mut self) {
+		unsafe {
+			list_close(self.ctx);
+		}
+	}
+}
+
+/// Iterates over the names and sizes of the objects in a pool, see
+/// `IoCtx::list_objects_with_size()`.
+pub struct ObjectSizeIterator<'a> {
+	ioctx: &'a IoCtx,
+	/// The names left to stat, `None` once they've all been started
+	names: Option<ObjectIterator<'a>>,
+	/// Why the listing couldn't be started, to return first
+	error: Option<RadosError>,
+	/// The stats in flight, in listing order
+	pending: VecDeque<PendingStat>,
+}
+
+/// An asynchronous stat of an object, see `ObjectSizeIterator`
+struct PendingStat {
+	name: String,
+	completion: rados_completion_t,
+	/// Where librados stores the results, boxed so they stay put until the
+	/// stat completes
+	size: Box<u64>,
+	mtime: Box<libc::time_t>,
+	started: bool,
+}
+
+impl<'a> ObjectSizeIterator<'a> {
+	/// Start a stat of the `name` object
+	fn start(&self, name: String) -> Result<PendingStat, RadosError> {
+		let name_cs = CString::new(name.as_str())?;
+		let mut stat = PendingStat { name: name, completion: ptr::null(), size: Box::new(0), mtime: Box::new(0),
+			started: false };
+		handle_errors!(rados_aio_create_completion(ptr::null_mut(), None, None, &mut stat.completion));
+		handle_errors!(rados_aio_stat(self.ioctx.handle, name_cs.as_ptr(), stat.completion,
+			&mut *stat.size, &mut *stat.mtime));
+		stat.started = true;
+		Ok(stat)
+	}
+}
+
+impl<'a> Iterator for ObjectSizeIterator<'a> {
+	type Item = Result<(String, u64), RadosError>;
+
+	fn next(&mut self) -> Option<Result<(String, u64), RadosError>> {
+		if let Some(e) = self.error.take() {
+			return Some(Err(e));
+		}
+		loop {
+			while self.pending.len() < STAT_BATCH_SIZE {
+				let name = match self.names.as_mut().and_then(|names| names.next()) {
+					Some(Ok(name)) => name,
+					Some(Err(e)) => {
+						self.names = None;
+						return Some(Err(e));
+					},
+					None => {
+						self.names = None;
+						break;
+					},
+				};
+				match self.start(name) {
+					Ok(stat) => self.pending.push_back(stat),
+					Err(e) => return Some(Err(e)),
+				}
+			}
+			let mut stat = match self.pending.pop_front() {
+				Some(stat) => stat,
+				None => return None,
+			};
+			let err = unsafe {
+				rados_aio_wait_for_complete(stat.completion);
+				rados_aio_get_return_value(stat.completion)
+			};
+			if err == -libc::ENOENT {
+				// Removed since it was listed
+				continue;
+			}
+			if err < 0 {
+				return Some(Err(RadosError::from_return_code(err)));
+			}
+			return Some(Ok((mem::replace(&mut stat.name, String::new()), *stat.size)));
+		}
+	}
+}
+
+impl Drop for PendingStat {
+	fn drop(&mut self) {
+		release_completion(self.completion, self.started);
+	}
+}
+
+/// A single object in a pool, see `IoCtx::object()`.
+pub struct RadosObject<'a> {
+	ioctx: &'a IoCtx,
+	oid: String,
+}
+
+impl<'a> RadosObject<'a> {
+	/// The name of this object
+	pub fn oid(&self) -> &str {
+		&self.oid
+	}
+
+	/// Read the whole object
+	pub fn read(&self) -> Result<Vec<u8>, RadosError> {
+		self.ioctx.read_full(&self.oid)
+	}
+
+	/// Replace the contents of the object with `data`
+	pub fn write(&self, data: &[u8]) -> Result<(), RadosError> {
+		RadosIo::write_full(self.ioctx, &self.oid, data)
+	}
+
+	/// Append `data` to the object, creating it if needed
+	pub fn append(&self, data: &[u8]) -> Result<(), RadosError> {
+		self.ioctx.append_create(&self.oid, data)
+	}
+
+	/// Get the size and modification time of the object
+	pub fn stat(&self) -> Result<(u64, SystemTime), RadosError> {
+		self.ioctx.stat(&self.oid)
+	}
+
+	/// Get the size, modification time and version of the object
+	pub fn meta(&self) -> Result<ObjectMeta, RadosError> {
+		self.ioctx.object_meta(&self.oid)
+	}
+
+	/// Read the data, size, modification time, version and xattrs of the
+	/// object at once
+	pub fn fetch(&self) -> Result<ObjectSnapshot, RadosError> {
+		self.ioctx.fetch(&self.oid)
+	}
+
+	/// Set the `name` extended attribute of the object
+	pub fn setxattr(&self, name: &str, value: &str) -> Result<(), RadosError> {
+		self.ioctx.setxattr(self.oid.as_str(), name, value)
+	}
+
+	/// Get the `name` extended attribute of the object, expected to be
+	/// about `len` bytes long, failing with `RadosError::AttrNotFound` if
+	/// it has no such attribute
+	pub fn getxattr(&self, name: &str, len: usize) -> Result<String, RadosError> {
+		self.ioctx.getxattr(self.oid.as_str(), name, len)
+	}
+
+	/// Delete the object
+	pub fn remove(&self) -> Result<(), RadosError> {
+		self.ioctx.remove(&self.oid)
+	}
+}
+
+/// What a `ResilientWatch` shares with the librados callbacks
+struct WatchState {
+	ioctx: rados_ioctx_t,
+	oid: CString,
+	on_notify: Mutex<Box<dyn FnMut(&[u8]) + Send>>,
+	/// Set by the error callback when librados reports the watch broken
+	lost: AtomicBool,
+}
+
+/// What `IoCtx::aio_with_timeout()` waits on
+struct AioWaiter {
+	complete: Mutex<bool>,
+	cond: Condvar,
+}
+
+/// The completion callback of `IoCtx::aio_with_timeout()`, `arg` is a
+/// reference to its `AioWaiter` from `Arc::into_raw()`
+extern "C" fn aio_waiter_callback(_c: rados_completion_t, arg: *mut c_void) {
+	let waiter = unsafe { Arc::from_raw(arg as *const AioWaiter) };
+	*waiter.complete.lock().unwrap_or_else(PoisonError::into_inner) = true;
+	waiter.cond.notify_all();
+}
+
+extern "C" fn watch_notify_callback(arg: *mut c_void, notify_id: u64, cookie: u64,
+	_notifier_id: u64, data: *mut c_void, data_len: size_t)
+{
+	let state = unsafe { &*(arg as *const WatchState) };
+	let payload = if data.is_null() {
+		&[][..]
+	} else {
+		unsafe { ::std::slice::from_raw_parts(data as *const u8, data_len as usize) }
+	};
+	if let Ok(mut on_notify) = state.on_notify.lock() {
+		(&mut *on_notify)(payload);
+	}
+	unsafe {
+		rados_notify_ack(state.ioctx, state.oid.as_ptr(), notify_id, cookie, ptr::null(), 0);
+	}
+}
+
+extern "C" fn watch_error_callback(arg: *mut c_void, _cookie: u64, _err: c_int) {
+	let state = unsafe { &*(arg as *const WatchState) };
+	state.lost.store(true, Ordering::SeqCst);
+}
+
+/// A watch on an object that re-establishes itself when it's lost.
+///
+/// Watches can be lost, for example when the OSD holding the object fails
+/// over and the client doesn't reconnect in time. Call `check()`
+/// periodically (e.g. from a timer, every few seconds): when the watch is
+/// found to be broken it's removed and registered again, and the reconnect
+/// callback is told about the error that broke it.
+///
+/// Notifications are acknowledged automatically once `on_notify` returns.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags, ResilientWatch};
+/// # use std::thread;
+/// # use std::time::Duration;
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// # let ioctx = cluster.create_ioctx("data").unwrap();
+/// let mut watch = ResilientWatch::new(&ioctx, "hw",
+/// 	|data| println!("notified: {:?}", data),
+/// 	|err| println!("watch re-established after: {}", err)).unwrap();
+/// loop {
+/// 	watch.check().unwrap();
+/// 	thread::sleep(Duration::from_secs(5));
+/// }
+/// ```
+pub struct ResilientWatch<'a> {
+	ioctx: &'a IoCtx,
+	cookie: u64,
+	state: Box<WatchState>,
+	on_reconnect: Box<dyn FnMut(&RadosError) + 'a>,
+}
+
+impl<'a> ResilientWatch<'a> {
+	/// Watch the `oid` object, calling `on_notify` with the payload of every
+	/// notification (from a librados thread), and `on_reconnect` from
+	/// `check()` whenever the watch had to be re-established.
 	///
-	/// * `--mon-host 10.0.0.1:6789`
-	/// * `--mon_host 10.0.0.1:6789`
-	/// * `-m 10.0.0.1:6789`
+	/// # Returns
 	///
-	/// # Prerequisites
+	/// * `Ok(ResilientWatch)` on success
+	/// * `Err(RadosError)` if the watch couldn't be registered
+	pub fn new<N, R>(ioctx: &'a IoCtx, oid: &str, on_notify: N, on_reconnect: R) -> Result<ResilientWatch<'a>, RadosError>
+		where N: FnMut(&[u8]) + Send + 'static, R: FnMut(&RadosError) + 'a
+	{
+		let mut watch = ResilientWatch {
+			ioctx: ioctx,
+			cookie: 0,
+			state: Box::new(WatchState {
+				ioctx: ioctx.handle,
+				oid: CString::new(oid)?,
+				on_notify: Mutex::new(Box::new(on_notify)),
+				lost: AtomicBool::new(false),
+			}),
+			on_reconnect: Box::new(on_reconnect),
+		};
+		watch.cookie = watch.register()?;
+		Ok(watch)
+	}
+
+	fn register(&self) -> Result<u64, RadosError> {
+		let mut cookie: u64 = 0;
+		let arg = &*self.state as *const WatchState as *mut c_void;
+		handle_errors!(rados_watch2(self.ioctx.handle, self.state.oid.as_ptr(), &mut cookie,
+			Some(watch_notify_callback), Some(watch_error_callback), arg));
+		self.state.lost.store(false, Ordering::SeqCst);
+		Ok(cookie)
+	}
+
+	/// Check that the watch is still registered, and if it isn't, replace
+	/// it with a new one and call the reconnect callback.
 	///
-	/// `rados_connect()` has not been called on the cluster handle
+	/// # Returns
 	///
-	/// # Parameters
+	/// * `Ok(false)` if the watch was fine
+	/// * `Ok(true)` if it was lost and has been re-established
+	/// * `Err(RadosError)` if it was lost and couldn't be re-established;
+	///   `check()` will try again next time
+	pub fn check(&mut self) -> Result<bool, RadosError> {
+		let err = unsafe { rados_watch_check(self.ioctx.handle, self.cookie) };
+		let lost = self.state.lost.load(Ordering::SeqCst);
+		if err >= 0 && !lost {
+			return Ok(false);
+		}
+		let cause = if err < 0 { RadosError::from_return_code(err) } else { RadosError::NotConnected };
+		unsafe {
+			rados_unwatch2(self.ioctx.handle, self.cookie);
+		}
+		self.cookie = self.register()?;
+		(self.on_reconnect)(&cause);
+		Ok(true)
+	}
+
+	/// The handle librados identifies the current watch by, as passed to
+	/// `rados_unwatch2()`; a new one is used each time `check()`
+	/// re-establishes the watch.
+	pub fn cookie(&self) -> u64 {
+		self.cookie
+	}
+}
+
+impl<'a> Drop for ResilientWatch<'a> {
+	fn drop(&mut self) {
+		unsafe {
+			rados_unwatch2(self.ioctx.handle, self.cookie);
+			// Make sure no callback still uses the state about to be freed
+			rados_watch_flush(rados_ioctx_get_cluster(self.ioctx.handle));
+		}
+	}
+}
+
+/// An asynchronous write in progress, see `IoCtx::aio_write_safe()`.
+///
+/// The data being written is owned by the completion, so it stays valid
+/// until the write is done. Dropping the completion blocks until the write
+/// has completed.
+pub struct WriteCompletion<'a> {
+	completion: rados_completion_t,
+	/// The io context the write was issued on, to cancel it
+	io: rados_ioctx_t,
+	data: Vec<u8>,
+	/// Whether librados accepted the write, so there's something to wait for
+	started: bool,
+	ioctx: PhantomData<&'a IoCtx>,
+}
+
+impl<'a> WriteCompletion<'a> {
+	/// Take ownership of `data`, and issue the write on `io` with `issue`,
+	/// given the new completion and the data
+	fn start<F>(io: rados_ioctx_t, data: Vec<u8>, issue: F) -> Result<WriteCompletion<'a>, RadosError>
+		where F: FnOnce(rados_completion_t, &[u8]) -> c_int
+	{
+		let mut completion = WriteCompletion { completion: ptr::null(), io: io, data: data, started: false, ioctx: PhantomData };
+		handle_errors!(rados_aio_create_completion(ptr::null_mut(), None, None, &mut completion.completion));
+		let err = issue(completion.completion, &completion.data);
+		if err < 0 {
+			return Err(RadosError::from_return_code(err));
+		}
+		completion.started = true;
+		Ok(completion)
+	}
+
+	/// Try to cancel the write, e.g. when the client it was made for went
+	/// away. A write the OSDs already applied can't be canceled.
 	///
-	/// * `cluster` cluster handle to configure
-	/// * `args` Vec of String arguments, e.g. `env::args().collect()`
+	/// When this returns, librados is done with the write either way, and
+	/// a canceled write makes `wait_complete()` fail with `ECANCELED`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(true)` if the write was canceled
+	/// * `Ok(false)` if it had already completed
+	/// * `Err(RadosError)` on failure
+	pub fn cancel(&self) -> Result<bool, RadosError> {
+		cancel_completion(self.io, self.completion)
+	}
+
+	/// Whether the write has been acknowledged by all replicas
+	pub fn is_complete(&self) -> bool {
+		unsafe { rados_aio_is_complete(self.completion) != 0 }
+	}
+
+	/// Whether the write is on stable storage on all replicas
+	pub fn is_safe(&self) -> bool {
+		unsafe { rados_aio_is_safe(self.completion) != 0 }
+	}
+
+	/// Block until the write is in memory on all replicas.
+	///
+	/// # Returns
+	///
+	/// * `Ok(())` if the write succeeded
+	/// * `Err(RadosError)` if it failed
+	pub fn wait_complete(&self) -> Result<(), RadosError> {
+		let ret = unsafe {
+			rados_aio_wait_for_complete(self.completion);
+			rados_aio_get_return_value(self.completion)
+		};
+		if ret < 0 {
+			return Err(RadosError::from_return_code(ret));
+		}
+		Ok(())
+	}
+
+	/// Block until the write is on stable storage on all replicas.
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` on success
-	/// * `Err(message: &str)` on failure
- 	pub fn conf_parse_argv(&self, args: &Vec<String>) -> Result<(), &str> {
-		let argc = args.len() as i32;
-		let args_cs : Vec<CString> = args.iter().map(|a| CString::new(a.as_str()).unwrap()).collect();
-		let argv : Vec<*const c_char> = args_cs.iter().map(|cs| cs.as_ptr()).collect();
-		handle_errors!(rados_conf_parse_argv(self.handle, argc, argv.as_slice().as_ptr()));
-		return Ok(());
+	/// * `Ok(())` if the write succeeded and is durable
+	/// * `Err(RadosError)` if it failed
+	pub fn wait_safe(&self) -> Result<(), RadosError> {
+		let ret = unsafe {
+			rados_aio_wait_for_safe(self.completion);
+			rados_aio_get_return_value(self.completion)
+		};
+		if ret < 0 {
+			return Err(RadosError::from_return_code(ret));
+		}
+		Ok(())
 	}
+}
 
-	/// Get the fsid of the cluster as a hexadecimal string.
+impl<'a> Drop for WriteCompletion<'a> {
+	fn drop(&mut self) {
+		release_completion(self.completion, self.started);
+	}
+}
+
+/// An asynchronous read in progress, see `IoCtx::aio_read()`.
+///
+/// The buffer being read into is owned by the completion, so it stays
+/// valid until the read is done. Dropping the completion blocks until the
+/// read has completed.
+pub struct ReadCompletion<'a> {
+	completion: rados_completion_t,
+	/// The io context the read was issued on, to cancel it
+	io: rados_ioctx_t,
+	buf: Vec<u8>,
+	/// Whether librados accepted the read, so there's something to wait for
+	started: bool,
+	ioctx: PhantomData<&'a IoCtx>,
+}
+
+impl<'a> ReadCompletion<'a> {
+	/// Whether the read has finished
+	pub fn is_complete(&self) -> bool {
+		unsafe { rados_aio_is_complete(self.completion) != 0 }
+	}
+
+	/// Try to cancel the read, as `WriteCompletion::cancel()` does for
+	/// writes.
 	///
-	/// The fsid is a unique id of an entire Ceph cluster.
+	/// # Returns
+	///
+	/// * `Ok(true)` if the read was canceled, in which case `wait()` fails
+	///   with `ECANCELED`
+	/// * `Ok(false)` if it had already completed
+	/// * `Err(RadosError)` on failure
+	pub fn cancel(&self) -> Result<bool, RadosError> {
+		cancel_completion(self.io, self.completion)
+	}
+
+	/// Block until the read has finished.
 	///
 	/// # Returns
 	///
-	/// * `Ok(fsid: &str)` on success
-	/// * `Err(message: &str)` on failure
-	pub fn fsid(&self) -> Result<&str, &str> {
-		// magic number
-		let buf_size = 37;
-		let mut buf = zeroed_c_char_buf!(buf_size);
-		let buf_ptr = buf.as_mut_ptr();
-		handle_errors!(rados_cluster_fsid(self.handle, buf_ptr as *mut c_char, buf_size as size_t));
- 		return Ok(unsafe {
-	 		CStr::from_ptr(buf_ptr).to_str().unwrap()
- 		});
+	/// * `Ok(data)` with the bytes read on success
+	/// * `Err(RadosError)` if the read failed
+	pub fn wait(mut self) -> Result<Vec<u8>, RadosError> {
+		let len = unsafe {
+			rados_aio_wait_for_complete(self.completion);
+			rados_aio_get_return_value(self.completion)
+		};
+		if len < 0 {
+			return Err(RadosError::from_return_code(len));
+		}
+		let mut buf = mem::replace(&mut self.buf, Vec::new());
+		buf.truncate(len as usize);
+		Ok(buf)
 	}
+}
 
-	pub fn create_ioctx<S>(&self, pool_name: S) -> Result<IoCtx, &str>
-		where S: Into<Vec<u8>>
-	{
-		let pool_name_ptr = CString::new(pool_name).unwrap().as_ptr();
+impl<'a> Drop for ReadCompletion<'a> {
+	fn drop(&mut self) {
+		release_completion(self.completion, self.started);
+	}
+}
 
-		let ioctx_handle: c_void_ptr = ptr::null_mut();
-		handle_errors!(rados_ioctx_create(self.handle, pool_name_ptr, &ioctx_handle));
-		return Ok(IoCtx { handle: ioctx_handle });
+/// Cancel the operation of `completion`, issued on `io`, waiting for
+/// librados to be done with it if it was canceled
+fn cancel_completion(io: rados_ioctx_t, completion: rados_completion_t) -> Result<bool, RadosError> {
+	let err = unsafe { rados_aio_cancel(io, completion) };
+	if err == -libc::ENOENT {
+		// No longer pending
+		return Ok(false);
+	}
+	if err < 0 {
+		return Err(RadosError::from_return_code(err));
+	}
+	unsafe {
+		rados_aio_wait_for_complete(completion);
 	}
+	Ok(true)
+}
 
-	pub fn shutdown(&self) {
-		unsafe {
-			rados_shutdown(self.handle);
+/// Release `completion`, first waiting for its operation if it was
+/// `started`, as the operation may still use its buffer until then
+fn release_completion(completion: rados_completion_t, started: bool) {
+	if completion.is_null() {
+		return;
+	}
+	unsafe {
+		if started {
+			rados_aio_wait_for_complete(completion);
 		}
+		rados_aio_release(completion);
 	}
+}
+
+/// The object I/O surface of an `IoCtx`, as a trait.
+///
+/// Code written against `RadosIo` instead of `IoCtx` can be unit tested
+/// without a live cluster by substituting an in-memory implementation such
+/// as `ceph::mock::MockIo`.
+pub trait RadosIo {
+	/// Replace the contents of the `oid` object with `data`, creating it if
+	/// needed. Fails with `RadosError::NoSpace` if the cluster is full.
+	fn write_full(&self, oid: &str, data: &[u8]) -> Result<(), RadosError>;
+
+	/// Write `data` into the `oid` object, starting at `offset`. Fails with
+	/// `RadosError::NoSpace` if the cluster is full.
+	fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError>;
+
+	/// Append `data` to the `oid` object, creating it if needed
+	fn append(&self, oid: &str, data: &[u8]) -> Result<(), RadosError>;
+
+	/// Read up to `buf.len()` bytes of the `oid` object, starting at
+	/// `offset`, returning how many bytes were read
+	fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError>;
 
+	/// Delete the `oid` object
+	fn remove(&self, oid: &str) -> Result<(), RadosError>;
 }
 
-impl Debug for Cluster {
-	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-		return f.write_fmt(format_args!("{:?}", self.handle))
+// librados io contexts can be used from several threads at once, as long
+// as their settings (namespace, locator, ...) aren't changed concurrently
+unsafe impl Send for IoCtx {}
+unsafe impl Sync for IoCtx {}
+
+// Likewise for cluster handles, which librados locks internally
+unsafe impl Send for Cluster {}
+unsafe impl Sync for Cluster {}
+
+impl RadosIo for IoCtx {
+	fn write_full(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		let oid_cs = CString::new(oid)?;
+		handle_errors!(rados_write_full(self.handle, oid_cs.as_ptr(), data.as_ptr() as *const c_char, data.len() as size_t));
+		return Ok(());
+	}
+
+	fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError> {
+		IoCtx::write_at(self, oid, data, offset)
+	}
+
+	fn append(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+		self.append_create(oid, data)
+	}
+
+	fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError> {
+		IoCtx::read_at(self, oid, buf, offset)
+	}
+
+	fn remove(&self, oid: &str) -> Result<(), RadosError> {
+		IoCtx::remove(self, oid)
 	}
 }
 
-impl Drop for Cluster {
+impl Drop for IoCtx {
 	fn drop(&mut self) {
-		println!("rados_shutdown({:?})", self.handle);
-		self.shutdown();
+		println!("rados_ioctx_destroy({:?})", self.handle);
+		// Held throughout, so Cluster::close() doesn't flush a destroyed
+		// handle, nor shut down while this one is in use
+		let mut open = self.ioctxs.lock().unwrap_or_else(PoisonError::into_inner);
+		let cluster = unsafe { rados_ioctx_get_cluster(self.handle) };
+		unsafe {
+			// Pending asynchronous writes would otherwise be lost if the
+			// cluster is shut down next, see Cluster::close()
+			rados_aio_flush(self.handle);
+			rados_ioctx_destroy(self.handle);
+		}
+		let handle = self.handle as usize;
+		open.handles.retain(|h| *h != handle);
+		if open.orphaned && open.handles.is_empty() {
+			// The Cluster was shut down while this was open, see
+			// Cluster::shutdown()
+			unsafe {
+				rados_shutdown(cluster);
+			}
+		}
 	}
 }
 
-#[allow(dead_code)]
-/// Keep this here for debugging for now
-fn dump(msg: &str, buf: *const c_char, len: isize) {
-	print!("{}: ({}) [", msg, len);
-	for i in 0..len {
-		let c = unsafe {*buf.offset(i)};
-		print!("{:02x}", c);
-		if c >= 32 {
-			print!(" '{}'", c as u8 as char);
+/// A compound write operation.
+///
+/// Actions are added with the builder methods, and are then applied to a
+/// single object atomically by `IoCtx::operate()`: either all of them
+/// succeed, or none are applied.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags, WriteOp};
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// # let ioctx = cluster.create_ioctx("data").unwrap();
+/// let mut op = WriteOp::new();
+/// op.create(true).append(b"Hello, world.");
+/// ioctx.operate("hw", &op).unwrap();
+/// ```
+///
+/// An action given a name with a NUL byte in it can't be passed to librados,
+/// so it isn't added, and running the operation fails with `EINVAL` instead.
+pub struct WriteOp {
+	handle: rados_write_op_t,
+	/// Whether an action was left out for an invalid argument
+	invalid: bool,
+}
+
+impl WriteOp {
+	pub fn new() -> WriteOp {
+		let handle = unsafe { rados_create_write_op() };
+		if handle.is_null() {
+			panic!("rados_create_write_op() failed to allocate");
 		}
-		if i < len - 1 {
-			print!(",");
+		WriteOp { handle: handle, invalid: false }
+	}
+
+	/// `s` as a `CString`, or `None`, marking the operation invalid, if it
+	/// has a NUL byte in it
+	fn cstring(&mut self, s: &str) -> Option<CString> {
+		match CString::new(s) {
+			Ok(cs) => Some(cs),
+			Err(_) => {
+				self.invalid = true;
+				None
+			},
 		}
 	}
-	println!("]");
-}
 
-impl IoCtx {
-	pub fn write<S, T>(&self, oid: S, data: T) -> Result<(), &str>
-		where S: Into<Vec<u8>>, T: Into<String>
-	{
-		let oid_cs = CString::new(oid).unwrap();
-		let s : String = data.into();
-		let len : size_t = s.len() as size_t;
-		let buf = CString::new(s).unwrap();
-		handle_errors!(rados_write_full(self.handle, oid_cs.as_ptr(), buf.as_ptr(), len));
-		return Ok(());
+	/// Fail with `EINVAL` if an action was left out, before running the
+	/// operation
+	fn check(&self) -> Result<(), RadosError> {
+		if self.invalid {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		Ok(())
 	}
 
-	pub fn read(&self, oid: &str, len: usize) -> Result<&str, &str> {
-		// Need to hang on the the CString, can immediately do as_ptr()
-		// see https://github.com/rust-lang/rust/issues/16035
-		let oid_cs = CString::new(oid).unwrap();
-		// allow for terminating '\0' (not really needed)
-		let buf_size = len + 1;
-		let mut buf = zeroed_c_char_buf!(buf_size);
-		handle_errors!(rados_read(self.handle, oid_cs.as_ptr(), buf.as_mut_ptr(), buf_size as size_t, 0));
- 		return Ok(unsafe {
-	 		CStr::from_ptr(buf.as_ptr()).to_str().unwrap()
- 		});
+	/// Create the object. If `exclusive` is `true`, the whole operation fails
+	/// with `RadosError::AlreadyExists` if the object already exists.
+	pub fn create(&mut self, exclusive: bool) -> &mut WriteOp {
+		let flag = if exclusive { LIBRADOS_CREATE_EXCLUSIVE } else { LIBRADOS_CREATE_IDEMPOTENT };
+		unsafe {
+			rados_write_op_create(self.handle, flag, ptr::null());
+		}
+		self
 	}
 
-	pub fn getxattr<S>(&self, oid: S, name: S, len: usize) -> Result<&str, &str>
-		where S: Into<Vec<u8>>
-	{
-		// Need to hang on the the CString, can't immediately do as_ptr()
-		// see https://github.com/rust-lang/rust/issues/16035
-		let oid_cs = CString::new(oid).unwrap();
-		let name_cs = CString::new(name).unwrap();
-		// allow for terminating '\0' (not really needed)
-		let buf_size = len + 1;
-		// A neat way to allocate a zeroed out array of given size
-		let mut buf = zeroed_c_char_buf!(buf_size);
-		handle_errors!(rados_getxattr(self.handle, oid_cs.as_ptr(), name_cs.as_ptr(), buf.as_mut_ptr(), buf_size as size_t));
- 		return Ok(unsafe {
-	 		CStr::from_ptr(buf.as_ptr()).to_str().unwrap()
- 		});
+	/// Like `create()`, also placing the object in the legacy `category`,
+	/// which current clusters ignore
+	pub fn create_in_category(&mut self, exclusive: bool, category: &str) -> &mut WriteOp {
+		let flag = if exclusive { LIBRADOS_CREATE_EXCLUSIVE } else { LIBRADOS_CREATE_IDEMPOTENT };
+		let category_cs = match self.cstring(category) {
+			Some(cs) => cs,
+			None => return self,
+		};
+		unsafe {
+			// librados copies the category before returning
+			rados_write_op_create(self.handle, flag, category_cs.as_ptr());
+		}
+		self
 	}
 
-	pub fn setxattr<S, T>(&self, oid: S, name: S, value: T) -> Result<(), &str>
-		where S: Into<Vec<u8>>, T: Into<String>
+	/// Append `data` to the end of the object
+	pub fn append(&mut self, data: &[u8]) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_append(self.handle, data.as_ptr() as *const c_char, data.len() as size_t);
+		}
+		self
+	}
+
+	/// Write `data` into the object, starting at `offset`
+	pub fn write(&mut self, data: &[u8], offset: u64) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_write(self.handle, data.as_ptr() as *const c_char, data.len() as size_t, offset);
+		}
+		self
+	}
+
+	/// Replace the contents of the object with `data`
+	pub fn write_full(&mut self, data: &[u8]) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_write_full(self.handle, data.as_ptr() as *const c_char, data.len() as size_t);
+		}
+		self
+	}
+
+	/// Remove the object
+	pub fn remove(&mut self) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_remove(self.handle);
+		}
+		self
+	}
+
+	/// Apply `flags` to the action added last
+	pub fn set_flags(&mut self, flags: WriteFlags) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_set_flags(self.handle, flags.bits() as c_int);
+		}
+		self
+	}
+
+	/// Fail the whole operation with `ENOENT` unless the object exists
+	pub fn assert_exists(&mut self) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_assert_exists(self.handle);
+		}
+		self
+	}
+
+	/// Fail the whole operation unless the object exists at version `ver`:
+	/// with `ERANGE` if it's newer, and `EOVERFLOW` if it's older
+	pub fn assert_version(&mut self, ver: u64) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_assert_version(self.handle, ver);
+		}
+		self
+	}
+
+	/// Fail the whole operation with `ECANCELED` unless the `name` xattr
+	/// is currently `value`. A missing xattr compares equal to an empty
+	/// value.
+	pub fn assert_xattr_eq(&mut self, name: &str, value: &[u8]) -> &mut WriteOp {
+		let name_cs = match self.cstring(name) {
+			Some(cs) => cs,
+			None => return self,
+		};
+		unsafe {
+			rados_write_op_cmpxattr(self.handle, name_cs.as_ptr(), LIBRADOS_CMPXATTR_OP_EQ,
+				value.as_ptr() as *const c_char, value.len() as size_t);
+		}
+		self
+	}
+
+	/// Set the `name` xattr to `value`
+	pub fn setxattr(&mut self, name: &str, value: &[u8]) -> &mut WriteOp {
+		let name_cs = match self.cstring(name) {
+			Some(cs) => cs,
+			None => return self,
+		};
+		unsafe {
+			rados_write_op_setxattr(self.handle, name_cs.as_ptr(),
+				value.as_ptr() as *const c_char, value.len() as size_t);
+		}
+		self
+	}
+
+	/// Zero `len` bytes of the object, starting at `offset`
+	pub fn zero(&mut self, offset: u64, len: u64) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_zero(self.handle, offset, len);
+		}
+		self
+	}
+
+	/// Truncate the object to `size` bytes
+	pub fn truncate(&mut self, size: u64) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_truncate(self.handle, size);
+		}
+		self
+	}
+
+	/// Remove all key/value pairs from the object's omap
+	pub fn omap_clear(&mut self) -> &mut WriteOp {
+		unsafe {
+			rados_write_op_omap_clear(self.handle);
+		}
+		self
+	}
+
+	/// Set the key/value pairs in `entries` in the object's omap
+	pub fn omap_set<K, V>(&mut self, entries: &[(K, V)]) -> &mut WriteOp
+		where K: AsRef<str>, V: AsRef<[u8]>
 	{
-		// Need to hang on the the CString, can't immediately do as_ptr()
-		// see https://github.com/rust-lang/rust/issues/16035
-		let oid_cs = CString::new(oid).unwrap();
-		let name_cs = CString::new(name).unwrap();
-		// allow for terminating '\0' (not really needed)
-		let s : String = value.into();
-		let len : size_t = s.len() as size_t;
-		let buf = CString::new(s).unwrap();
-		handle_errors!(rados_setxattr(self.handle, oid_cs.as_ptr(), name_cs.as_ptr(), buf.as_ptr(), len));
-		return Ok(());
+		let keys = match entries.iter().map(|e| CString::new(e.0.as_ref())).collect::<Result<Vec<CString>, NulError>>() {
+			Ok(keys) => keys,
+			Err(_) => {
+				self.invalid = true;
+				return self;
+			},
+		};
+		let key_ptrs: Vec<*const c_char> = keys.iter().map(|k| k.as_ptr()).collect();
+		let val_ptrs: Vec<*const c_char> = entries.iter().map(|e| e.1.as_ref().as_ptr() as *const c_char).collect();
+		let lens: Vec<size_t> = entries.iter().map(|e| e.1.as_ref().len() as size_t).collect();
+		unsafe {
+			// librados copies the keys and values before returning
+			rados_write_op_omap_set(self.handle, key_ptrs.as_ptr(), val_ptrs.as_ptr(), lens.as_ptr(),
+				entries.len() as size_t);
+		}
+		self
 	}
+}
 
-	pub fn remove(&self, oid: &str) -> Result<(), &str> {
-		let oid_ptr = CString::new(oid).unwrap().as_ptr();
-		handle_errors!(rados_remove(self.handle, oid_ptr));
-		return Ok(());
+impl Drop for WriteOp {
+	fn drop(&mut self) {
+		unsafe {
+			rados_release_write_op(self.handle);
+		}
 	}
 }
 
+/// A compound read operation. The actions added to it write their results
+/// through raw pointers, so it's only used internally, with the outputs
+/// living on the stack next to it.
+struct ReadOp {
+	handle: rados_read_op_t
+}
+
+impl ReadOp {
+	fn new() -> ReadOp {
+		let handle = unsafe { rados_create_read_op() };
+		if handle.is_null() {
+			panic!("rados_create_read_op() failed to allocate");
+		}
+		ReadOp { handle: handle }
+	}
+}
 
-impl Drop for IoCtx {
+impl Drop for ReadOp {
 	fn drop(&mut self) {
-		println!("rados_ioctx_destroy({:?})", self.handle);
 		unsafe {
-			rados_ioctx_destroy(self.handle);
+			rados_release_read_op(self.handle);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use mock::MockIo;
+
+	#[test]
+	fn retry_retries_transient_errors() {
+		let policy = RetryPolicy {
+			max_attempts: 5,
+			initial_backoff: Duration::from_millis(1),
+			max_backoff: Duration::from_millis(2),
+			errnos: vec![libc::EAGAIN],
+		};
+		let mut attempts = 0;
+		let result = policy.retry(|| {
+			attempts += 1;
+			if attempts <= 2 {
+				Err(RadosError::from_return_code(-libc::EAGAIN))
+			} else {
+				Ok(attempts)
+			}
+		});
+		assert_eq!(result, Ok(3));
+		assert_eq!(attempts, 3);
+	}
+
+	#[test]
+	fn find_conf_file_expands_variables() {
+		let dir = env::temp_dir().join(format!("ceph-rs-conf-{}", ::std::process::id()));
+		::std::fs::create_dir_all(dir.join(".ceph")).unwrap();
+		let dir_str = dir.to_str().unwrap();
+		let search = format!("{0}/missing.conf, {0}/$data_dir/config; ~/.ceph/$cluster.conf", dir_str);
+		assert_eq!(find_conf_file(&search, "test", "nope", dir_str), None);
+
+		::std::fs::write(dir.join(".ceph/test.conf"), "[global]\n").unwrap();
+		assert_eq!(find_conf_file(&search, "test", "nope", dir_str), Some(dir.join(".ceph/test.conf")));
+		::std::fs::write(dir.join("config"), "[global]\n").unwrap();
+		assert_eq!(find_conf_file(&search, "test", "", dir_str), Some(PathBuf::from(format!("{}//config", dir_str))));
+		::std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn fill_buffer_grows() {
+		let mut sizes = Vec::new();
+		let filled = fill_buffer(3, |buf| {
+			sizes.push(buf.len());
+			if buf.len() < 1000 {
+				return if sizes.len() % 2 == 0 { -libc::ERANGE } else { -libc::ENAMETOOLONG };
+			}
+			buf[..5].copy_from_slice(b"value");
+			5
+		});
+		assert_eq!(sizes, [3, 6, 12, 24, 48, 96, 192, 384, 768, 1536]);
+		let (buf, len) = filled.unwrap();
+		assert_eq!((buf.len(), len), (1536, 5));
+		assert_eq!(c_buf_to_string(&buf), "value");
+
+		// Gives up once the buffer reached the cap
+		sizes.clear();
+		let filled = fill_buffer(MAX_BUF_SIZE / 3, |buf| {
+			sizes.push(buf.len());
+			-libc::ERANGE
+		});
+		assert_eq!(filled, Err(-libc::ERANGE));
+		assert_eq!(sizes, [MAX_BUF_SIZE / 3, MAX_BUF_SIZE / 3 * 2, MAX_BUF_SIZE]);
+
+		// Other errors aren't retried
+		let mut calls = 0;
+		assert_eq!(fill_buffer(0, |_| { calls += 1; -libc::EIO }), Err(-libc::EIO));
+		assert_eq!(calls, 1);
+	}
+
+	#[test]
+	fn exec_buffer_fits_1mib() {
+		// What rados_exec() does with an output larger than the buffer
+		let output: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+		let mut calls = 0;
+		let (mut buf, len) = fill_buffer(EXEC_INITIAL_BUF_SIZE, |buf| {
+			calls += 1;
+			if buf.len() < output.len() {
+				return -libc::ERANGE;
+			}
+			buf[..output.len()].copy_from_slice(&output);
+			output.len() as c_int
+		}).unwrap();
+		buf.truncate(len);
+		assert_eq!(buf, output);
+		// 4 KiB doubled 8 times
+		assert_eq!(calls, 9);
+	}
+
+	#[test]
+	fn durations_in_ms() {
+		assert_eq!(duration_to_ms(Duration::new(1, 999999)), 1000);
+		assert_eq!(duration_to_ms(Duration::from_millis(1500)), 1500);
+		assert_eq!(duration_to_ms(Duration::new(u64::max_value(), 999999999)), usize::max_value());
+		assert!(deadline_after(Duration::from_secs(1)).is_some());
+		assert_eq!(deadline_after(Duration::new(u64::max_value(), 0)), None);
+	}
+
+	#[test]
+	fn object_class_lists() {
+		assert_eq!(parse_object_classes("*"), ObjectClasses::All);
+		assert_eq!(parse_object_classes(" * "), ObjectClasses::All);
+		let classes = parse_object_classes("lock rbd,\trefcount;  ");
+		assert_eq!(classes, ObjectClasses::Only(vec!["lock".to_string(), "rbd".to_string(), "refcount".to_string()]));
+		assert!(classes.allows("rbd") && !classes.allows("hello"));
+		assert_eq!(parse_object_classes(""), ObjectClasses::Only(Vec::new()));
+	}
+
+	#[test]
+	fn pgids() {
+		assert!(is_valid_pgid("1.2f"));
+		assert!(is_valid_pgid("12.0"));
+		for pgid in &["", "1", "1.", ".2f", "x.1", "1.2g", "1.2.3"] {
+			assert!(!is_valid_pgid(pgid), "{} is valid", pgid);
+		}
+	}
+
+	#[test]
+	fn cursor_round_trip() {
+		let cursor = encode_cursor(0x0102_0304, 0x0506_0708_090a_0b0c);
+		assert_eq!(cursor, [4, 3, 2, 1, 0xc, 0xb, 0xa, 9, 8, 7, 6, 5]);
+		assert_eq!(decode_cursor(&cursor).unwrap(), (0x0102_0304, 0x0506_0708_090a_0b0c));
+		assert_eq!(decode_cursor(&encode_cursor(0, 0)).unwrap(), (0, 0));
+		assert_eq!(decode_cursor(&encode_cursor(u32::max_value(), u64::max_value())).unwrap(), (u32::max_value(), u64::max_value()));
+		assert_eq!(decode_cursor(&cursor[..11]).unwrap_err(), RadosError::Other(libc::EINVAL));
+		assert_eq!(decode_cursor(&[0; 13]).unwrap_err(), RadosError::Other(libc::EINVAL));
+	}
+
+	#[test]
+	fn glob() {
+		assert!(glob_match("logs/*", "logs/2016-01-01"));
+		assert!(glob_match("logs/*", "logs/"));
+		assert!(!glob_match("logs/*", "log"));
+		assert!(glob_match("*.json", "a.b.json"));
+		assert!(glob_match("a*b*c", "aXbYbZc"));
+		assert!(!glob_match("a*b*c", "aXbYbZ"));
+		assert!(glob_match("file?", "file1"));
+		assert!(!glob_match("file?", "file"));
+		assert!(!glob_match("file?", "file12"));
+		assert!(glob_match("?é", "aé"));
+		assert!(glob_match("\\*", "*"));
+		assert!(!glob_match("\\*", "x"));
+		assert!(glob_match("what\\?", "what?"));
+		assert!(!glob_match("what\\?", "whats"));
+		assert!(glob_match("back\\\\slash", "back\\slash"));
+		assert!(glob_match("", ""));
+		assert!(!glob_match("", "x"));
+		assert!(glob_match("*", ""));
+	}
+
+	#[test]
+	fn mon_hosts() {
+		let two = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+		assert_eq!(parse_mon_hosts("10.0.0.1,10.0.0.2"), two);
+		assert_eq!(parse_mon_hosts("10.0.0.1 10.0.0.2"), two);
+		assert_eq!(parse_mon_hosts(" 10.0.0.1, 10.0.0.2 "), two);
+		assert_eq!(parse_mon_hosts("10.0.0.1:6789,10.0.0.2:3300"), ["10.0.0.1:6789", "10.0.0.2:3300"]);
+		assert_eq!(parse_mon_hosts("mon-a:6789 mon-b"), ["mon-a:6789", "mon-b"]);
+		assert_eq!(parse_mon_hosts("[v2:10.0.0.1:3300/0,v1:10.0.0.1:6789/0] [v2:10.0.0.2:3300/0,v1:10.0.0.2:6789/0]"),
+			["10.0.0.1:3300", "10.0.0.1:6789", "10.0.0.2:3300", "10.0.0.2:6789"]);
+		assert!(parse_mon_hosts("").is_empty());
+	}
+
+	#[test]
+	fn export_fields_round_trip() {
+		let mut stream = EXPORT_MAGIC.to_vec();
+		let mut written = write_field(&mut stream, b"object", false).unwrap();
+		let xattrs = [("user.a", &b"1"[..]), ("user.empty", &b""[..])];
+		written += write_entries(&mut stream, xattrs.iter().cloned()).unwrap();
+		written += write_entries(&mut stream, [].iter().cloned()).unwrap();
+		written += write_field(&mut stream, b"data", true).unwrap();
+		assert_eq!(written as usize + EXPORT_MAGIC.len(), stream.len());
+
+		let mut reader = &stream[..];
+		read_export_magic(&mut reader).unwrap();
+		assert_eq!(read_field(&mut reader, false).unwrap(), b"object");
+		assert_eq!(read_entries(&mut reader).unwrap(),
+			[("user.a".to_string(), b"1".to_vec()), ("user.empty".to_string(), Vec::new())]);
+		assert!(read_entries(&mut reader).unwrap().is_empty());
+		assert_eq!(read_field(&mut reader, true).unwrap(), b"data");
+		assert_eq!(read_length(&mut reader, false).unwrap(), None);
+	}
+
+	#[test]
+	fn export_truncated() {
+		let mut stream = Vec::new();
+		write_entries(&mut stream, [("user.a", &b"value"[..])].iter().cloned()).unwrap();
+		write_field(&mut stream, b"data", true).unwrap();
+		let eof = |e: RadosError| match e {
+			RadosError::Io(io::ErrorKind::UnexpectedEof, _) => true,
+			_ => false,
+		};
+		for len in 1..stream.len() - 4 - 8 {
+			assert!(eof(read_entries(&mut &stream[..len]).unwrap_err()), "{} bytes", len);
+		}
+		let field = &stream[stream.len() - 12..];
+		for len in 0..field.len() {
+			assert!(eof(read_field(&mut &field[..len], true).unwrap_err()), "{} bytes", len);
+		}
+		// A length larger than the stream isn't allocated up front
+		let mut huge = u64::max_value().to_le_bytes().to_vec();
+		huge.extend_from_slice(b"data");
+		assert!(eof(read_field(&mut &huge[..], true).unwrap_err()));
+	}
+
+	#[test]
+	fn export_bad_magic() {
+		let invalid = |stream: &[u8]| match read_export_magic(&mut &stream[..]) {
+			Err(RadosError::Io(io::ErrorKind::InvalidData, _)) => true,
+			_ => false,
+		};
+		assert!(invalid(b"ceph-rs2\0\0\0\0"));
+		assert!(invalid(b"ceph-rs"));
+		assert!(invalid(b""));
+		assert!(!invalid(b"ceph-rs1"));
+	}
+
+	#[test]
+	fn upload_progress() {
+		let io = MockIo::new();
+		let data: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+		let mut calls = Vec::new();
+		let written = upload_chunks(&io, "big", &data[..], 1000, |done, total| calls.push((done, total))).unwrap();
+		assert_eq!(written, 2500);
+		assert_eq!(calls, [(1000, None), (2000, None), (2500, None)]);
+		assert_eq!(io.get("big").unwrap(), data);
+
+		// A size that's a multiple of the chunk size doesn't report twice
+		let mut calls = 0;
+		upload_chunks(&io, "big", &data[..2000], 1000, |_, _| calls += 1).unwrap();
+		assert_eq!(calls, 2);
+		assert_eq!(io.get("big").unwrap(), &data[..2000]);
+
+		let mut calls = 0;
+		assert_eq!(upload_chunks(&io, "empty", &b""[..], 1000, |_, _| calls += 1).unwrap(), 0);
+		assert_eq!(calls, 0);
+		assert_eq!(io.get("empty").unwrap(), b"");
+	}
+
+	#[test]
+	fn download_progress() {
+		let io = MockIo::new();
+		let data: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+		io.write_full("big", &data).unwrap();
+		let mut calls = Vec::new();
+		let mut out = Vec::new();
+		let read = download_chunks(&io, "big", &mut out, 1000, Some(2500), |done, total| calls.push((done, total))).unwrap();
+		assert_eq!(read, 2500);
+		assert_eq!(calls, [(1000, Some(2500)), (2000, Some(2500)), (2500, Some(2500))]);
+		assert_eq!(out, data);
+
+		assert_eq!(download_chunks(&io, "missing", Vec::new(), 1000, None, |_, _| panic!()).unwrap_err(), RadosError::ObjectNotFound);
+	}
+
+	/// Returns at most `max` bytes from each read, like an OSD may
+	struct ShortReads {
+		io: MockIo,
+		max: usize,
+	}
+
+	impl RadosIo for ShortReads {
+		fn write_full(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+			self.io.write_full(oid, data)
+		}
+
+		fn write_at(&self, oid: &str, data: &[u8], offset: u64) -> Result<(), RadosError> {
+			self.io.write_at(oid, data, offset)
+		}
+
+		fn append(&self, oid: &str, data: &[u8]) -> Result<(), RadosError> {
+			self.io.append(oid, data)
+		}
+
+		fn read_at(&self, oid: &str, buf: &mut [u8], offset: u64) -> Result<usize, RadosError> {
+			let len = cmp::min(buf.len(), self.max);
+			self.io.read_at(oid, &mut buf[..len], offset)
+		}
+
+		fn remove(&self, oid: &str) -> Result<(), RadosError> {
+			self.io.remove(oid)
+		}
+	}
+
+	#[test]
+	fn read_chunks_continues_after_short_reads() {
+		let io = ShortReads { io: MockIo::new(), max: 300 };
+		let data: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+		io.write_full("big", &data).unwrap();
+		assert_eq!(read_chunks(&io, "big", 2500, 1000).unwrap(), data);
+		// Nine reads of 300 bytes or less, and the empty one at the end
+		assert_eq!(io.io.read_count(), 10);
+
+		// A stale size, from before an append or truncate
+		assert_eq!(read_chunks(&io, "big", 1000, 1000).unwrap(), data);
+		assert_eq!(read_chunks(&io, "big", 5000, 1000).unwrap(), data);
+		assert_eq!(read_chunks(&io, "missing", 0, 1000).unwrap_err(), RadosError::ObjectNotFound);
+	}
+
+	#[test]
+	fn chunk_sizes() {
+		let limit = (u32::MAX / 2) as usize;
+		assert_eq!(check_chunk_size(0), Err(RadosError::Other(libc::EINVAL)));
+		assert_eq!(check_chunk_size(limit), Err(RadosError::Other(libc::EINVAL)));
+		assert_eq!(check_chunk_size(usize::MAX), Err(RadosError::Other(libc::EINVAL)));
+		assert_eq!(check_chunk_size(1), Ok(()));
+		assert_eq!(check_chunk_size(64 * 1024), Ok(()));
+		assert_eq!(check_chunk_size(limit - 1), Ok(()));
+	}
+
+	#[test]
+	fn io_error_kinds() {
+		let kinds = [
+			(RadosError::PermissionDenied, io::ErrorKind::PermissionDenied),
+			(RadosError::AlreadyExists, io::ErrorKind::AlreadyExists),
+			(RadosError::Timeout, io::ErrorKind::TimedOut),
+			(RadosError::UnexpectedEof, io::ErrorKind::UnexpectedEof),
+			(RadosError::NoSpace, io::ErrorKind::Other),
+		];
+		for &(ref rados_err, kind) in kinds.iter() {
+			let err: io::Error = rados_err.clone().into();
+			assert_eq!(err.kind(), kind, "{:?}", rados_err);
+			// The RadosError is kept as the inner error
+			let inner = err.get_ref().and_then(|inner| inner.downcast_ref::<RadosError>());
+			assert_eq!(inner, Some(rados_err));
 		}
+		let err: io::Error = RadosError::Other(libc::EINVAL).into();
+		assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+		assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn no_space() {
+		assert_eq!(RadosError::from_return_code(-libc::ENOSPC), RadosError::NoSpace);
+		assert_eq!(RadosError::from_pool_return_code(-libc::ENOSPC), RadosError::NoSpace);
+		assert_eq!(RadosError::NoSpace.errno(), Some(libc::ENOSPC));
+		// Quotas are a separate error
+		assert_eq!(RadosError::from_return_code(-libc::EDQUOT), RadosError::QuotaExceeded);
+	}
+
+	#[test]
+	fn command_failed_keeps_status() {
+		let err = RadosError::CommandFailed(libc::ENOENT, "pool 'nope' does not exist".to_string());
+		assert_eq!(err.errno(), Some(libc::ENOENT));
+		assert!(err.to_string().ends_with(": pool 'nope' does not exist"));
+		let err: io::Error = err.into();
+		assert_eq!(err.kind(), io::ErrorKind::NotFound);
+	}
+
+	#[test]
+	fn retry_gives_up() {
+		let policy = RetryPolicy {
+			max_attempts: 2,
+			initial_backoff: Duration::from_millis(1),
+			max_backoff: Duration::from_secs(u64::max_value()),
+			errnos: vec![libc::EAGAIN],
+		};
+		let mut attempts = 0;
+		let result: Result<(), RadosError> = policy.retry(|| {
+			attempts += 1;
+			Err(RadosError::Other(libc::EAGAIN))
+		});
+		assert_eq!(result, Err(RadosError::Other(libc::EAGAIN)));
+		assert_eq!(attempts, 2);
+
+		attempts = 0;
+		let result: Result<(), RadosError> = policy.retry(|| {
+			attempts += 1;
+			Err(RadosError::Other(libc::EIO))
+		});
+		assert_eq!(result, Err(RadosError::Other(libc::EIO)));
+		assert_eq!(attempts, 1);
 	}
 }
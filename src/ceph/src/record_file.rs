@@ -0,0 +1,99 @@
+use rados::{IoCtx, RadosError};
+
+use libc;
+
+/// An object used as an array of fixed-size records of `N` bytes each,
+/// stored back to back: record `i` is at offset `i * N`.
+///
+/// Records are appended with a write operation asserting the version of
+/// the object they were counted on (see `IoCtx::append_capped()`), so
+/// concurrent appenders each get a distinct index. The object should only
+/// be written through a `RecordFile` of the same `N`: a size that isn't a
+/// multiple of `N` shifts every record appended after it.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use ceph::rados::{Cluster, CreateFlags};
+/// use ceph::record_file::RecordFile;
+///
+/// # let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+/// # let ioctx = cluster.create_ioctx("data").unwrap();
+/// let samples: RecordFile<8> = RecordFile::new(&ioctx, "samples").unwrap();
+/// let index = samples.append(&42u64.to_le_bytes()).unwrap();
+/// assert_eq!(samples.get(index).unwrap(), 42u64.to_le_bytes());
+/// ```
+pub struct RecordFile<'a, const N: usize> {
+	ioctx: &'a IoCtx,
+	oid: String,
+}
+
+impl<'a, const N: usize> RecordFile<'a, N> {
+	/// Use the `oid` object as an array of records. The object doesn't
+	/// need to exist: it's created by the first `append()`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(RecordFile)` on success
+	/// * `Err(RadosError)` with `EINVAL` if `N` is `0`
+	pub fn new(ioctx: &'a IoCtx, oid: &str) -> Result<RecordFile<'a, N>, RadosError> {
+		if N == 0 {
+			return Err(RadosError::Other(libc::EINVAL));
+		}
+		Ok(RecordFile { ioctx: ioctx, oid: oid.to_string() })
+	}
+
+	/// The name of the object holding the records
+	pub fn oid(&self) -> &str {
+		&self.oid
+	}
+
+	/// Add `record` after the last record, creating the object if needed.
+	///
+	/// # Returns
+	///
+	/// * `Ok(index)` with the index of the new record on success
+	/// * `Err(RadosError)` on failure
+	pub fn append(&self, record: &[u8; N]) -> Result<u64, RadosError> {
+		let size = self.ioctx.append_capped(&self.oid, record, u64::max_value())?;
+		Ok(size / N as u64 - 1)
+	}
+
+	/// Read the record at `index`.
+	///
+	/// # Returns
+	///
+	/// * `Ok(record)` on success
+	/// * `Err(RadosError)` on failure, `RadosError::UnexpectedEof` if there
+	///   is no record at `index`, and `RadosError::ObjectNotFound` if there
+	///   are no records at all
+	pub fn get(&self, index: u64) -> Result<[u8; N], RadosError> {
+		let offset = match index.checked_mul(N as u64) {
+			Some(offset) => offset,
+			None => return Err(RadosError::UnexpectedEof),
+		};
+		let mut record = [0u8; N];
+		self.ioctx.read_exact_into(&self.oid, offset, &mut record)?;
+		Ok(record)
+	}
+
+	/// Count the records, from the size of the object. A missing object
+	/// has none.
+	///
+	/// # Returns
+	///
+	/// * `Ok(count)` on success
+	/// * `Err(RadosError)` on failure
+	pub fn len(&self) -> Result<u64, RadosError> {
+		match self.ioctx.stat(&self.oid) {
+			Ok((size, _)) => Ok(size / N as u64),
+			Err(RadosError::ObjectNotFound) => Ok(0),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Whether there are no records
+	pub fn is_empty(&self) -> Result<bool, RadosError> {
+		self.len().map(|len| len == 0)
+	}
+}
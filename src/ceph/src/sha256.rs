@@ -0,0 +1,116 @@
+//! A minimal SHA-256 (FIPS 180-4), enough to name objects after their
+//! contents without pulling in a crypto crate.
+
+const K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+	0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+	let mut w = [0u32; 64];
+	for i in 0..16 {
+		w[i] = ((block[i * 4] as u32) << 24) | ((block[i * 4 + 1] as u32) << 16)
+			| ((block[i * 4 + 2] as u32) << 8) | (block[i * 4 + 3] as u32);
+	}
+	for i in 16..64 {
+		let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+		let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+		w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+	}
+	let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+	let (mut e, mut f, mut g, mut h) = (state[4], state[5], state[6], state[7]);
+	for i in 0..64 {
+		let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+		let ch = (e & f) ^ (!e & g);
+		let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+		let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+		let maj = (a & b) ^ (a & c) ^ (b & c);
+		let t2 = s0.wrapping_add(maj);
+		h = g;
+		g = f;
+		f = e;
+		e = d.wrapping_add(t1);
+		d = c;
+		c = b;
+		b = a;
+		a = t1.wrapping_add(t2);
+	}
+	let words = [a, b, c, d, e, f, g, h];
+	for i in 0..8 {
+		state[i] = state[i].wrapping_add(words[i]);
+	}
+}
+
+/// The SHA-256 digest of `data`
+pub fn digest(data: &[u8]) -> [u8; 32] {
+	let mut state = H0;
+	let mut chunks = data.chunks_exact(64);
+	for block in &mut chunks {
+		compress(&mut state, block);
+	}
+	// pad the rest with 0x80, zeros and the length in bits, big endian
+	let rest = chunks.remainder();
+	let mut tail = [0u8; 128];
+	tail[..rest.len()].copy_from_slice(rest);
+	tail[rest.len()] = 0x80;
+	let tail_len = if rest.len() < 56 { 64 } else { 128 };
+	let bits = (data.len() as u64).wrapping_mul(8);
+	for i in 0..8 {
+		tail[tail_len - 1 - i] = (bits >> (i * 8)) as u8;
+	}
+	for block in tail[..tail_len].chunks(64) {
+		compress(&mut state, block);
+	}
+	let mut out = [0u8; 32];
+	for i in 0..8 {
+		out[i * 4] = (state[i] >> 24) as u8;
+		out[i * 4 + 1] = (state[i] >> 16) as u8;
+		out[i * 4 + 2] = (state[i] >> 8) as u8;
+		out[i * 4 + 3] = state[i] as u8;
+	}
+	out
+}
+
+/// The SHA-256 digest of `data` as 64 lowercase hex digits
+pub fn hex_digest(data: &[u8]) -> String {
+	digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{digest, hex_digest};
+	use std::iter;
+
+	#[test]
+	fn known_answers() {
+		assert_eq!(hex_digest(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+		assert_eq!(hex_digest(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+	}
+
+	#[test]
+	fn padding_boundaries() {
+		// 55 bytes still fit the length in the last block, 56 need another
+		// one, and 64 fill a block exactly
+		let hash = |n: usize| hex_digest(&iter::repeat(b'a').take(n).collect::<Vec<u8>>());
+		assert_eq!(hash(55), "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318");
+		assert_eq!(hash(56), "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a");
+		assert_eq!(hash(64), "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb");
+	}
+
+	#[test]
+	fn digest_matches_hex_digest() {
+		let raw = digest(b"abc");
+		assert_eq!(raw[0], 0xba);
+		assert_eq!(raw[31], 0xad);
+	}
+}
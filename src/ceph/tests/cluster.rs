@@ -0,0 +1,633 @@
+//! Tests against a running cluster, only built with the `integration`
+//! feature.
+
+#![cfg(feature = "integration")]
+
+extern crate ceph;
+#[cfg(feature = "futures")]
+extern crate futures;
+extern crate libc;
+extern crate serde_json;
+
+mod common;
+
+use std::env;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ceph::lock::ObjectLock;
+use ceph::multi_pool::MultiPool;
+use ceph::rados::{Cluster, CreateFlags, HealthStatus, PoolQuota, PoolType, RadosError, ResilientWatch, WriteOp};
+
+#[test]
+fn put_cas_is_idempotent() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let data = format!("content addressed {}", common::oid("cas"));
+	let oid = ioctx.put_cas(data.as_bytes()).unwrap();
+	assert_eq!(oid.len(), 64);
+	assert_eq!(ioctx.put_cas(data.as_bytes()).unwrap(), oid);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), data.as_bytes());
+	ioctx.remove(&oid).unwrap();
+	assert_eq!(ioctx.stat(&oid).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn set_client_name_before_connect() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	for name in &["ingest", "client.", ".ingest"] {
+		assert_eq!(cluster.set_client_name(name).unwrap_err(), RadosError::Other(libc::EINVAL));
+	}
+	cluster.set_client_name("client.ingest").unwrap();
+	assert_eq!(cluster.conf_get("name").unwrap(), "client.ingest");
+	cluster.set_client_name("client.admin").unwrap();
+	cluster.conf_read_file(None).unwrap();
+	cluster.connect().unwrap();
+	assert_eq!(cluster.set_client_name("client.ingest").unwrap_err(), RadosError::Other(libc::EISCONN));
+	assert_eq!(cluster.conf_get("name").unwrap(), "client.admin");
+}
+
+#[test]
+fn scrub_pg() {
+	let cluster = common::cluster();
+	let pgid = cluster.list_pgs(&common::pool()).unwrap().remove(0);
+	cluster.scrub_pg(&pgid, false).unwrap();
+	cluster.scrub_pg(&pgid, true).unwrap();
+	assert_eq!(cluster.scrub_pg("1.", false).unwrap_err(), RadosError::Other(libc::EINVAL));
+}
+
+#[test]
+fn wait_for_pg_state() {
+	let cluster = common::cluster();
+	// A PG of the second pool, which the scrub above leaves alone
+	let pgid = cluster.list_pgs(&common::other_pool()).unwrap().pop().unwrap();
+	cluster.wait_for_pg_state(&pgid, "active+clean", Duration::from_secs(60)).unwrap();
+	// A timeout past the last Instant is no timeout
+	cluster.wait_for_pg_state(&pgid, "active+clean", Duration::new(u64::MAX, 0)).unwrap();
+	assert_eq!(cluster.wait_for_pg_state(&pgid, "no-such-state", Duration::from_millis(200)).unwrap_err(), RadosError::Timeout);
+	assert_eq!(cluster.wait_for_pg_state("1.", "active+clean", Duration::from_secs(1)).unwrap_err(), RadosError::Other(libc::EINVAL));
+}
+
+#[test]
+fn list_object_classes() {
+	let cluster = common::cluster();
+	let classes = cluster.list_object_classes().unwrap();
+	assert!(classes.allows("lock"), "lock isn't allowed: {:?}", classes);
+	// A class it reports can be called
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("classes");
+	ioctx.write_at(&oid, b"x", 0).unwrap();
+	assert!(!ioctx.exec(&oid, "lock", "list_locks", b"").unwrap().is_empty());
+	ioctx.remove(&oid).unwrap();
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn connect_async() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_read_file(None).unwrap();
+	let cluster = futures::executor::block_on(cluster.connect_async()).unwrap();
+	assert!(cluster.is_connected());
+	cluster.create_ioctx(common::pool()).unwrap();
+}
+
+#[test]
+fn op_timeout() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.set_op_timeout(Duration::new(0, 1));
+	assert_eq!(ioctx.op_timeout(), Some(Duration::from_millis(1)));
+	ioctx.set_op_timeout(Duration::from_secs(0));
+	assert_eq!(ioctx.op_timeout(), None);
+
+	// Timed reads and writes go through the asynchronous path
+	ioctx.set_op_timeout(Duration::from_secs(30));
+	let oid = common::oid("timeout");
+	ioctx.write_at(&oid, b"in time", 0).unwrap();
+	let mut buf = [0u8; 7];
+	assert_eq!(ioctx.read_at(&oid, &mut buf, 0).unwrap(), 7);
+	assert_eq!(&buf, b"in time");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn multi_pool_routes_by_pool() {
+	let cluster = common::cluster();
+	let pools = MultiPool::new(&cluster);
+	let (pool, other_pool) = (common::pool(), common::other_pool());
+	let oid = common::oid("multi-pool");
+	pools.write(&pool, &oid, b"first").unwrap();
+	pools.write(&other_pool, &oid, b"second").unwrap();
+	assert_eq!(pools.read(&pool, &oid, 16).unwrap(), b"first");
+	assert_eq!(pools.read(&other_pool, &oid, 16).unwrap(), b"second");
+	assert!(Arc::ptr_eq(&pools.ioctx(&pool).unwrap(), &pools.ioctx(&pool).unwrap()));
+	pools.ioctx(&pool).unwrap().remove(&oid).unwrap();
+	pools.ioctx(&other_pool).unwrap().remove(&oid).unwrap();
+}
+
+#[test]
+fn incr_from_many_threads() {
+	let cluster = common::cluster();
+	let ioctx = Arc::new(common::ioctx(&cluster));
+	let oid = common::oid("counter");
+	let threads: Vec<_> = (0..8).map(|_| {
+		let (ioctx, oid) = (ioctx.clone(), oid.clone());
+		thread::spawn(move || {
+			for _ in 0..25 {
+				ioctx.incr(&oid, "count", 1).unwrap();
+			}
+		})
+	}).collect();
+	for thread in threads {
+		thread.join().unwrap();
+	}
+	assert_eq!(ioctx.incr(&oid, "count", 0).unwrap(), 8 * 25);
+
+	assert_eq!(ioctx.incr(&oid, "count", i64::MAX).unwrap_err(), RadosError::Other(libc::ERANGE));
+	assert_eq!(ioctx.incr(&oid, "count", -200).unwrap(), 0);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn close_keeps_async_writes() {
+	let oid = common::oid("close");
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.aio_write_safe(&oid, b"durable", 0).unwrap();
+	drop(ioctx);
+	cluster.close().unwrap();
+
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"durable");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn close_refuses_with_open_ioctx() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let (cluster, err) = cluster.close().unwrap_err();
+	assert_eq!(err, RadosError::Other(libc::EBUSY));
+	// The handle was left open for the context
+	let oid = common::oid("close-busy");
+	ioctx.write_at(&oid, b"still usable", 0).unwrap();
+	ioctx.remove(&oid).unwrap();
+	drop(ioctx);
+	cluster.close().unwrap();
+}
+
+#[test]
+fn shutdown_waits_for_open_ioctx() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	cluster.shutdown();
+	// Only once, however often it's called
+	cluster.shutdown();
+	assert_eq!(cluster.create_ioctx(common::pool()).err(), Some(RadosError::NotConnected));
+	// The context keeps the handle open until it's dropped, after the
+	// Cluster here
+	drop(cluster);
+	let oid = common::oid("shutdown");
+	ioctx.write_at(&oid, b"still usable", 0).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"still usable");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn list_objects_resumes_from_cursor() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	// A namespace of its own, so only this test's objects are listed
+	ioctx.set_namespace(Some(&common::oid("cursor"))).unwrap();
+	let mut names: Vec<String> = (0..20).map(|i| format!("object-{}", i)).collect();
+	for name in &names {
+		ioctx.write_at(name, b"x", 0).unwrap();
+	}
+
+	let mut listed = Vec::new();
+	let cursor = {
+		let mut objects = ioctx.list_objects().unwrap();
+		for _ in 0..names.len() / 2 {
+			listed.push(objects.next().unwrap().unwrap());
+		}
+		objects.cursor()
+	};
+	let mut rest = ioctx.list_objects_from(&cursor).unwrap();
+	for name in rest.by_ref() {
+		listed.push(name.unwrap());
+	}
+	// Once over, the listing stays over
+	assert!(rest.next().is_none());
+	assert!(rest.next().is_none());
+	listed.sort();
+	names.sort();
+	assert_eq!(listed, names);
+	assert_eq!(ioctx.list_objects_from(&cursor[1..]).err(), Some(RadosError::Other(libc::EINVAL)));
+	for name in &names {
+		ioctx.remove(name).unwrap();
+	}
+}
+
+#[test]
+fn list_objects_sharded() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.set_namespace(Some(&common::oid("sharded"))).unwrap();
+	let names: Vec<String> = (0..50).map(|i| format!("object-{}", i)).collect();
+	for name in &names {
+		ioctx.write_at(name, b"x", 0).unwrap();
+	}
+
+	let mut all: Vec<String> = ioctx.list_objects().unwrap().map(|name| name.unwrap()).collect();
+	let mut shards: Vec<String> = Vec::new();
+	for shard in 0..2 {
+		shards.extend(ioctx.list_objects_sharded(shard, 2).unwrap().map(|name| name.unwrap()));
+	}
+	all.sort();
+	shards.sort();
+	// The same objects, none of them listed by both shards
+	assert_eq!(shards, all);
+	assert_eq!(ioctx.list_objects_sharded(2, 2).err(), Some(RadosError::Other(libc::EINVAL)));
+	for name in &names {
+		ioctx.remove(name).unwrap();
+	}
+}
+
+#[test]
+fn list_objects_matching() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.set_namespace(Some(&common::oid("glob"))).unwrap();
+	let names = ["prefix/a", "prefix/b", "prefix-c", "other/prefix/d"];
+	for name in &names {
+		ioctx.write_at(name, b"x", 0).unwrap();
+	}
+	let mut matching = ioctx.list_objects_matching("prefix/*").unwrap();
+	matching.sort();
+	assert_eq!(matching, ["prefix/a", "prefix/b"]);
+	for name in &names {
+		ioctx.remove(name).unwrap();
+	}
+}
+
+#[test]
+fn mon_hosts() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_set("mon_host", "10.0.0.1:6789, 10.0.0.2").unwrap();
+	assert_eq!(cluster.mon_hosts().unwrap(), ["10.0.0.1:6789", "10.0.0.2"]);
+}
+
+#[test]
+fn create_ioctx_before_connect() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_read_file(None).unwrap();
+	assert_eq!(cluster.create_ioctx(common::pool()).err(), Some(RadosError::NotConnected));
+	cluster.connect().unwrap();
+	cluster.create_ioctx(common::pool()).unwrap();
+}
+
+#[test]
+fn conf_get_long_value() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	// Longer than the buffer conf_get() starts with
+	let path = format!("/tmp/{}.log", "x".repeat(1000));
+	cluster.conf_set("log_file", &path).unwrap();
+	assert_eq!(cluster.conf_get("log_file").unwrap(), path);
+}
+
+#[test]
+fn fsid_is_a_uuid() {
+	let cluster = common::cluster();
+	let fsid = cluster.fsid().unwrap();
+	assert_eq!(fsid.len(), 36, "{}", fsid);
+	assert_eq!(fsid.matches('-').count(), 4, "{}", fsid);
+}
+
+#[test]
+fn features_match_library_version() {
+	let features = ceph::features::available();
+	assert_eq!(features.version, ceph::rados::library_version());
+	let version = features.version.expect("no librados version");
+	assert!(features.watch2 && features.nobjects_list);
+	assert_eq!(features.application_enable, version.major >= 3);
+}
+
+#[test]
+fn export_import_round_trip() {
+	let cluster = common::cluster();
+	let source = common::ioctx(&cluster);
+	source.set_namespace(Some(&common::oid("export"))).unwrap();
+	let mut op = WriteOp::new();
+	op.write_full(b"first data").setxattr("user.kind", b"first").omap_set(&[("key", &b"value"[..])]);
+	source.operate("first", &op).unwrap();
+	source.write_at("second", b"second data", 0).unwrap();
+
+	let mut stream = Vec::new();
+	let written = source.export(&mut stream).unwrap();
+	assert_eq!(written as usize, stream.len());
+
+	let target = common::ioctx(&cluster);
+	target.set_namespace(Some(&common::oid("import"))).unwrap();
+	assert_eq!(target.import(&stream[..]).unwrap(), 2);
+	assert_eq!(target.read_full("first").unwrap(), b"first data");
+	assert_eq!(target.getxattrs("first").unwrap()["user.kind"], b"first");
+	assert_eq!(target.export_omap("first").unwrap(), [("key".to_string(), b"value".to_vec())]);
+	assert_eq!(target.read_full("second").unwrap(), b"second data");
+	for ioctx in &[&source, &target] {
+		ioctx.remove("first").unwrap();
+		ioctx.remove("second").unwrap();
+	}
+}
+
+#[test]
+fn list_snaps() {
+	let cluster = common::cluster();
+	// Pool snapshots can't be taken in a pool with self-managed ones, so
+	// these go to the second pool
+	let pool = common::other_pool();
+	let ioctx = cluster.create_ioctx(pool.as_str()).unwrap();
+	let names: Vec<String> = (0..3).map(|i| common::oid(&format!("snap{}", i))).collect();
+	for name in &names {
+		let cmd = format!(r#"{{"prefix": "osd pool mksnap", "pool": "{}", "snap": "{}"}}"#, pool, name);
+		cluster.mon_command(&cmd).unwrap();
+	}
+	let snaps: Vec<_> = ioctx.list_snaps().unwrap().into_iter().filter(|snap| names.contains(&snap.name)).collect();
+	assert_eq!(snaps.iter().map(|snap| &snap.name).collect::<Vec<_>>(), names.iter().collect::<Vec<_>>());
+	assert!(snaps.windows(2).all(|pair| pair[0].id < pair[1].id));
+	for name in &names {
+		let cmd = format!(r#"{{"prefix": "osd pool rmsnap", "pool": "{}", "snap": "{}"}}"#, pool, name);
+		cluster.mon_command(&cmd).unwrap();
+	}
+}
+
+#[test]
+fn write_when_healthy() {
+	let cluster = common::cluster();
+	assert_ne!(cluster.health().unwrap(), HealthStatus::Err);
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("healthy");
+	ioctx.write_when_healthy(&oid, b"first").unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"first");
+	// Too long to count in milliseconds: the health is cached for good
+	ioctx.set_health_ttl(Duration::new(u64::MAX, 0));
+	ioctx.write_when_healthy(&oid, b"second").unwrap();
+	ioctx.set_health_ttl(Duration::from_secs(0));
+	ioctx.write_when_healthy(&oid, b"third").unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"third");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn object_lock() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("locked");
+	ioctx.write_at(&oid, b"x", 0).unwrap();
+	assert!(ObjectLock::info(&ioctx, &oid, "writer").unwrap().lockers.is_empty());
+	{
+		let lock = ObjectLock::exclusive(&ioctx, &oid, "writer", None).unwrap();
+		let info = ObjectLock::info(&ioctx, &oid, "writer").unwrap();
+		assert!(info.exclusive);
+		assert_eq!(info.lockers.len(), 1);
+		assert_eq!(info.lockers[0].cookie, lock.cookie());
+		assert!(info.lockers[0].entity.starts_with("client."), "{}", info.lockers[0].entity);
+		assert_eq!(info.lockers[0].expiration, None);
+		// Another holder, even in the same process, is refused
+		assert_eq!(ObjectLock::exclusive(&ioctx, &oid, "writer", None).err(), Some(RadosError::Other(libc::EBUSY)));
+	} // unlocked here
+	assert!(ObjectLock::info(&ioctx, &oid, "writer").unwrap().lockers.is_empty());
+	ObjectLock::exclusive(&ioctx, &oid, "writer", Some(Duration::from_secs(60))).unwrap().unlock().unwrap();
+	assert_eq!(ObjectLock::info(&ioctx, &common::oid("unlocked"), "writer").unwrap_err(), RadosError::ObjectNotFound);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn instance_id() {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_read_file(None).unwrap();
+	assert_eq!(cluster.instance_id(), 0);
+	cluster.connect().unwrap();
+	assert_ne!(cluster.instance_id(), 0);
+	// Each connection is a client instance of its own
+	assert_ne!(common::cluster().instance_id(), cluster.instance_id());
+}
+
+#[test]
+fn pool_type() {
+	let cluster = common::cluster();
+	assert_eq!(common::ioctx(&cluster).pool_type().unwrap(), PoolType::Replicated);
+	let pool = common::TempPool::new(&cluster, "erasure", 8, PoolType::Erasure);
+	let ioctx = cluster.create_ioctx(pool.name.as_str()).unwrap();
+	assert_eq!(ioctx.pool_type().unwrap(), PoolType::Erasure);
+}
+
+#[cfg(feature = "nautilus")]
+#[link(name = "rados")]
+extern "C" {
+	fn rados_ioctx_create(cluster: *const libc::c_void, pool_name: *const libc::c_char, ioctx: *mut *const libc::c_void) -> libc::c_int;
+	fn rados_ioctx_destroy(io: *const libc::c_void);
+	fn rados_unwatch2(io: *const libc::c_void, cookie: u64) -> libc::c_int;
+	fn rados_notify2(io: *const libc::c_void, oid: *const libc::c_char, buf: *const libc::c_char, buf_len: libc::c_int,
+		timeout_ms: u64, reply_buffer: *mut *mut libc::c_char, reply_buffer_len: *mut libc::size_t) -> libc::c_int;
+	fn rados_buffer_free(buf: *mut libc::c_char);
+}
+
+/// Only with Nautilus, as older librados crash on watch handles that were
+/// already removed
+#[cfg(feature = "nautilus")]
+#[test]
+fn resilient_watch_survives_unwatch() {
+	use std::ffi::CString;
+	use std::ptr;
+	use std::sync::mpsc;
+
+	// A context of our own on the same handle, to remove and notify the
+	// watch behind ResilientWatch's back
+	let raw = common::cluster().into_raw();
+	let cluster = unsafe { Cluster::from_raw(raw) };
+	let pool = CString::new(common::pool()).unwrap();
+	let mut io = ptr::null();
+	assert_eq!(unsafe { rados_ioctx_create(raw, pool.as_ptr(), &mut io) }, 0);
+	let notify = |oid: &CString, payload: &[u8]| {
+		let (mut reply, mut reply_len) = (ptr::null_mut(), 0);
+		let err = unsafe {
+			rados_notify2(io, oid.as_ptr(), payload.as_ptr() as *const libc::c_char, payload.len() as libc::c_int,
+				10000, &mut reply, &mut reply_len)
+		};
+		if !reply.is_null() {
+			unsafe { rados_buffer_free(reply) };
+		}
+		assert_eq!(err, 0);
+	};
+
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("watched");
+	let oid_cs = CString::new(oid.as_str()).unwrap();
+	ioctx.write_at(&oid, b"x", 0).unwrap();
+	let (tx, rx) = mpsc::channel();
+	let tx = ::std::sync::Mutex::new(tx);
+	let mut reconnects = Vec::new();
+	{
+		let mut watch = ResilientWatch::new(&ioctx, &oid,
+			move |data| tx.lock().unwrap().send(data.to_vec()).unwrap(),
+			|err| reconnects.push(err.clone())).unwrap();
+		notify(&oid_cs, b"first");
+		assert_eq!(rx.recv_timeout(Duration::from_secs(10)).unwrap(), b"first");
+		assert!(!watch.check().unwrap());
+
+		assert_eq!(unsafe { rados_unwatch2(io, watch.cookie()) }, 0);
+		assert!(watch.check().unwrap());
+		assert!(!watch.check().unwrap());
+		notify(&oid_cs, b"second");
+		assert_eq!(rx.recv_timeout(Duration::from_secs(10)).unwrap(), b"second");
+	}
+	assert_eq!(reconnects.len(), 1);
+	ioctx.remove(&oid).unwrap();
+	unsafe { rados_ioctx_destroy(io) };
+}
+
+#[test]
+fn check_space() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	assert!(ioctx.check_space(1).unwrap());
+	assert!(!ioctx.check_space(u64::MAX).unwrap());
+}
+
+#[test]
+fn create_ioctx_missing_pool() {
+	let cluster = common::cluster();
+	assert_eq!(cluster.create_ioctx(common::oid("no-such-pool")).err(), Some(RadosError::PoolNotFound));
+}
+
+#[test]
+fn min_compat_client() {
+	let cluster = common::cluster();
+	let release = cluster.min_compat_client().unwrap();
+	let known = ["argonaut", "bobtail", "cuttlefish", "dumpling", "emperor", "firefly", "giant", "hammer",
+		"infernalis", "jewel", "kraken", "luminous", "mimic", "nautilus", "octopus", "pacific", "quincy", "reef", "squid"];
+	assert!(known.contains(&release.as_str()), "{}", release);
+}
+
+#[test]
+fn create_pool_with_pg_num() {
+	let cluster = common::cluster();
+	let pool = common::TempPool::new(&cluster, "pg-num", 16, PoolType::Replicated);
+	let cmd = format!(r#"{{"prefix": "osd pool get", "pool": "{}", "var": "pg_num", "format": "json"}}"#, pool.name);
+	let reply: serde_json::Value = serde_json::from_slice(&cluster.mon_command(&cmd).unwrap()).unwrap();
+	assert_eq!(reply["pg_num"], 16);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn list_objects_stream_in_namespace() {
+	use futures::StreamExt;
+
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.set_namespace(Some(&common::oid("stream"))).unwrap();
+	for i in 0..20 {
+		ioctx.write_at(&format!("object{}", i), b"x", 0).unwrap();
+	}
+	let mut streamed: Vec<String> = futures::executor::block_on(ioctx.list_objects_stream().collect::<Vec<_>>())
+		.into_iter().map(|oid| oid.unwrap()).collect();
+	let mut listed: Vec<String> = ioctx.list_objects().unwrap().map(|oid| oid.unwrap()).collect();
+	streamed.sort();
+	listed.sort();
+	assert_eq!(streamed.len(), 20);
+	assert_eq!(streamed, listed);
+	for oid in &listed {
+		ioctx.remove(oid).unwrap();
+	}
+}
+
+#[test]
+fn namespace() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	assert_eq!(ioctx.namespace().unwrap(), "");
+	let nspace = common::oid("x".repeat(100).as_str());
+	ioctx.set_namespace(Some(&nspace)).unwrap();
+	assert_eq!(ioctx.namespace().unwrap(), nspace);
+	assert_eq!(ioctx.try_clone().unwrap().namespace().unwrap(), "");
+	ioctx.set_namespace(None).unwrap();
+	assert_eq!(ioctx.namespace().unwrap(), "");
+}
+
+#[test]
+fn pool_quota() {
+	let cluster = common::cluster();
+	let pool = common::TempPool::new(&cluster, "quota", 8, PoolType::Replicated);
+	assert_eq!(cluster.get_pool_quota(&pool.name).unwrap(), PoolQuota { max_bytes: None, max_objects: None });
+	cluster.set_pool_quota(&pool.name, Some(10 * 1024 * 1024), None).unwrap();
+	assert_eq!(cluster.get_pool_quota(&pool.name).unwrap(), PoolQuota { max_bytes: Some(10 * 1024 * 1024), max_objects: None });
+	cluster.set_pool_quota(&pool.name, None, Some(100)).unwrap();
+	assert_eq!(cluster.get_pool_quota(&pool.name).unwrap(), PoolQuota { max_bytes: Some(10 * 1024 * 1024), max_objects: Some(100) });
+	assert_eq!(cluster.set_pool_quota(&pool.name, None, None).unwrap_err(), RadosError::Other(libc::EINVAL));
+}
+
+/// The key is taken from `CEPH_TEST_KEY`, or else asked for with the
+/// default keyring
+#[test]
+fn connect_with_key() {
+	let key = env::var("CEPH_TEST_KEY").unwrap_or_else(|_| {
+		let out = common::cluster().mon_command(r#"{"prefix": "auth get-key", "entity": "client.admin"}"#).unwrap();
+		String::from_utf8(out).unwrap().trim().to_string()
+	});
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_read_file(None).unwrap();
+	// Only the key may authenticate
+	cluster.set_keyring("/dev/null").unwrap();
+	cluster.set_key(&key).unwrap();
+	cluster.connect().unwrap();
+	cluster.create_ioctx(common::pool()).unwrap();
+}
+
+#[test]
+fn pool_layout() {
+	let cluster = common::cluster();
+	let layout = common::ioctx(&cluster).pool_layout().unwrap();
+	assert!(layout.pg_num > 0, "{:?}", layout);
+	assert!(layout.pgp_num > 0 && layout.pgp_num <= layout.pg_num, "{:?}", layout);
+	assert!(layout.size > 0, "{:?}", layout);
+	assert!(layout.min_size > 0 && layout.min_size <= layout.size, "{:?}", layout);
+}
+
+#[test]
+fn osd_count() {
+	let cluster = common::cluster();
+	let (total, up) = cluster.osd_count().unwrap();
+	assert!(up > 0, "{} of {} up", up, total);
+	assert!(total >= up, "{} of {} up", up, total);
+}
+
+#[test]
+fn raw_handle_round_trip() {
+	let raw = common::cluster().into_raw();
+	let cluster = unsafe { Cluster::from_raw(raw) };
+	assert!(cluster.is_connected());
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("raw");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	assert_eq!(cluster.into_raw(), raw);
+	// Still open, as into_raw() didn't shut the handle down
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	ioctx.remove(&oid).unwrap();
+	drop(ioctx);
+
+	// A borrowed handle isn't shut down by its borrower
+	let borrowed = unsafe { Cluster::from_raw(raw) };
+	borrowed.forget_on_drop();
+	borrowed.shutdown();
+	assert!(borrowed.is_connected());
+	borrowed.create_ioctx(common::pool()).unwrap();
+	borrowed.close().unwrap();
+
+	// Its owner shuts it down, once
+	let owner = unsafe { Cluster::from_raw(raw) };
+	owner.create_ioctx(common::pool()).unwrap();
+	owner.shutdown();
+	assert!(!owner.is_connected());
+}
@@ -0,0 +1,64 @@
+//! Helpers for the tests that run against a cluster, see the `integration`
+//! feature.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ceph::rados::{Cluster, CreateFlags, IoCtx, PoolType};
+
+/// Makes every object name handed out by `oid()` unique
+static NEXT_OID: AtomicUsize = AtomicUsize::new(0);
+
+/// Connect to the cluster as `client.admin`
+pub fn cluster() -> Cluster {
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	cluster.conf_read_file(None).unwrap();
+	cluster.connect().unwrap();
+	cluster
+}
+
+/// The name of the pool the tests write to
+pub fn pool() -> String {
+	env::var("CEPH_TEST_POOL").unwrap_or_else(|_| "rbd".to_string())
+}
+
+/// The name of a second pool, for tests spanning pools
+pub fn other_pool() -> String {
+	env::var("CEPH_TEST_POOL2").unwrap_or_else(|_| format!("{}-2", pool()))
+}
+
+/// Open the test pool
+pub fn ioctx(cluster: &Cluster) -> IoCtx {
+	cluster.create_ioctx(pool()).unwrap()
+}
+
+/// A name no other test, or run of the tests, uses
+pub fn oid(name: &str) -> String {
+	format!("ceph-rs-test.{}.{}.{}", process::id(), NEXT_OID.fetch_add(1, Ordering::SeqCst), name)
+}
+
+/// A pool created for a single test, removed again when dropped, as far
+/// as the monitors allow it (`mon_allow_pool_delete`)
+pub struct TempPool<'a> {
+	cluster: &'a Cluster,
+	pub name: String,
+}
+
+impl<'a> TempPool<'a> {
+	pub fn new(cluster: &'a Cluster, name: &str, pg_num: u32, pool_type: PoolType) -> TempPool<'a> {
+		let name = oid(name);
+		cluster.create_pool_with(&name, pg_num, None, pool_type).unwrap();
+		TempPool { cluster, name }
+	}
+}
+
+impl<'a> Drop for TempPool<'a> {
+	fn drop(&mut self) {
+		// Luminous wants `sure`, later releases the flag
+		let cmd = format!(r#"{{"prefix": "osd pool delete", "pool": "{0}", "pool2": "{0}", "sure": "--yes-i-really-really-mean-it", "yes_i_really_really_mean_it": true}}"#, self.name);
+		let _ = self.cluster.mon_command(&cmd);
+	}
+}
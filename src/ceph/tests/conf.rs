@@ -0,0 +1,58 @@
+//! Tests of the search for configuration files, only built with the
+//! `integration` feature. They're kept apart from the other tests as they
+//! change the environment.
+
+#![cfg(feature = "integration")]
+
+extern crate ceph;
+
+use std::env;
+use std::fs;
+use std::process;
+
+use ceph::rados::{Cluster, CreateFlags};
+
+#[test]
+fn conf_read_file_finds_working_directory_file() {
+	// A cluster name of our own, so that no `/etc/ceph/$cluster.conf` is
+	// found ahead of the file in the working directory
+	let name = format!("ceph-rs-test-{}", process::id());
+	let file = format!("{}.conf", name);
+	fs::write(&file, "[global]\nmon_host = 127.0.0.1\n").unwrap();
+	env::remove_var("CEPH_CONF");
+
+	let cluster = Cluster::create(name.as_str(), "client.admin", CreateFlags::empty()).unwrap();
+	let path = cluster.conf_read_file(None);
+	fs::remove_file(&file).unwrap();
+	let path = path.unwrap().expect("the configuration file wasn't found");
+	assert_eq!(path.file_name().unwrap(), file.as_str());
+	assert_eq!(path.canonicalize().unwrap().parent(), Some(env::current_dir().unwrap().canonicalize().unwrap().as_path()));
+	assert_eq!(cluster.conf_get("mon_host").unwrap(), "127.0.0.1");
+}
+
+#[test]
+fn conf_read_files_later_files_win() {
+	let dir = env::temp_dir();
+	let base = dir.join(format!("ceph-rs-test-{}-base.conf", process::id()));
+	let local = dir.join(format!("ceph-rs-test-{}-local.conf", process::id()));
+	fs::write(&base, "[global]\nmon_host = 127.0.0.1\nrados_osd_op_timeout = 10\n").unwrap();
+	fs::write(&local, "[global]\nrados_osd_op_timeout = 20\n").unwrap();
+	let (base, local) = (base.to_str().unwrap().to_string(), local.to_str().unwrap().to_string());
+	let missing = dir.join("ceph-rs-test-missing.conf").to_str().unwrap().to_string();
+
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	let read = cluster.conf_read_files(&[&base, &local]);
+	let failed = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap();
+	let err = failed.conf_read_files(&[&local, &missing, &base]);
+	fs::remove_file(&base).unwrap();
+	fs::remove_file(&local).unwrap();
+
+	read.unwrap();
+	assert_eq!(cluster.conf_get("rados_osd_op_timeout").unwrap(), "20");
+	// Not overridden
+	assert_eq!(cluster.conf_get("mon_host").unwrap(), "127.0.0.1");
+	// Files before the missing one apply, the ones after don't
+	assert_eq!(err.unwrap_err().0, missing);
+	assert_eq!(failed.conf_get("rados_osd_op_timeout").unwrap(), "20");
+	assert_ne!(failed.conf_get("mon_host").unwrap(), "127.0.0.1");
+}
@@ -0,0 +1,154 @@
+//! Arguments librados can't take, fed to the public methods against a
+//! running cluster, only built with the `integration` feature. Each call
+//! must fail with `EINVAL` rather than panic, and leave nothing behind.
+
+#![cfg(feature = "integration")]
+
+extern crate ceph;
+extern crate libc;
+
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "luminous")]
+use ceph::rados::ChecksumType;
+use ceph::rados::{RadosError, ReadFlags, WriteFlags, WriteMode, WriteOp};
+
+/// Names with a NUL byte in them, which can't be passed to librados
+const BAD_NAMES: &[&str] = &["\0", "\0leading", "trailing\0", "in\0between"];
+
+/// Assert each of the calls fails with `EINVAL`
+macro_rules! assert_einval {
+	($($call:expr;)+) => {
+		$(assert_eq!($call.err(), Some(RadosError::Other(libc::EINVAL)), "{}", stringify!($call));)+
+	}
+}
+
+#[test]
+fn nul_in_oids() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	ioctx.set_namespace(Some(&common::oid("nul-oids"))).unwrap();
+	for &oid in BAD_NAMES {
+		let mut buf = [0u8; 4];
+		let mut sink = Vec::new();
+		assert_einval! {
+			ioctx.write(oid, "data");
+			ioctx.write_at(oid, b"data", 0);
+			ioctx.write_with_flags(oid, b"data", 0, WriteFlags::empty());
+			ioctx.write_when_healthy(oid, b"data");
+			ioctx.write_record(oid, b"data", SystemTime::now());
+			ioctx.put(oid, b"data", WriteMode::CreateOrReplace);
+			ioctx.write_immutable(oid, b"data");
+			ioctx.replace(oid, b"data");
+			ioctx.append_create(oid, b"data");
+			ioctx.append_capped(oid, b"data", 100);
+			ioctx.write_if_smaller_than(oid, b"data", 100);
+			ioctx.create_full(oid, b"data", false, None);
+			ioctx.create_or_truncate(oid);
+			ioctx.zero(oid, 0, 4);
+			ioctx.aio_write_safe(oid, b"data", 0);
+			ioctx.upload(oid, &b"data"[..], 0);
+			ioctx.operate(oid, &WriteOp::new());
+			ioctx.sync(oid);
+			ioctx.read(oid, 4);
+			ioctx.read_at(oid, &mut buf, 0);
+			ioctx.read_exact_into(oid, 0, &mut buf);
+			ioctx.read_with_flags(oid, &mut buf, 0, ReadFlags::empty());
+			ioctx.read_full(oid);
+			ioctx.aio_read(oid, 4, 0);
+			ioctx.download(oid, &mut sink, 0);
+			ioctx.stat(oid);
+			ioctx.object_meta(oid);
+			ioctx.fetch(oid);
+			ioctx.read_if_changed(oid, 0);
+			ioctx.wait_for_size(oid, 1, Duration::from_secs(1));
+			ioctx.getxattr(oid, "user.name", 16);
+			ioctx.setxattr(oid, "user.name", "value");
+			ioctx.getxattrs(oid);
+			ioctx.getxattrs_by_name(oid, &["user.name"]);
+			ioctx.setxattrs(oid, &[("user.name", &b"value"[..])]);
+			ioctx.omap_clear(oid);
+			ioctx.export_omap(oid);
+			ioctx.import_omap(oid, &[("key", &b"value"[..])]);
+			ioctx.incr(oid, "user.count", 1);
+			ioctx.exec(oid, "lock", "get_info", b"");
+			ioctx.rename(oid, "renamed");
+			ioctx.clone_object(oid, "cloned");
+			ioctx.remove(oid);
+			ioctx.object(oid).read();
+			ioctx.object(oid).write(b"data");
+		}
+		#[cfg(feature = "luminous")]
+		assert_einval! {
+			ioctx.checksum(oid, ChecksumType::Crc32c, 0, 0);
+		}
+	}
+	// Not even the part before the NUL was written to
+	assert_eq!(ioctx.list_objects().unwrap().count(), 0);
+}
+
+#[test]
+fn nul_in_xattr_names_and_omap_keys() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("nul-names");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	for &name in BAD_NAMES {
+		let mut op = WriteOp::new();
+		op.setxattr(name, b"value");
+		let mut omap_op = WriteOp::new();
+		omap_op.omap_set(&[(name, &b"value"[..])]);
+		assert_einval! {
+			ioctx.getxattr(oid.as_str(), name, 16);
+			ioctx.setxattr(oid.as_str(), name, "value");
+			ioctx.setxattrs(&oid, &[("user.fine", &b"value"[..]), (name, &b"value"[..])]);
+			ioctx.replace_with_xattrs(&oid, b"new data", &[(name, &b"value"[..])]);
+			ioctx.operate(&oid, &op);
+			ioctx.incr(&oid, name, 1);
+			ioctx.import_omap(&oid, &[("fine", &b"value"[..]), (name, &b"value"[..])]);
+			ioctx.operate(&oid, &omap_op);
+			ioctx.exec(&oid, name, "get_info", b"");
+			ioctx.exec(&oid, "lock", name, b"");
+			ioctx.object(&oid).setxattr(name, "value");
+			ioctx.object(&oid).getxattr(name, 16);
+		}
+	}
+	// Nothing of the rejected operations was applied
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	assert!(ioctx.getxattrs(&oid).unwrap().is_empty());
+	assert!(ioctx.export_omap(&oid).unwrap().is_empty());
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn nul_in_pool_names_and_options() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	for &name in BAD_NAMES {
+		assert_einval! {
+			cluster.create_ioctx(name);
+			cluster.create_pool(name);
+			cluster.conf_get(name);
+			cluster.conf_set(name, "value");
+			cluster.conf_set("rados_osd_op_timeout", name);
+			ioctx.set_namespace(Some(name));
+		}
+	}
+}
+
+#[test]
+fn durations_past_any_limit() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let forever = Duration::new(u64::MAX, 999999999);
+	ioctx.set_op_timeout(forever);
+	assert!(ioctx.op_timeout().is_some());
+	ioctx.set_health_ttl(forever);
+	let oid = common::oid("forever");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	ioctx.write_when_healthy(&oid, b"data").unwrap();
+	assert_eq!(ioctx.wait_for_size(&oid, 4, forever).unwrap(), 4);
+	ioctx.remove(&oid).unwrap();
+}
@@ -0,0 +1,905 @@
+//! Round trips of object data through a running cluster, only built with
+//! the `integration` feature.
+
+#![cfg(feature = "integration")]
+
+#[cfg(feature = "bytes")]
+extern crate bytes;
+extern crate ceph;
+extern crate libc;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+
+mod common;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use ceph::crc32c;
+#[cfg(feature = "luminous")]
+use ceph::rados::ChecksumType;
+use ceph::rados::{NamespaceStats, RadosError, ReadFlags, WriteFlags, WriteMode, WriteOp, RECORD_CRC_XATTR};
+use ceph::record_file::RecordFile;
+
+#[test]
+fn append_create_from_many_threads() {
+	let cluster = common::cluster();
+	let ioctx = Arc::new(common::ioctx(&cluster));
+	let oid = common::oid("log");
+	let threads: Vec<_> = (0..8).map(|i| {
+		let (ioctx, oid) = (ioctx.clone(), oid.clone());
+		thread::spawn(move || {
+			let line = format!("writer {} was here\n", i);
+			for _ in 0..10 {
+				ioctx.append_create(&oid, line.as_bytes()).unwrap();
+			}
+			line.len() as u64 * 10
+		})
+	}).collect();
+	let expected: u64 = threads.into_iter().map(|t| t.join().unwrap()).sum();
+	assert_eq!(ioctx.stat(&oid).unwrap().0, expected);
+	ioctx.remove(&oid).unwrap();
+}
+
+/// `len` bytes that differ from one offset to the next
+fn pattern(len: usize) -> Vec<u8> {
+	(0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+#[test]
+fn upload_file() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("upload");
+	let data = pattern(5 * 1024 * 1024);
+	let path = env::temp_dir().join(&oid);
+	fs::write(&path, &data).unwrap();
+	let written = ioctx.upload(&oid, File::open(&path).unwrap(), 1024 * 1024);
+	fs::remove_file(&path).unwrap();
+	assert_eq!(written.unwrap(), data.len() as u64);
+	assert_eq!(ioctx.stat(&oid).unwrap().0, data.len() as u64);
+	let mut sample = vec![0u8; 4096];
+	let offset = 3 * 1024 * 1024 - 2048;
+	assert_eq!(ioctx.read_at(&oid, &mut sample, offset as u64).unwrap(), sample.len());
+	assert_eq!(sample, &data[offset..offset + 4096]);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn upload_then_download() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("download");
+	let data = pattern(3 * 1024 * 1024);
+	ioctx.upload(&oid, &data[..], 1024 * 1024).unwrap();
+	let mut out = Vec::new();
+	// A chunk size that doesn't divide the object
+	assert_eq!(ioctx.download(&oid, &mut out, 700 * 1024).unwrap(), data.len() as u64);
+	assert!(out == data);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn zero_middle() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("zero");
+	let mut data = vec![0xffu8; 1024 * 1024];
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	ioctx.zero(&oid, 384 * 1024, 256 * 1024).unwrap();
+	for b in &mut data[384 * 1024..640 * 1024] {
+		*b = 0;
+	}
+	assert_eq!(ioctx.stat(&oid).unwrap().0, data.len() as u64);
+	assert!(ioctx.read_full(&oid).unwrap() == data);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn read_many_with_missing() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oids: Vec<String> = ["one", "two", "three", "missing"].iter().map(|name| common::oid(name)).collect();
+	for oid in &oids[..3] {
+		ioctx.write_at(oid, oid.as_bytes(), 0).unwrap();
+	}
+	let names: Vec<&str> = oids.iter().map(|oid| oid.as_str()).collect();
+	let results = ioctx.read_many(&names);
+	assert_eq!(results.len(), 4);
+	for ((oid, result), name) in results[..3].iter().zip(&names) {
+		assert_eq!(oid, name);
+		assert_eq!(result.as_ref().unwrap(), oid.as_bytes());
+	}
+	assert_eq!(results[3], (oids[3].clone(), Err(RadosError::ObjectNotFound)));
+	for oid in &oids[..3] {
+		ioctx.remove(oid).unwrap();
+	}
+}
+
+#[test]
+fn object_handle_round_trips() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("handle");
+	let object = ioctx.object(&oid);
+	assert_eq!(object.oid(), oid);
+	object.write(b"Hello, ").unwrap();
+	object.append(b"world.").unwrap();
+	assert_eq!(object.read().unwrap(), b"Hello, world.");
+	assert_eq!(object.stat().unwrap().0, 13);
+	object.setxattr("user.greeting", "hello").unwrap();
+	assert_eq!(object.getxattr("user.greeting", 1).unwrap(), "hello");
+	assert_eq!(object.getxattr("user.missing", 8).unwrap_err(), RadosError::AttrNotFound);
+	object.write(b"Bye.").unwrap();
+	assert_eq!(object.fetch().unwrap().data, b"Bye.");
+	object.remove().unwrap();
+	assert_eq!(object.read().unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn write_with_fadvise_flags() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("write-flags");
+	let data = pattern(64 * 1024);
+	ioctx.write_with_flags(&oid, &data, 0, WriteFlags::FADVISE_DONTNEED).unwrap();
+	ioctx.write_with_flags(&oid, b"tail", data.len() as u64, WriteFlags::FADVISE_SEQUENTIAL | WriteFlags::FADVISE_NOCACHE).unwrap();
+	let read = ioctx.read_full(&oid).unwrap();
+	assert!(read[..data.len()] == data[..]);
+	assert_eq!(&read[data.len()..], b"tail");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn read_with_fadvise_flags() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("read-flags");
+	let data = pattern(16 * 1024);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	let mut buf = vec![0u8; data.len()];
+	assert_eq!(ioctx.read_with_flags(&oid, &mut buf, 0, ReadFlags::FADVISE_NOCACHE).unwrap(), data.len());
+	assert!(buf == data);
+	let mut tail = [0u8; 100];
+	assert_eq!(ioctx.read_with_flags(&oid, &mut tail, data.len() as u64 - 10, ReadFlags::FADVISE_SEQUENTIAL).unwrap(), 10);
+	assert_eq!(&tail[..10], &data[data.len() - 10..]);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn rename_keeps_data_and_xattrs() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let (src, dst) = (common::oid("rename-src"), common::oid("rename-dst"));
+	ioctx.write_at(&src, b"moving", 0).unwrap();
+	ioctx.setxattr(src.as_str(), "user.owner", "alice").unwrap();
+	ioctx.rename(&src, &dst).unwrap();
+	assert_eq!(ioctx.read_full(&dst).unwrap(), b"moving");
+	assert_eq!(ioctx.getxattrs(&dst).unwrap()["user.owner"], b"alice");
+	assert_eq!(ioctx.stat(&src).unwrap_err(), RadosError::ObjectNotFound);
+	assert_eq!(ioctx.rename(&src, &dst).unwrap_err(), RadosError::ObjectNotFound);
+	assert_eq!(ioctx.read_full(&dst).unwrap(), b"moving");
+	ioctx.remove(&dst).unwrap();
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_round_trip() {
+	use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("bytes");
+	let mut head = BytesMut::new();
+	head.put_slice(b"Hello, ");
+	// Written chunk by chunk
+	let buf = head.freeze().chain(Bytes::from_static(b"world."));
+	assert_eq!(ioctx.write_bytes_buf(&oid, buf).unwrap(), 13);
+	assert_eq!(ioctx.read_bytes(&oid, 64, 0).unwrap(), Bytes::from_static(b"Hello, world."));
+	assert_eq!(ioctx.read_bytes(&oid, 5, 7).unwrap(), Bytes::from_static(b"world"));
+	// Shorter content replaces it whole
+	assert_eq!(ioctx.write_bytes_buf(&oid, Bytes::from_static(b"Bye.")).unwrap(), 4);
+	assert_eq!(ioctx.read_bytes(&oid, 64, 0).unwrap(), Bytes::from_static(b"Bye."));
+	ioctx.remove(&oid).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Account {
+	name: String,
+	address: Address,
+	logins: Vec<u64>,
+	manager: Option<Box<Account>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Address {
+	city: String,
+	zip: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn value_round_trip() {
+	use ceph::rados::SerdeFormat;
+
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("value");
+	let manager = Account {
+		name: "bob".to_string(),
+		address: Address { city: "Paris".to_string(), zip: None },
+		logins: Vec::new(),
+		manager: None,
+	};
+	let account = Account {
+		name: "alice".to_string(),
+		address: Address { city: "Lyon".to_string(), zip: Some(69001) },
+		logins: vec![1451606400, 1451692800],
+		manager: Some(Box::new(manager)),
+	};
+	for &format in &[SerdeFormat::Json, SerdeFormat::Bincode] {
+		ioctx.write_value(&oid, &account, format).unwrap();
+		assert_eq!(ioctx.read_value::<Account>(&oid, format).unwrap(), account);
+	}
+	ioctx.write_at(&oid, b"not json", 0).unwrap();
+	match ioctx.read_value::<Account>(&oid, SerdeFormat::Json) {
+		Err(RadosError::Serialization(_)) => {},
+		other => panic!("{:?}", other),
+	}
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn replace_shrinks_object() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("replace");
+	ioctx.write_at(&oid, &pattern(100 * 1024), 0).unwrap();
+	ioctx.replace(&oid, b"small").unwrap();
+	assert_eq!(ioctx.stat(&oid).unwrap().0, 5);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"small");
+	ioctx.replace_with_xattrs(&oid, b"tiny", &[("user.version", &b"2"[..])]).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"tiny");
+	assert_eq!(ioctx.getxattrs(&oid).unwrap()["user.version"], b"2");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn transfer_progress() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("progress");
+	let data = pattern(2 * 1024 * 1024 + 1000);
+	let mut uploaded = Vec::new();
+	ioctx.upload_with_progress(&oid, &data[..], 1024 * 1024, |done, total| uploaded.push((done, total))).unwrap();
+	assert_eq!(uploaded, [(1024 * 1024, None), (2 * 1024 * 1024, None), (data.len() as u64, None)]);
+	let mut downloaded = Vec::new();
+	let mut out = Vec::new();
+	ioctx.download_with_progress(&oid, &mut out, 1024 * 1024, |done, total| downloaded.push((done, total))).unwrap();
+	let total = Some(data.len() as u64);
+	assert_eq!(downloaded, [(1024 * 1024, total), (2 * 1024 * 1024, total), (data.len() as u64, total)]);
+	assert!(out == data);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn put_modes() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("put");
+	assert_eq!(ioctx.put(&oid, b"v0", WriteMode::ReplaceExisting).unwrap_err(), RadosError::ObjectNotFound);
+	assert_eq!(ioctx.stat(&oid).unwrap_err(), RadosError::ObjectNotFound);
+	ioctx.put(&oid, b"v1", WriteMode::CreateNew).unwrap();
+	assert_eq!(ioctx.put(&oid, b"v2", WriteMode::CreateNew).unwrap_err(), RadosError::AlreadyExists);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"v1");
+	ioctx.put(&oid, b"v3", WriteMode::ReplaceExisting).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"v3");
+	ioctx.put(&oid, b"v4", WriteMode::CreateOrReplace).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"v4");
+	ioctx.remove(&oid).unwrap();
+	ioctx.put(&oid, b"v5", WriteMode::CreateOrReplace).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"v5");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn list_objects_with_size() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	// A namespace of its own, so only this test's objects are listed
+	ioctx.set_namespace(Some(&common::oid("sizes"))).unwrap();
+	// More objects than stats are kept in flight
+	let mut expected: Vec<(String, u64)> = (0..40).map(|i| (format!("object-{}", i), i * 100)).collect();
+	for &(ref name, size) in &expected {
+		ioctx.put(name, &pattern(size as usize), WriteMode::CreateOrReplace).unwrap();
+	}
+	let mut listed: Vec<(String, u64)> = ioctx.list_objects_with_size().map(|entry| entry.unwrap()).collect();
+	listed.sort();
+	expected.sort();
+	assert_eq!(listed, expected);
+	for (name, _) in &expected {
+		ioctx.remove(name).unwrap();
+	}
+}
+
+#[test]
+fn aio_write_safe_waits() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("aio-safe");
+	let completion = ioctx.aio_write_safe(&oid, b"on disk", 0).unwrap();
+	completion.wait_complete().unwrap();
+	completion.wait_safe().unwrap();
+	assert!(completion.is_complete());
+	drop(completion);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"on disk");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn read_if_changed() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("if-changed");
+	ioctx.write_at(&oid, b"first", 0).unwrap();
+	let (version, data) = ioctx.read_if_changed(&oid, 0).unwrap().unwrap();
+	assert_eq!(data, b"first");
+	assert_eq!(ioctx.read_if_changed(&oid, version).unwrap(), None);
+	ioctx.write_at(&oid, b"F", 0).unwrap();
+	let (new_version, data) = ioctx.read_if_changed(&oid, version).unwrap().unwrap();
+	assert!(new_version > version);
+	assert_eq!(data, b"First");
+	assert_eq!(ioctx.read_if_changed(&oid, new_version).unwrap(), None);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn object_meta_version_changes_within_a_second() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("meta");
+	ioctx.write_at(&oid, b"first", 0).unwrap();
+	let before = ioctx.object_meta(&oid).unwrap();
+	assert_eq!(before.size, 5);
+	ioctx.write_at(&oid, b"second", 0).unwrap();
+	let after = ioctx.object_meta(&oid).unwrap();
+	assert_eq!(after.size, 6);
+	assert!(after.version > before.version);
+	ioctx.remove(&oid).unwrap();
+	assert_eq!(ioctx.object_meta(&oid).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn fetch_reads_everything_at_once() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("fetch");
+	// Larger than the initial buffer, so the object is read twice
+	let data = pattern(100 * 1024);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	ioctx.setxattr(oid.as_str(), "user.owner", "alice").unwrap();
+	ioctx.setxattr(oid.as_str(), "user.colour", "blue").unwrap();
+	let snapshot = ioctx.fetch(&oid).unwrap();
+	let (size, mtime) = ioctx.stat(&oid).unwrap();
+	let meta = ioctx.object_meta(&oid).unwrap();
+	assert_eq!(snapshot.data, data);
+	assert_eq!(snapshot.size, size);
+	assert_eq!(snapshot.mtime, mtime);
+	assert_eq!(snapshot.version, meta.version);
+	assert_eq!(snapshot.xattrs.len(), 2);
+	assert_eq!(snapshot.xattrs["user.owner"], b"alice");
+	assert_eq!(snapshot.xattrs["user.colour"], b"blue");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+#[cfg(feature = "luminous")]
+fn checksum_matches_crc32c() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("checksum");
+	let data = pattern(10000);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	let crc = |offset, len| {
+		let sum = ioctx.checksum(&oid, ChecksumType::Crc32c, offset, len).unwrap();
+		sum[0] as u32 | (sum[1] as u32) << 8 | (sum[2] as u32) << 16 | (sum[3] as u32) << 24
+	};
+	// Both start from 0xffffffff, but only crc32c::checksum() inverts
+	// the result at the end
+	assert_eq!(crc(0, 0), !crc32c::checksum(&data));
+	assert_eq!(crc(100, 1000), !crc32c::checksum(&data[100..1100]));
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn wait_for_size_sees_appends() {
+	let cluster = common::cluster();
+	let ioctx = Arc::new(common::ioctx(&cluster));
+	let oid = common::oid("growing");
+	let appender = {
+		let (ioctx, oid) = (ioctx.clone(), oid.clone());
+		thread::spawn(move || {
+			for i in 0..10 {
+				thread::sleep(Duration::from_millis(50));
+				ioctx.write_at(&oid, &[0u8; 100], i * 100).unwrap();
+			}
+		})
+	};
+	// Called before the object exists, which counts as empty
+	let size = ioctx.wait_for_size(&oid, 500, Duration::from_secs(30)).unwrap();
+	assert!((500..=1000).contains(&size), "size {}", size);
+	appender.join().unwrap();
+	assert_eq!(ioctx.wait_for_size(&oid, 1000, Duration::from_secs(0)).unwrap(), 1000);
+	// A timeout past the last Instant is no timeout
+	assert_eq!(ioctx.wait_for_size(&oid, 1000, Duration::new(u64::MAX, 0)).unwrap(), 1000);
+	assert_eq!(ioctx.wait_for_size(&oid, 1001, Duration::from_millis(100)).unwrap_err(), RadosError::Timeout);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn getxattr_grows_the_buffer() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("long-xattr");
+	let value = "v".repeat(10000);
+	ioctx.write_at(&oid, b"x", 0).unwrap();
+	ioctx.setxattr(oid.as_str(), "user.long", value.as_str()).unwrap();
+	// Far smaller than the value
+	assert_eq!(ioctx.getxattr(oid.as_str(), "user.long", 1).unwrap(), value);
+	assert_eq!(ioctx.object(&oid).getxattr("user.long", 0).unwrap(), value);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn omap_clear() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("omap-clear");
+	let mut op = WriteOp::new();
+	op.write_full(b"data").omap_set(&[("a", &b"1"[..]), ("b", &b"2"[..])]);
+	ioctx.operate(&oid, &op).unwrap();
+	assert_eq!(ioctx.export_omap(&oid).unwrap().len(), 2);
+	ioctx.omap_clear(&oid).unwrap();
+	assert!(ioctx.export_omap(&oid).unwrap().is_empty());
+	// Only the omap went
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn try_clone_namespaces_are_independent() {
+	let cluster = common::cluster();
+	let first = common::ioctx(&cluster);
+	let second = first.try_clone().unwrap();
+	first.set_namespace(Some(&common::oid("first"))).unwrap();
+	second.set_namespace(Some(&common::oid("second"))).unwrap();
+	first.write_at("object", b"first", 0).unwrap();
+	second.write_at("object", b"second", 0).unwrap();
+	assert_eq!(first.read_full("object").unwrap(), b"first");
+	assert_eq!(second.read_full("object").unwrap(), b"second");
+	first.remove("object").unwrap();
+	assert_eq!(first.stat("object").unwrap_err(), RadosError::ObjectNotFound);
+	assert_eq!(second.read_full("object").unwrap(), b"second");
+	second.remove("object").unwrap();
+}
+
+#[test]
+fn setxattrs_all_or_nothing() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("setxattrs");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	ioctx.setxattrs(&oid, &[("user.a", &b"1"[..]), ("user.b", &b"2"[..]), ("user.c", &b"3"[..])]).unwrap();
+	let xattrs = ioctx.getxattrs(&oid).unwrap();
+	assert_eq!(xattrs.len(), 3);
+	assert_eq!(xattrs["user.a"], b"1");
+	assert_eq!(xattrs["user.b"], b"2");
+	assert_eq!(xattrs["user.c"], b"3");
+
+	// One bad name and none of them is set
+	let err = ioctx.setxattrs(&oid, &[("user.a", &b"changed"[..]), ("user.d", &b"4"[..]), ("bad\0name", &b"5"[..])]);
+	assert_eq!(err.unwrap_err(), RadosError::Other(libc::EINVAL));
+	assert_eq!(ioctx.getxattrs(&oid).unwrap(), xattrs);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn create_full_in_category() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("category");
+	ioctx.create_full(&oid, b"data", true, Some("legacy")).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	assert_eq!(ioctx.create_full(&oid, b"other", true, Some("legacy")).unwrap_err(), RadosError::AlreadyExists);
+	ioctx.create_full(&oid, b"other", false, None).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"other");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn read_full_in_small_chunks() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("read-full-chunks");
+	let data = pattern(100 * 1024 + 1);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	ioctx.set_chunk_size(4096).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), data);
+	ioctx.remove(&oid).unwrap();
+}
+
+/// Counts the writes made to it, which `download()` makes one of per read
+struct CountingWriter {
+	data: Vec<u8>,
+	writes: usize,
+}
+
+impl Write for CountingWriter {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.writes += 1;
+		self.data.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn download_with_chunk_size() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("download-chunks");
+	let data = pattern(4 * 1024 * 1024);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	ioctx.set_chunk_size(64 * 1024).unwrap();
+	let mut out = CountingWriter { data: Vec::new(), writes: 0 };
+	assert_eq!(ioctx.download(&oid, &mut out, 0).unwrap(), data.len() as u64);
+	assert!(out.data == data);
+	assert_eq!(out.writes, 64);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn export_import_omap_round_trip() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("omap-round-trip");
+	// More than a page of entries, and an empty value
+	let entries: Vec<(String, Vec<u8>)> = (0..1500).map(|i| (format!("key{:05}", i), pattern(i % 50))).collect();
+	ioctx.import_omap(&oid, &entries).unwrap();
+	let exported = ioctx.export_omap(&oid).unwrap();
+	assert!(exported == entries);
+
+	ioctx.omap_clear(&oid).unwrap();
+	assert!(ioctx.export_omap(&oid).unwrap().is_empty());
+	ioctx.import_omap(&oid, &exported).unwrap();
+	assert!(ioctx.export_omap(&oid).unwrap() == entries);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn selfmanaged_snaps_keep_old_data() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("selfmanaged-snap");
+	ioctx.write_at(&oid, b"first", 0).unwrap();
+	let first = ioctx.selfmanaged_snap_create().unwrap();
+	ioctx.write_with_snap_context(&oid, b"second", 0, first, &[first]).unwrap();
+	let second = ioctx.selfmanaged_snap_create().unwrap();
+	assert!(second > first);
+	ioctx.write_with_snap_context(&oid, b"third!", 0, second, &[second, first]).unwrap();
+
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"third!");
+	ioctx.set_read_snap(Some(first));
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"first");
+	ioctx.set_read_snap(Some(second));
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"second");
+	ioctx.set_read_snap(None);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"third!");
+
+	ioctx.remove(&oid).unwrap();
+	ioctx.selfmanaged_snap_remove(second).unwrap();
+	ioctx.selfmanaged_snap_remove(first).unwrap();
+}
+
+#[test]
+fn getxattr_missing_object_or_attr() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("getxattr-missing");
+	assert_eq!(ioctx.getxattr(oid.as_str(), "user.name", 16).unwrap_err(), RadosError::ObjectNotFound);
+	assert_eq!(ioctx.object(&oid).getxattr("user.name", 16).unwrap_err(), RadosError::ObjectNotFound);
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	assert_eq!(ioctx.getxattr(oid.as_str(), "user.name", 16).unwrap_err(), RadosError::AttrNotFound);
+	assert_eq!(ioctx.object(&oid).getxattr("user.name", 16).unwrap_err(), RadosError::AttrNotFound);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn tags() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	// find_by_tag() lists the whole namespace
+	ioctx.set_namespace(Some(&common::oid("tags"))).unwrap();
+	for oid in &["a", "b", "c"] {
+		ioctx.write_at(oid, b"data", 0).unwrap();
+	}
+	assert!(ioctx.get_tags("a").unwrap().is_empty());
+	ioctx.set_tag("a", "red").unwrap();
+	ioctx.set_tag("a", "blue").unwrap();
+	// Already there
+	ioctx.set_tag("a", "red").unwrap();
+	ioctx.set_tag("b", "red").unwrap();
+	assert_eq!(ioctx.get_tags("a").unwrap(), ["red", "blue"]);
+	assert_eq!(ioctx.set_tag("missing", "red").unwrap_err(), RadosError::ObjectNotFound);
+
+	let mut red = ioctx.find_by_tag("red").unwrap();
+	red.sort();
+	assert_eq!(red, ["a", "b"]);
+	assert_eq!(ioctx.find_by_tag("blue").unwrap(), ["a"]);
+	assert!(ioctx.find_by_tag("green").unwrap().is_empty());
+
+	ioctx.remove_tag("a", "red").unwrap();
+	// Not there
+	ioctx.remove_tag("c", "red").unwrap();
+	assert_eq!(ioctx.get_tags("a").unwrap(), ["blue"]);
+	assert_eq!(ioctx.find_by_tag("red").unwrap(), ["b"]);
+	for oid in &["a", "b", "c"] {
+		ioctx.remove(oid).unwrap();
+	}
+}
+
+#[test]
+fn getxattrs_by_name_skips_missing() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("xattrs-by-name");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	ioctx.setxattrs(&oid, &[("user.a", &b"1"[..]), ("user.b", &b"2"[..]), ("user.c", &b"3"[..])]).unwrap();
+	let xattrs = ioctx.getxattrs_by_name(&oid, &["user.a", "user.c", "user.missing"]).unwrap();
+	assert_eq!(xattrs.len(), 2);
+	assert_eq!(xattrs["user.a"], b"1");
+	assert_eq!(xattrs["user.c"], b"3");
+	ioctx.remove(&oid).unwrap();
+	assert_eq!(ioctx.getxattrs_by_name(&oid, &["user.a"]).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn clone_object_copies_everything() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let (src, dst) = (common::oid("clone-src"), common::oid("clone-dst"));
+	let data = pattern(100 * 1024);
+	let mut op = WriteOp::new();
+	op.write_full(&data)
+		.setxattr("user.a", b"1")
+		.setxattr("user.b", b"2")
+		.omap_set(&[("x", &b"10"[..]), ("y", &b"20"[..]), ("z", &b"30"[..])]);
+	ioctx.operate(&src, &op).unwrap();
+
+	ioctx.clone_object(&src, &dst).unwrap();
+	assert!(ioctx.read_full(&dst).unwrap() == data);
+	assert_eq!(ioctx.getxattrs(&dst).unwrap(), ioctx.getxattrs(&src).unwrap());
+	assert_eq!(ioctx.getxattrs(&dst).unwrap().len(), 2);
+	let omap = ioctx.export_omap(&dst).unwrap();
+	assert_eq!(omap, [("x".to_string(), b"10".to_vec()), ("y".to_string(), b"20".to_vec()), ("z".to_string(), b"30".to_vec())]);
+	// The source is left as it was
+	assert!(ioctx.read_full(&src).unwrap() == data);
+	assert_eq!(ioctx.export_omap(&src).unwrap(), omap);
+
+	assert_eq!(ioctx.clone_object(&common::oid("missing"), &dst).unwrap_err(), RadosError::ObjectNotFound);
+	ioctx.remove(&src).unwrap();
+	ioctx.remove(&dst).unwrap();
+}
+
+#[test]
+fn append_capped() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("append-capped");
+	assert_eq!(ioctx.append_capped(&oid, b"0123", 10).unwrap(), 4);
+	assert_eq!(ioctx.append_capped(&oid, b"456789", 10).unwrap(), 10);
+	assert_eq!(ioctx.append_capped(&oid, b"a", 10).unwrap_err(), RadosError::QuotaExceeded);
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"0123456789");
+	ioctx.remove(&oid).unwrap();
+	// Nor is a missing object created past the cap
+	assert_eq!(ioctx.append_capped(&oid, b"0123", 3).unwrap_err(), RadosError::QuotaExceeded);
+	assert_eq!(ioctx.stat(&oid).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn purge_only_empties_the_namespace() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let other = common::ioctx(&cluster);
+	ioctx.set_namespace(Some(&common::oid("purged"))).unwrap();
+	other.set_namespace(Some(&common::oid("kept"))).unwrap();
+	for i in 0..10 {
+		ioctx.write_at(&format!("object{}", i), b"data", 0).unwrap();
+	}
+	other.write_at("object0", b"data", 0).unwrap();
+	assert_eq!(ioctx.purge().unwrap(), 10);
+	assert_eq!(ioctx.list_objects().unwrap().count(), 0);
+	assert_eq!(ioctx.purge().unwrap(), 0);
+	assert_eq!(other.read_full("object0").unwrap(), b"data");
+	other.remove("object0").unwrap();
+}
+
+#[test]
+fn read_exact_into() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("read-exact");
+	let data = pattern(10000);
+	ioctx.write_at(&oid, &data, 0).unwrap();
+	let mut buf = vec![0u8; 10000];
+	ioctx.read_exact_into(&oid, 0, &mut buf).unwrap();
+	assert!(buf == data);
+	let mut buf = [0u8; 100];
+	ioctx.read_exact_into(&oid, 9900, &mut buf).unwrap();
+	assert_eq!(&buf[..], &data[9900..]);
+	let mut buf = vec![0u8; 10001];
+	assert_eq!(ioctx.read_exact_into(&oid, 0, &mut buf).unwrap_err(), RadosError::UnexpectedEof);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn sync() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("sync");
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	ioctx.sync(&oid).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	ioctx.remove(&oid).unwrap();
+	assert_eq!(ioctx.sync(&oid).unwrap_err(), RadosError::ObjectNotFound);
+	// Without creating it
+	assert_eq!(ioctx.stat(&oid).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn remove_with_version() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("remove-with");
+	ioctx.write_at(&oid, b"first", 0).unwrap();
+	let stale = ioctx.object_meta(&oid).unwrap().version;
+	ioctx.write_at(&oid, b"second", 0).unwrap();
+	let current = ioctx.object_meta(&oid).unwrap().version;
+
+	let mut op = WriteOp::new();
+	op.assert_version(stale);
+	assert_eq!(ioctx.remove_with(&oid, &mut op, WriteFlags::empty()).unwrap_err(), RadosError::Other(libc::ERANGE));
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"second");
+
+	let mut op = WriteOp::new();
+	op.assert_version(current);
+	ioctx.remove_with(&oid, &mut op, WriteFlags::empty()).unwrap();
+	assert_eq!(ioctx.stat(&oid).unwrap_err(), RadosError::ObjectNotFound);
+}
+
+#[test]
+fn create_or_truncate() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let (missing, populated) = (common::oid("truncate-missing"), common::oid("truncate-populated"));
+	ioctx.create_or_truncate(&missing).unwrap();
+	assert_eq!(ioctx.stat(&missing).unwrap().0, 0);
+
+	ioctx.write_at(&populated, &pattern(1000), 0).unwrap();
+	ioctx.setxattr(populated.as_str(), "user.kept", "value").unwrap();
+	ioctx.create_or_truncate(&populated).unwrap();
+	assert_eq!(ioctx.stat(&populated).unwrap().0, 0);
+	assert_eq!(ioctx.getxattr(populated.as_str(), "user.kept", 16).unwrap(), "value");
+	ioctx.remove(&missing).unwrap();
+	ioctx.remove(&populated).unwrap();
+}
+
+#[test]
+fn set_full_try() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("full-try");
+	ioctx.set_full_try(true);
+	ioctx.write_at(&oid, b"data", 0).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"data");
+	ioctx.set_full_try(false);
+	ioctx.write_at(&oid, b"more", 4).unwrap();
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"datamore");
+	// Turning it off twice is fine too
+	ioctx.set_full_try(false);
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn upload_parallel() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("upload-parallel");
+	let data = pattern(16 * 1024 * 1024);
+	assert_eq!(ioctx.upload_parallel(&oid, &data[..], 1024 * 1024, 4).unwrap(), data.len() as u64);
+	assert_eq!(ioctx.stat(&oid).unwrap().0, data.len() as u64);
+	assert!(ioctx.read_full(&oid).unwrap() == data);
+	assert_eq!(ioctx.upload_parallel(&oid, &data[..], 1024 * 1024, 0).unwrap_err(), RadosError::Other(libc::EINVAL));
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn write_record() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("record");
+	let data = pattern(5000);
+	let mtime = UNIX_EPOCH + Duration::from_secs(1500000000);
+	ioctx.write_record(&oid, &data, mtime).unwrap();
+	assert!(ioctx.read_full(&oid).unwrap() == data);
+	let crc = ioctx.getxattrs(&oid).unwrap().remove(RECORD_CRC_XATTR).unwrap();
+	assert_eq!(crc, format!("{:08x}", crc32c::checksum(&data)).into_bytes());
+	assert_eq!(ioctx.stat(&oid).unwrap(), (5000, mtime));
+	assert_eq!(ioctx.write_record(&oid, &data, UNIX_EPOCH - Duration::from_secs(1)).unwrap_err(), RadosError::Other(libc::EINVAL));
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn namespace_stats() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let (first, second) = (common::oid("stats-first"), common::oid("stats-second"));
+	ioctx.set_namespace(Some(&first)).unwrap();
+	for i in 0..3 {
+		ioctx.write_at(&format!("object{}", i), &pattern(100), 0).unwrap();
+	}
+	ioctx.set_namespace(Some(&second)).unwrap();
+	ioctx.write_at("object0", &pattern(1000), 0).unwrap();
+
+	assert_eq!(ioctx.namespace_stats(&first).unwrap(), NamespaceStats { objects: 3, bytes: 300 });
+	assert_eq!(ioctx.namespace_stats(&second).unwrap(), NamespaceStats { objects: 1, bytes: 1000 });
+	assert_eq!(ioctx.namespace_stats(&common::oid("stats-empty")).unwrap(), NamespaceStats::default());
+	// The namespace of the context is left alone
+	assert_eq!(ioctx.namespace().unwrap(), second);
+	ioctx.purge().unwrap();
+	ioctx.set_namespace(Some(&first)).unwrap();
+	ioctx.purge().unwrap();
+}
+
+#[test]
+fn write_if_smaller_than() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("smaller-than");
+	ioctx.write_at(&oid, b"123456789", 0).unwrap();
+	// 9 bytes, just under the threshold
+	assert!(ioctx.write_if_smaller_than(&oid, b"0123456789", 10).unwrap());
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"0123456789");
+	// 10 bytes, at the threshold
+	assert!(!ioctx.write_if_smaller_than(&oid, b"new", 10).unwrap());
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"0123456789");
+	ioctx.remove(&oid).unwrap();
+}
+
+#[test]
+fn record_file() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let records: RecordFile<8> = RecordFile::new(&ioctx, &common::oid("records")).unwrap();
+	assert_eq!(records.len().unwrap(), 0);
+	assert!(records.is_empty().unwrap());
+	for i in 0..10u64 {
+		assert_eq!(records.append(&(i * 100).to_le_bytes()).unwrap(), i);
+	}
+	assert_eq!(records.len().unwrap(), 10);
+	for &i in &[7u64, 0, 9, 3, 3, 5] {
+		assert_eq!(records.get(i).unwrap(), (i * 100).to_le_bytes());
+	}
+	assert_eq!(records.get(10).unwrap_err(), RadosError::UnexpectedEof);
+	assert_eq!(records.get(u64::MAX).unwrap_err(), RadosError::UnexpectedEof);
+	ioctx.remove(records.oid()).unwrap();
+}
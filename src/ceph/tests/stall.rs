@@ -0,0 +1,52 @@
+//! Tests against a stalled cluster, only built with the `integration`
+//! feature. They pause all client I/O with `ceph osd pause`, so they're
+//! kept apart from the other tests, which would stall with them.
+
+#![cfg(feature = "integration")]
+
+extern crate ceph;
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use ceph::rados::{Cluster, RadosError};
+
+/// Pauses the OSDs for as long as it lives, unpausing them even when the
+/// test fails
+struct Paused<'a>(&'a Cluster);
+
+impl<'a> Paused<'a> {
+	fn new(cluster: &'a Cluster) -> Paused<'a> {
+		cluster.mon_command(r#"{"prefix": "osd pause"}"#).unwrap();
+		Paused(cluster)
+	}
+}
+
+impl<'a> Drop for Paused<'a> {
+	fn drop(&mut self) {
+		self.0.mon_command(r#"{"prefix": "osd unpause"}"#).unwrap();
+	}
+}
+
+#[test]
+fn op_timeout_on_stalled_osds() {
+	let cluster = common::cluster();
+	let ioctx = common::ioctx(&cluster);
+	let oid = common::oid("stalled");
+	ioctx.write_at(&oid, b"before", 0).unwrap();
+	ioctx.set_op_timeout(Duration::from_secs(2));
+	{
+		let _paused = Paused::new(&cluster);
+		let start = Instant::now();
+		assert_eq!(ioctx.write_at(&oid, b"during", 0).unwrap_err(), RadosError::Timeout);
+		let mut buf = [0u8; 6];
+		assert_eq!(ioctx.read_at(&oid, &mut buf, 0).unwrap_err(), RadosError::Timeout);
+		let elapsed = start.elapsed();
+		assert!(elapsed >= Duration::from_secs(4), "timed out after {:?}", elapsed);
+		assert!(elapsed < Duration::from_secs(10), "timed out after {:?}", elapsed);
+	}
+	// The canceled write never happened
+	assert_eq!(ioctx.read_full(&oid).unwrap(), b"before");
+	ioctx.remove(&oid).unwrap();
+}
@@ -3,14 +3,14 @@ extern crate ceph;
 use std::env;
 
 use ceph::rados::version;
-use ceph::rados::Cluster;
+use ceph::rados::{Cluster, CreateFlags};
 
 fn main() {
 	let args: Vec<_> = env::args().collect();
     println!("{}: rados::version() => {}", args[0], ceph::rados::version());
 
     /* Initialize the cluster handle with a 'null' cluster name and the "client.admin" user */
-	let cluster = Cluster::create(None, "client.admin", 0).unwrap_or_else(|e|
+	let cluster = Cluster::create(None, "client.admin", CreateFlags::empty()).unwrap_or_else(|e|
 		panic!(format!("{}: Couldn't create the cluster handle! {}", args[0], e))
 	);
     println!("Created a cluster handle: {:?}", cluster);